@@ -1,6 +1,10 @@
 use std::error::Error;
 
 fn main() -> Result<(), Box<dyn Error>> {
+    // Prefer an explicitly configured protoc, fall back to the vendored binary otherwise.
+    if std::env::var_os("PROTOC").is_none() {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
     tonic_build::configure().compile_protos(&["hailstorm.proto"], &["proto"])?;
 
     Ok(())
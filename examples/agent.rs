@@ -63,14 +63,14 @@ async fn main() {
             let mut ctx =
                 rune::Context::with_default_modules().expect("Error loading default rune modules");
             ctx.install(
-                &extension::storage::module(
+                extension::storage::module(
                     StorageModuleArgs::default().with_initializer(EmptyInitializer),
                 )
                 .expect("Error initializing storage extension module"),
             )
             .expect("Error loading storage extension module");
             ctx.install(
-                &extension::env::module(EnvModuleConf::default().with_prefix("hsa"))
+                extension::env::module(EnvModuleConf::default().with_prefix("hsa"))
                     .expect("Error initializing env extension module"),
             )
             .expect("Error loading env extension module");
@@ -78,7 +78,8 @@ async fn main() {
             ctx
         })
         .launch_grpc()
-        .await;
+        .await
+        .expect("Error running agent grpc server");
 }
 
 #[cfg(test)]
@@ -62,9 +62,12 @@ async fn initialize_agents(
                 CommandItem {
                     command: Some(Command::Launch(LaunchCommand {
                         start_ts: Some(start_ts.into()),
+                        end_ts: None,
+                        simulation_id: String::new(),
                     })),
                 },
             ],
+            command_id: 0,
         })
         .await
         .expect("Error sending load command");
@@ -120,6 +123,7 @@ async fn handle_messages(
                                     registered_agents.len() as u32
                                 )),
                             }],
+                            command_id: 0,
                         })
                         .await
                         .expect("Error sending UpdateAgentsCount")
@@ -171,9 +175,17 @@ async fn main() {
             clients_evolution: config
                 .clients_distribution
                 .into_iter()
-                .map(|(model, shape)| ClientDistribution { model, shape })
+                .map(|(model, shape)| ClientDistribution {
+                    model,
+                    shape,
+                    stages: vec![],
+                    series: None,
+                    arrival_rate: None,
+                })
                 .collect(),
             script: fs::read_to_string(config.script_path).expect("Error loading script file"),
+            phases: vec![],
+            simulation_id: String::new(),
         }),
     };
     Server::builder()
@@ -0,0 +1,197 @@
+use clap::{Parser, ValueEnum};
+use hailstorm::agent::builder::SimulationParams;
+use hailstorm::simulation::preview::ShapePreview;
+use hailstorm::simulation::series::{Interpolation, TimeSeries};
+use std::fs::File;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(ValueEnum, Clone, Debug)]
+enum Format {
+    Chart,
+    Csv,
+    Json,
+}
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about = "Preview the bots expected by a load shape", long_about = None)]
+struct Args {
+    /// Shape expression to sample (e.g. "ln(1 + t/1000) * 1000")
+    #[clap(short, long, value_parser, required_unless_present = "series")]
+    shape: Option<String>,
+
+    /// Recorded traffic curve to sample instead of the shape, a `.json` or `.csv` file of `t`
+    /// and `value` points
+    #[clap(long, value_parser, conflicts_with = "shape")]
+    series: Option<PathBuf>,
+
+    /// Hold each recorded value until the next point instead of interpolating linearly
+    #[clap(long, value_parser)]
+    step_interpolation: bool,
+
+    /// Factor applied to the recorded timestamps
+    #[clap(long, value_parser, default_value_t = 1.0)]
+    time_scale: f64,
+
+    /// Factor applied to the recorded values
+    #[clap(long, value_parser, default_value_t = 1.0)]
+    amplitude_scale: f64,
+
+    /// Sampled duration in seconds
+    #[clap(short, long, value_parser, default_value_t = 600)]
+    duration: u64,
+
+    /// Interval between two samples in seconds
+    #[clap(long, value_parser = parse_step, default_value = "10")]
+    step: Duration,
+
+    /// Value of the `duration` shape variable in seconds, infinite if missing
+    #[clap(long, value_parser)]
+    phase_duration: Option<u64>,
+
+    /// Wall-clock time (UTC) at the preview start as `HH:MM`, for shapes using `tod`
+    #[clap(long, value_parser = parse_time_of_day)]
+    start_time: Option<Duration>,
+
+    /// Agent limits as `max_running=<n>,max_rate=<n>,weight=<w>`, repeat for each agent.
+    /// Empty for an agent without limits, a single unbounded agent is used if missing
+    #[clap(short, long, value_parser = parse_agent)]
+    agent: Vec<AgentArg>,
+
+    /// Output format
+    #[clap(short, long, value_enum, default_value_t = Format::Chart)]
+    format: Format,
+
+    /// Chart width in columns
+    #[clap(long, value_parser, default_value_t = 80)]
+    width: usize,
+
+    /// Chart height in rows
+    #[clap(long, value_parser, default_value_t = 20)]
+    height: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+struct AgentArg {
+    max_running: Option<usize>,
+    max_rate: Option<usize>,
+    weight: Option<f64>,
+}
+
+impl From<AgentArg> for SimulationParams {
+    fn from(agent: AgentArg) -> Self {
+        let mut params = SimulationParams::default();
+        if let Some(max_running) = agent.max_running {
+            params = params.max_running(max_running);
+        }
+        if let Some(max_rate) = agent.max_rate {
+            params = params.max_rate(max_rate);
+        }
+        if let Some(weight) = agent.weight {
+            params = params.weight(weight);
+        }
+        params
+    }
+}
+
+fn parse_agent(arg: &str) -> Result<AgentArg, String> {
+    let mut agent = AgentArg::default();
+    for item in arg.split(',').filter(|item| !item.trim().is_empty()) {
+        let (key, value) = item
+            .split_once('=')
+            .ok_or_else(|| format!("expected <key>=<value>, found '{item}'"))?;
+        let value = value.trim();
+        match key.trim() {
+            "max_running" => agent.max_running = Some(value.parse().map_err(|e| format!("{e}"))?),
+            "max_rate" => agent.max_rate = Some(value.parse().map_err(|e| format!("{e}"))?),
+            "weight" => agent.weight = Some(value.parse().map_err(|e| format!("{e}"))?),
+            other => return Err(format!("unknown agent limit '{other}'")),
+        }
+    }
+    Ok(agent)
+}
+
+fn parse_step(arg: &str) -> Result<Duration, String> {
+    let secs: f64 = arg.trim().parse().map_err(|e| format!("{e}"))?;
+    if secs.is_nan() || secs <= 0.0 {
+        return Err(format!(
+            "expected a positive number of seconds, found '{arg}'"
+        ));
+    }
+    Duration::try_from_secs_f64(secs).map_err(|e| format!("{e}"))
+}
+
+fn parse_time_of_day(arg: &str) -> Result<Duration, String> {
+    let (hours, minutes) = arg
+        .split_once(':')
+        .ok_or_else(|| format!("expected HH:MM, found '{arg}'"))?;
+    let hours: u64 = hours.trim().parse().map_err(|e| format!("{e}"))?;
+    let minutes: u64 = minutes.trim().parse().map_err(|e| format!("{e}"))?;
+    if hours >= 24 || minutes >= 60 {
+        return Err(format!("invalid time of day '{arg}'"));
+    }
+    Ok(Duration::from_secs(hours * 3600 + minutes * 60))
+}
+
+fn load_series(args: &Args, path: &PathBuf) -> Result<TimeSeries, String> {
+    let file = File::open(path).map_err(|e| format!("{e}"))?;
+    let series = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => TimeSeries::from_json(file),
+        _ => TimeSeries::from_csv(file),
+    }
+    .map_err(|e| format!("{e}"))?;
+    let interpolation = if args.step_interpolation {
+        Interpolation::Step
+    } else {
+        Interpolation::Linear
+    };
+    Ok(series
+        .interpolation(interpolation)
+        .time_scale(args.time_scale)
+        .amplitude_scale(args.amplitude_scale))
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let preview = match (&args.series, &args.shape) {
+        (Some(path), _) => match load_series(&args, path) {
+            Ok(series) => ShapePreview::series(series),
+            Err(err) => {
+                eprintln!("Invalid series {} - {err}", path.display());
+                std::process::exit(1);
+            }
+        },
+        (None, Some(shape)) => ShapePreview::new(shape),
+        (None, None) => unreachable!("shape is required without series"),
+    };
+    let mut preview = preview
+        .duration(Duration::from_secs(args.duration))
+        .step(args.step);
+    if let Some(phase_duration) = args.phase_duration {
+        preview = preview.phase_duration(Duration::from_secs(phase_duration));
+    }
+    if let Some(start_time) = args.start_time {
+        preview = preview.time_of_day(start_time);
+    }
+    if !args.agent.is_empty() {
+        preview = preview.agents(args.agent.into_iter().map(Into::into).collect());
+    }
+
+    let report = match preview.sample() {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("Invalid shape - {err}");
+            std::process::exit(1);
+        }
+    };
+
+    match args.format {
+        Format::Chart => print!("{}", report.to_ascii_chart(args.width, args.height)),
+        Format::Csv => print!("{}", report.to_csv()),
+        Format::Json => println!(
+            "{}",
+            report.to_json().expect("Error serializing preview report")
+        ),
+    }
+}
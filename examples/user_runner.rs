@@ -2,7 +2,7 @@ use actix::{Actor, Context, Handler};
 use clap::Parser;
 use hailstorm::agent::metrics::manager::actor::MetricsManagerActor;
 use hailstorm::simulation::actor::bot::BotActor;
-use hailstorm::simulation::actor::simulation::BotStateChange;
+use hailstorm::simulation::actor::simulation::{BotStateChange, BotTicksSkipped};
 use hailstorm::simulation::bot::registry::BotRegistry;
 use hailstorm::simulation::compound_id::CompoundId;
 use hailstorm::simulation::rune::extension;
@@ -26,6 +26,14 @@ impl Handler<BotStateChange> for StateChangeLoggerActor {
     }
 }
 
+impl Handler<BotTicksSkipped> for StateChangeLoggerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: BotTicksSkipped, _ctx: &mut Self::Context) -> Self::Result {
+        log::warn!("bot skipped {} interval ticks", msg.count)
+    }
+}
+
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
@@ -49,7 +57,7 @@ async fn main() {
         rune::Context::with_default_modules().expect("Error loading default rune modules");
     rune_ctx
         .install(
-            &extension::storage::module(
+            extension::storage::module(
                 StorageModuleArgs::default().with_initializer(EmptyInitializer),
             )
             .expect("Error initializing storage extension module"),
@@ -57,7 +65,7 @@ async fn main() {
         .expect("Error loading storage extension module");
     rune_ctx
         .install(
-            &extension::env::module(EnvModuleConf::default().with_prefix("hsa"))
+            extension::env::module(EnvModuleConf::default().with_prefix("hsa"))
                 .expect("Error initializing env extension module"),
         )
         .expect("Error loading env extension module");
@@ -2,8 +2,11 @@ use std::collections::HashMap;
 use std::ops::Add;
 use std::time::{Duration, SystemTime};
 
+use crate::agent::metrics::custom::CustomMetricSnapshot;
 use crate::agent::metrics::manager::actor::MetricsManagerActor;
-use crate::agent::metrics::manager::message::{ActionMetricsFamilySnapshot, FetchActionMetrics};
+use crate::agent::metrics::manager::message::{
+    ActionMetricsFamilySnapshot, FetchActionMetrics, FetchCustomMetrics,
+};
 use actix::dev::Request;
 use actix::{
     Actor, ActorFutureExt, ActorTryFutureExt, Addr, AsyncContext, Context, Handler, MailboxError,
@@ -14,17 +17,24 @@ use futures::{join, StreamExt};
 use rand::Rng;
 use tokio::sync::mpsc::Receiver;
 
-use crate::communication::message::{ControllerCommandMessage, SendAgentMessage};
+use crate::communication::message::{
+    ControllerCommandMessage, MultiCommandAckMessage, SendAgentMessage,
+};
 use crate::communication::notifier_actor::{RegisterAgentUpdateSender, UpdatesNotifierActor};
 use crate::communication::protobuf::grpc;
 use crate::communication::protobuf::grpc::command_item::Command;
-use crate::communication::protobuf::grpc::{AgentUpdate, ControllerCommand};
-use crate::communication::protobuf::grpc::{ModelStateSnapshot, ModelStats, StopCommand};
+use crate::communication::protobuf::grpc::{AgentUpdate, CommandAck, ControllerCommand};
+use crate::communication::protobuf::grpc::{
+    CustomMetric, ModelStateSnapshot, ModelStats, StopCommand,
+};
 use crate::simulation::actor::bot::BotState;
 use crate::simulation::actor::simulation::{
-    ClientStats, FetchSimulationStats, SimulationActor, SimulationCommand, SimulationCommandLst,
-    SimulationState, SimulationStats,
+    ClientStats, FetchSimulationStats, PhaseShapes, SimulationActor, SimulationCommand,
+    SimulationCommandLst, SimulationState, SimulationStats,
 };
+use crate::simulation::error::SimulationError;
+use crate::simulation::executor::ExecutionMode;
+use crate::simulation::shape::ModelShape;
 use crate::utils::actix::synchro_context::WeakContext;
 use crate::MultiAgentUpdateMessage;
 
@@ -38,6 +48,9 @@ struct AggregatedBotStateMetric {
 /// Actor representing the core hailstorm instance
 pub struct AgentCoreActor {
     agent_id: u32,
+    name: String,
+    labels: HashMap<String, String>,
+    capacity: Option<grpc::AgentCapacity>,
     notifier_addr: Addr<UpdatesNotifierActor>,
     cmd_recipient: Recipient<ControllerCommandMessage>,
     simulation_addr: Addr<SimulationActor>,
@@ -58,6 +71,9 @@ impl AgentCoreActor {
     {
         Self {
             agent_id,
+            name: String::new(),
+            labels: HashMap::new(),
+            capacity: None,
             notifier_addr,
             cmd_recipient: server_addr.recipient(),
             simulation_addr,
@@ -66,30 +82,60 @@ impl AgentCoreActor {
         }
     }
 
+    /// Name reported to the controller in the agent updates
+    pub(crate) fn name(self, name: String) -> Self {
+        Self { name, ..self }
+    }
+
+    /// Labels reported to the controller and matched by label-targeted commands
+    pub(crate) fn labels(self, labels: HashMap<String, String>) -> Self {
+        Self { labels, ..self }
+    }
+
+    /// Capacity reported to the controller to weight the share of load of this agent
+    pub(crate) fn capacity(self, capacity: grpc::AgentCapacity) -> Self {
+        Self {
+            capacity: Some(capacity),
+            ..self
+        }
+    }
+
     fn fetch_perf_data(&mut self) -> Request<MetricsManagerActor, FetchActionMetrics> {
         self.metrics_addr.send(FetchActionMetrics)
     }
 
+    fn fetch_custom_data(&mut self) -> Request<MetricsManagerActor, FetchCustomMetrics> {
+        self.metrics_addr.send(FetchCustomMetrics)
+    }
+
     fn fetch_state_data(&mut self) -> Request<SimulationActor, FetchSimulationStats> {
         self.simulation_addr.send(FetchSimulationStats)
     }
 
     fn send_data(&mut self, ctx: &mut actix::Context<Self>) {
         let agent_id = self.agent_id;
+        let name = self.name.clone();
+        let labels = self.labels.clone();
+        let capacity = self.capacity;
         let notifier_addr = self.notifier_addr.clone();
         let fetch_perf_req = self.fetch_perf_data();
+        let fetch_custom_req = self.fetch_custom_data();
         let fetch_state_req = self.fetch_state_data();
 
         let fut = async move {
-            let (perf_res, state_res) = join!(fetch_perf_req, fetch_state_req);
-            { Ok((perf_res?, state_res?)) }.map_err(|err: MailboxError| {
+            let (perf_res, custom_res, state_res) =
+                join!(fetch_perf_req, fetch_custom_req, fetch_state_req);
+            { Ok((perf_res?, custom_res?, state_res?)) }.inspect_err(|err: &MailboxError| {
                 log::error!("Error fetching stats - {err}");
-                err
             })
         }
         .into_actor(self)
         .and_then(
-            move |(in_perf, in_stats): (Vec<ActionMetricsFamilySnapshot>, SimulationStats),
+            move |(in_perf, in_custom, in_stats): (
+                Vec<ActionMetricsFamilySnapshot>,
+                Vec<CustomMetricSnapshot>,
+                SimulationStats,
+            ),
                   act,
                   _ctx| {
                 let state = match in_stats.state {
@@ -97,12 +143,17 @@ impl AgentCoreActor {
                     SimulationState::Ready => grpc::AgentSimulationState::Ready,
                     SimulationState::Waiting => grpc::AgentSimulationState::Waiting,
                     SimulationState::Running => grpc::AgentSimulationState::Running,
+                    SimulationState::Stopping => grpc::AgentSimulationState::Stopping,
+                    SimulationState::Finished => grpc::AgentSimulationState::Finished,
                 };
 
                 let model_states = act
                     .update_simulation_stats(in_stats.stats, in_stats.timestamp)
                     .into_iter()
-                    .map(|cs| (cs.model.clone(), ModelStateSnapshot::from(cs)))
+                    .map(|cs| {
+                        let counters = (cs.dropped_iterations, cs.skipped_ticks);
+                        (cs.model.clone(), (ModelStateSnapshot::from(cs), counters))
+                    })
                     .collect::<HashMap<_, _>>();
 
                 notifier_addr
@@ -110,21 +161,30 @@ impl AgentCoreActor {
                         agent_id,
                         stats: model_states
                             .into_iter()
-                            .map(|(model, v)| ModelStats {
+                            .map(|(model, (v, counters))| ModelStats {
                                 states: vec![v],
+                                dropped_iterations: counters.0,
+                                skipped_ticks: counters.1,
                                 performance: in_perf
                                     .iter()
                                     .filter(|p| p.key.model.eq(&model))
                                     .flat_map(|metr_fam| metr_fam.to_protobuf())
                                     .collect(),
+                                custom_metrics: in_custom
+                                    .iter()
+                                    .filter(|metr| metr.key.model.eq(&model))
+                                    .map(CustomMetric::from)
+                                    .collect(),
                                 model,
                             })
                             .collect(),
                         update_id: rand::rng().random(),
                         timestamp: Some(SystemTime::now().into()),
-                        name: "".to_string(),
+                        name,
                         state: state as i32,
-                        simulation_id: "".to_string(),
+                        simulation_id: in_stats.simulation_id,
+                        labels,
+                        capacity,
                     }]))
                     .unwrap_or_else(|err| {
                         log::error!("Error sending agent stats to notifier actor {err}");
@@ -161,37 +221,32 @@ impl AgentCoreActor {
                         client.count_by_state.insert(m.state, 0);
                     });
 
-                client.count_by_state = client
-                    .count_by_state
-                    .into_iter()
-                    .filter(|(state, count)| {
-                        if let Some(sent_metrics) = self
-                            .last_sent_metrics
-                            .iter_mut()
-                            .find(|m| m.model.eq(&client.model) && m.state.eq(state))
+                client.count_by_state.retain(|state, count| {
+                    if let Some(sent_metrics) = self
+                        .last_sent_metrics
+                        .iter_mut()
+                        .find(|m| m.model.eq(&client.model) && m.state.eq(state))
+                    {
+                        if *count != sent_metrics.count
+                            || (*count > 0
+                                && sent_metrics.timestamp.add(Duration::from_secs(25)) < timestamp)
                         {
-                            if *count != sent_metrics.count
-                                || (*count > 0
-                                    && sent_metrics.timestamp.add(Duration::from_secs(25))
-                                        < timestamp)
-                            {
-                                sent_metrics.count = *count;
-                                sent_metrics.timestamp = timestamp;
-                                true
-                            } else {
-                                false
-                            }
-                        } else {
-                            self.last_sent_metrics.push(AggregatedBotStateMetric {
-                                timestamp,
-                                model: client.model.clone(),
-                                state: *state,
-                                count: *count,
-                            });
+                            sent_metrics.count = *count;
+                            sent_metrics.timestamp = timestamp;
                             true
+                        } else {
+                            false
                         }
-                    })
-                    .collect();
+                    } else {
+                        self.last_sent_metrics.push(AggregatedBotStateMetric {
+                            timestamp,
+                            model: client.model.clone(),
+                            state: *state,
+                            count: *count,
+                        });
+                        true
+                    }
+                });
                 client
             })
             .collect()
@@ -239,34 +294,98 @@ impl ConnectedClientMessage {
     }
 }
 
+fn to_system_time(ts: prost_types::Timestamp) -> Option<SystemTime> {
+    ts.try_into()
+        .map_err(|err| log::error!("Error converting timestamp to systemtime - {err}"))
+        .ok()
+}
+
 impl From<&Command> for Option<SimulationCommand> {
     fn from(cmd: &Command) -> Self {
         match cmd {
-            Command::Load(load) => Some(SimulationCommand::LoadSimulation {
-                model_shapes: load
-                    .clients_evolution
-                    .iter()
-                    .map(|cd| (cd.model.clone(), cd.shape.clone()))
-                    .collect(),
-                script: load.script.clone(),
-            }),
-            Command::Launch(launch) => launch
-                .start_ts
-                .clone()
-                .and_then(|ts| {
-                    ts.try_into()
-                        .map_err(|err| {
-                            log::error!("Error converting timestamp to systemtime - {err}")
+            Command::Load(load) => {
+                let model_shapes = |clients_evolution: &[grpc::ClientDistribution]| {
+                    clients_evolution
+                        .iter()
+                        .map(|cd| (cd.model.clone(), ModelShape::from(cd)))
+                        .collect()
+                };
+                let model_executions = |clients_evolution: &[grpc::ClientDistribution]| {
+                    clients_evolution
+                        .iter()
+                        .map(|cd| (cd.model.clone(), ExecutionMode::from(cd)))
+                        .collect()
+                };
+                let phases = if load.phases.is_empty() {
+                    vec![PhaseShapes {
+                        name: String::new(),
+                        duration: None,
+                        model_shapes: model_shapes(&load.clients_evolution),
+                        model_executions: model_executions(&load.clients_evolution),
+                    }]
+                } else {
+                    load.phases
+                        .iter()
+                        .map(|phase| PhaseShapes {
+                            name: phase.name.clone(),
+                            duration: phase.duration.and_then(|duration| duration.try_into().ok()),
+                            model_shapes: model_shapes(&phase.clients_evolution),
+                            model_executions: model_executions(&phase.clients_evolution),
                         })
-                        .ok()
+                        .collect()
+                };
+                Some(SimulationCommand::LoadSimulation {
+                    simulation_id: load.simulation_id.clone(),
+                    phases,
+                    script: load.script.clone(),
                 })
-                .map(|start_ts| SimulationCommand::LaunchSimulation { start_ts }),
+            }
+            Command::Launch(launch) => launch.start_ts.and_then(to_system_time).map(|start_ts| {
+                SimulationCommand::LaunchSimulation {
+                    simulation_id: launch.simulation_id.clone(),
+                    start_ts,
+                    end_ts: launch.end_ts.and_then(to_system_time),
+                }
+            }),
+            Command::Phase(phase) => phase.start_ts.and_then(to_system_time).map(|start_ts| {
+                SimulationCommand::EnterPhase {
+                    index: phase.index as usize,
+                    start_ts,
+                }
+            }),
             Command::UpdateAgentsCount(count) => {
                 Some(SimulationCommand::UpdateAgentsCount { count: *count })
             }
-            Command::Stop(StopCommand { reset }) => {
-                Some(SimulationCommand::StopSimulation { reset: *reset })
+            Command::Ordinals(ordinals) => Some(SimulationCommand::UpdateAgentOrdinals {
+                ordinals: ordinals.ordinals.clone(),
+                weights: ordinals.weights.clone(),
+            }),
+            Command::Stop(StopCommand { reset, abort }) => {
+                Some(SimulationCommand::StopSimulation {
+                    reset: *reset,
+                    abort: *abort,
+                })
+            }
+        }
+    }
+}
+
+impl From<SimulationError> for grpc::CommandError {
+    fn from(err: SimulationError) -> Self {
+        let (kind, model) = match &err {
+            SimulationError::Script(_) => (grpc::CommandErrorKind::InvalidScript, String::new()),
+            SimulationError::BadShape(_) => (grpc::CommandErrorKind::BadShape, String::new()),
+            SimulationError::BadModelShape { model, .. } => {
+                (grpc::CommandErrorKind::BadShape, model.clone())
+            }
+            SimulationError::UnknownModel(model) => {
+                (grpc::CommandErrorKind::UnknownModel, model.clone())
             }
+        };
+        Self {
+            kind: kind.into(),
+            message: err.to_string(),
+            model,
         }
     }
 }
@@ -289,22 +408,41 @@ impl Handler<ConnectedClientMessage> for AgentCoreActor {
 
         let sim_addr = self.simulation_addr.clone();
         let server_addr = self.cmd_recipient.clone();
+        let notifier_addr = self.notifier_addr.clone();
         let agent_id = self.agent_id;
+        let simulation_id = message
+            .commands
+            .iter()
+            .find_map(|ci| match &ci.command {
+                Some(Command::Load(load)) => Some(load.simulation_id.clone()),
+                Some(Command::Launch(launch)) => Some(launch.simulation_id.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let included = message
+            .target
+            .as_ref()
+            .map(|t| t.includes_agent(self.agent_id, &self.labels))
+            .unwrap_or(true);
 
         Box::pin(async move {
-            if message
-                .target
-                .as_ref()
-                .map(|t| t.includes_agent(agent_id))
-                .unwrap_or(true)
-            {
+            if included {
                 let sim_cmd_out = sim_addr
                     .send(SimulationCommandLst {
                         commands: sim_commands,
                     })
                     .await;
-                if let Err(err) = sim_cmd_out {
-                    log::error!("Error sending simulation command - {err}");
+                match sim_cmd_out {
+                    Ok(errors) if message.command_id != 0 || !errors.is_empty() => {
+                        notifier_addr.do_send(MultiCommandAckMessage(vec![CommandAck {
+                            agent_id,
+                            command_id: message.command_id,
+                            simulation_id,
+                            errors: errors.into_iter().map(grpc::CommandError::from).collect(),
+                        }]));
+                    }
+                    Ok(_) => {}
+                    Err(err) => log::error!("Error sending simulation command - {err}"),
                 }
             }
 
@@ -1,4 +1,5 @@
 use crate::agent::actor::AgentCoreActor;
+use crate::agent::metrics::layout::HistogramLayout;
 use crate::agent::metrics::manager::actor::MetricsManagerActor;
 use crate::communication::notifier_actor::UpdatesNotifierActor;
 use crate::communication::protobuf::grpc;
@@ -10,6 +11,7 @@ use crate::simulation::actor::simulation::SimulationActor;
 use crate::simulation::bot::registry::BotRegistry;
 use actix::{Actor, Addr, AsyncContext, Context};
 use rand::RngCore;
+use std::cmp::min;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use tonic::transport::Server;
@@ -23,6 +25,8 @@ pub struct SimulationParams {
     pub(crate) max_running: Option<usize>,
     /// Maximum number of bots that can be spawned per tick cycle.
     pub(crate) max_rate: Option<usize>,
+    /// Relative weight of this agent when the controller splits the load.
+    pub(crate) weight: Option<f64>,
 }
 
 impl SimulationParams {
@@ -40,25 +44,71 @@ impl SimulationParams {
             ..self
         }
     }
+    /// Set the relative weight of this agent (default 1.0).
+    ///
+    /// The controller splits the load proportionally to `weight * max_running`, agents without
+    /// `max_running` count as the average of the others.
+    pub fn weight(self, weight: f64) -> Self {
+        Self {
+            weight: Some(weight),
+            ..self
+        }
+    }
+
+    /// Capacity reported upstream to the controller
+    pub(crate) fn capacity(&self) -> grpc::AgentCapacity {
+        let to_u32 = |limit: Option<usize>| {
+            limit.map_or(0, |limit| u32::try_from(limit).unwrap_or(u32::MAX))
+        };
+        grpc::AgentCapacity {
+            max_running: to_u32(self.max_running),
+            max_rate: to_u32(self.max_rate),
+            weight: self.weight.unwrap_or_default(),
+        }
+    }
+
+    /// Bots the agent keeps running given its share of the global count
+    pub(crate) fn cap_running(&self, count: usize) -> usize {
+        self.max_running
+            .map_or(count, |max_running| min(count, max_running))
+    }
+
+    /// Bots the agent spawns in a tick to get closer to the expected running ones
+    pub(crate) fn cap_spawn(&self, missing: usize) -> usize {
+        self.max_rate
+            .map_or(missing, |max_rate| min(missing, max_rate))
+    }
 }
 
 /// Struct used to build an agent instance
 pub struct AgentBuilder<ContextBuilder, UpstreamCfg, DownstreamCfg> {
     agent_id: u32,
+    name: String,
+    labels: HashMap<String, String>,
     simulation_params: SimulationParams,
+    correct_omission: bool,
+    histogram_layout: HistogramLayout,
     downstream: DownstreamCfg,
     upstream: HashMap<String, UpstreamCfg>,
     rune_context_builder: ContextBuilder,
+    #[cfg(feature = "http-api")]
+    metrics_endpoint: Option<SocketAddr>,
 }
 
 impl<UpstreamCfg> Default for AgentBuilder<(), UpstreamCfg, ()> {
     fn default() -> Self {
         Self {
             agent_id: rand::rng().next_u32(),
+            name: String::new(),
+            labels: Default::default(),
             simulation_params: SimulationParams::default(),
+            correct_omission: false,
+            histogram_layout: HistogramLayout::default(),
             downstream: (),
             upstream: Default::default(),
             rune_context_builder: (),
+            #[cfg(feature = "http-api")]
+            metrics_endpoint: None,
         }
     }
 }
@@ -84,6 +134,21 @@ impl<ContextBuilder, UpstreamCfg, DownstreamCfg>
         Self { agent_id, ..self }
     }
 
+    /// Set the human readable name reported by this agent to the controller
+    pub fn name(self, name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..self
+        }
+    }
+
+    /// Set the key/value labels of this agent (e.g. `region=eu-west`), commands can target
+    /// the agents matching a [`LabelSelector`](crate::grpc::LabelSelector), e.g. the simulations
+    /// restricted to a pool of agents with `SimulationDef::agent_labels`
+    pub fn labels(self, labels: HashMap<String, String>) -> Self {
+        Self { labels, ..self }
+    }
+
     /// Parameters used by this agent to run the simulations
     pub fn simulation_params(self, simulation_params: SimulationParams) -> Self {
         Self {
@@ -92,6 +157,27 @@ impl<ContextBuilder, UpstreamCfg, DownstreamCfg>
         }
     }
 
+    /// Correct the coordinated omission of the latencies (default disabled).
+    ///
+    /// A paced bot runs its next action one interval after the end of the previous one, so the
+    /// executions that a slow action delays are never measured. When enabled, an action slower
+    /// than the bot interval also records the latencies those executions would have observed.
+    pub fn coordinated_omission_correction(self, enabled: bool) -> Self {
+        Self {
+            correct_omission: enabled,
+            ..self
+        }
+    }
+
+    /// Set the bucket boundaries of the latency histograms (default 20 log2 buckets of 10ms),
+    /// e.g. [`HistogramLayout::micros`] or [`HistogramLayout::hdr`] for sub-10ms latencies
+    pub fn histogram_layout(self, histogram_layout: HistogramLayout) -> Self {
+        Self {
+            histogram_layout,
+            ..self
+        }
+    }
+
     /// Configure the interface exposed for other agent to connect
     pub fn downstream<DownstreamConfigType>(
         self,
@@ -99,10 +185,16 @@ impl<ContextBuilder, UpstreamCfg, DownstreamCfg>
     ) -> AgentBuilder<ContextBuilder, UpstreamCfg, DownstreamConfigType> {
         AgentBuilder {
             agent_id: self.agent_id,
+            name: self.name,
+            labels: self.labels,
             simulation_params: self.simulation_params,
+            correct_omission: self.correct_omission,
+            histogram_layout: self.histogram_layout,
             downstream,
             upstream: self.upstream,
             rune_context_builder: self.rune_context_builder,
+            #[cfg(feature = "http-api")]
+            metrics_endpoint: self.metrics_endpoint,
         }
     }
 
@@ -118,10 +210,25 @@ impl<ContextBuilder, UpstreamCfg, DownstreamCfg>
     ) -> AgentBuilder<ContextBuilderType, UpstreamCfg, DownstreamCfg> {
         AgentBuilder {
             agent_id: self.agent_id,
+            name: self.name,
+            labels: self.labels,
             simulation_params: self.simulation_params,
+            correct_omission: self.correct_omission,
+            histogram_layout: self.histogram_layout,
             downstream: self.downstream,
             upstream: self.upstream,
             rune_context_builder,
+            #[cfg(feature = "http-api")]
+            metrics_endpoint: self.metrics_endpoint,
+        }
+    }
+
+    /// Expose the metrics of the bots running on this agent in Prometheus text format on `/metrics`
+    #[cfg(feature = "http-api")]
+    pub fn metrics_endpoint(self, address: SocketAddr) -> Self {
+        Self {
+            metrics_endpoint: Some(address),
+            ..self
         }
     }
 }
@@ -135,7 +242,10 @@ where
     pub fn launch<Upstream: UpstreamAgentActor<Config = UpstreamCfg>>(
         self,
     ) -> AgentRuntime<Upstream> {
-        let metrics_addr = MetricsManagerActor::start_default();
+        let metrics_addr = MetricsManagerActor::default()
+            .coordinated_omission_correction(self.correct_omission)
+            .histogram_layout(self.histogram_layout)
+            .start();
 
         let simulation_ctx: Context<SimulationActor> = Context::new();
 
@@ -144,13 +254,30 @@ where
             .expect("Error during registry construction");
 
         let updater_addr = UpdatesNotifierActor::create(|_| UpdatesNotifierActor::new());
-        let server_actor =
-            GrpcServerActor::create(|_| GrpcServerActor::new(updater_addr.clone().recipient()));
-        let simulation_actor = simulation_ctx.run(SimulationActor::new(
-            self.agent_id,
-            self.simulation_params,
-            bot_registry,
-        ));
+        let server_actor = GrpcServerActor::create(|_| {
+            GrpcServerActor::new(updater_addr.clone().recipient())
+                .command_acks(updater_addr.clone().recipient())
+        });
+        let capacity = self.simulation_params.capacity();
+        let simulation_actor = simulation_ctx.run(
+            SimulationActor::new(self.agent_id, self.simulation_params, bot_registry)
+                .expected_intervals(metrics_addr.clone().recipient())
+                .metrics_reset(metrics_addr.clone().recipient()),
+        );
+
+        #[cfg(feature = "http-api")]
+        if let Some(address) = self.metrics_endpoint {
+            let endpoint = crate::agent::metrics::prometheus::AgentMetricsEndpoint::new(
+                metrics_addr.clone(),
+                simulation_actor.clone(),
+            );
+            actix::spawn(async move {
+                if let Err(err) = endpoint.serve(address).await {
+                    log::error!("Error serving prometheus metrics endpoint - {err}");
+                }
+            });
+        }
+
         let core_addr = AgentCoreActor::create(|_| {
             AgentCoreActor::new(
                 self.agent_id,
@@ -159,6 +286,9 @@ where
                 simulation_actor,
                 metrics_addr,
             )
+            .name(self.name)
+            .labels(self.labels)
+            .capacity(capacity)
         });
 
         if self.upstream.is_empty() {
@@ -232,8 +362,8 @@ mod test {
             type InitializationError = std::io::Error;
 
             fn new(
-                cfg: Self::Config,
-                core_addr: Addr<AgentCoreActor>,
+                _cfg: Self::Config,
+                _core_addr: Addr<AgentCoreActor>,
             ) -> Result<Self, Self::InitializationError> {
                 Ok(Self)
             }
@@ -248,11 +378,11 @@ mod test {
             })
             .launch::<MockUpstream>();
 
-        let out = rt
-            .server
+        rt.server
             .send(ControllerCommandMessage(ControllerCommand {
                 commands: vec![],
                 target: None,
+                command_id: 0,
             }))
             .await
             .expect("Error sending command to server");
@@ -0,0 +1,455 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Upper bounds of the value histograms created without explicit bounds, 1-2-5 steps from 1 to
+/// one million.
+pub const DEFAULT_VALUE_BOUNDS: [f64; 19] = [
+    1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1e3, 2e3, 5e3, 1e4, 2e4, 5e4, 1e5, 2e5,
+    5e5, 1e6,
+];
+
+/// Name of the counter of the script checks.
+pub const CHECKS_METRIC: &str = "checks";
+
+const CHECK_LABEL: &str = "check";
+
+const RESULT_LABEL: &str = "result";
+
+/// Key identifying a custom metric recorded by the scripts.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CustomMetricKey {
+    /// Bot model recording the metric.
+    pub model: String,
+    /// Metric name.
+    pub name: String,
+    /// Metric labels.
+    pub labels: BTreeMap<String, String>,
+}
+
+impl CustomMetricKey {
+    /// Counter of the passed or failed evaluations of a script check, checks are recorded as the
+    /// `checks` counter labelled with the check name and its `pass` or `fail` result
+    pub fn check(model: &str, check: &str, passed: bool) -> Self {
+        Self {
+            model: model.to_string(),
+            name: CHECKS_METRIC.to_string(),
+            labels: BTreeMap::from([
+                (CHECK_LABEL.to_string(), check.to_string()),
+                (
+                    RESULT_LABEL.to_string(),
+                    if passed { "pass" } else { "fail" }.to_string(),
+                ),
+            ]),
+        }
+    }
+
+    /// Check name and result if the key identifies a check counter
+    pub fn as_check(&self) -> Option<(&str, bool)> {
+        if self.name != CHECKS_METRIC {
+            return None;
+        }
+        let check = self.labels.get(CHECK_LABEL)?;
+        match self.labels.get(RESULT_LABEL)?.as_str() {
+            "pass" => Some((check, true)),
+            "fail" => Some((check, false)),
+            _ => None,
+        }
+    }
+}
+
+/// Update of a custom metric sent by a script.
+#[derive(Debug, Clone)]
+pub enum CustomMetricUpdate {
+    /// Increment a counter.
+    Increment(f64),
+    /// Set the value of a gauge.
+    Set(f64),
+    /// Add a (possibly negative) amount to a gauge.
+    Adjust(f64),
+    /// Record a value in a value histogram, created with the given bounds on the first record.
+    Record {
+        /// Recorded value.
+        value: f64,
+        /// Upper bounds of the histogram buckets.
+        bounds: Arc<[f64]>,
+    },
+}
+
+/// Value of a custom metric, counters and histograms are cumulative.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CustomMetricValue {
+    /// Monotonic counter.
+    Counter(f64),
+    /// Last value set.
+    Gauge(f64),
+    /// Distribution of the recorded values.
+    Histogram(ValueHistogram),
+}
+
+impl CustomMetricValue {
+    /// Metric created by its first update
+    pub fn new(update: &CustomMetricUpdate) -> Self {
+        match update {
+            CustomMetricUpdate::Increment(_) => Self::Counter(0.0),
+            CustomMetricUpdate::Set(_) | CustomMetricUpdate::Adjust(_) => Self::Gauge(0.0),
+            CustomMetricUpdate::Record { bounds, .. } => {
+                Self::Histogram(ValueHistogram::new(bounds.to_vec()))
+            }
+        }
+    }
+
+    /// Kind of the metric, as used in messages and expositions
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Counter(_) => "counter",
+            Self::Gauge(_) => "gauge",
+            Self::Histogram(_) => "histogram",
+        }
+    }
+
+    /// Apply an update, returns `false` leaving the metric untouched if the update is meant for
+    /// another kind of metric
+    pub fn apply(&mut self, update: &CustomMetricUpdate) -> bool {
+        match (self, update) {
+            (Self::Counter(total), CustomMetricUpdate::Increment(amount)) => *total += amount,
+            (Self::Gauge(current), CustomMetricUpdate::Set(value)) => *current = *value,
+            (Self::Gauge(current), CustomMetricUpdate::Adjust(amount)) => *current += amount,
+            (Self::Histogram(histogram), CustomMetricUpdate::Record { value, .. }) => {
+                histogram.record(*value)
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Merge the metric of another agent or time window: counters and histograms are added, the
+    /// gauge takes the value of `other`, meant to be the most recent one
+    pub fn merge(&mut self, other: &Self) {
+        match (self, other) {
+            (Self::Counter(total), Self::Counter(other)) => *total += other,
+            (Self::Histogram(histogram), Self::Histogram(other)) => histogram.merge(other),
+            (this, other) => *this = other.clone(),
+        }
+    }
+
+    /// Increase since a previous value of the same metric, counters and histograms that went
+    /// backwards (e.g. agent restarted) are considered reset
+    pub fn delta(&self, previous: &Self) -> Self {
+        match (self, previous) {
+            (Self::Counter(total), Self::Counter(previous)) if total >= previous => {
+                Self::Counter(total - previous)
+            }
+            (Self::Histogram(histogram), Self::Histogram(previous)) => {
+                Self::Histogram(histogram.delta(previous))
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+/// Custom metric value at a given time.
+#[derive(Debug, Clone)]
+pub struct CustomMetricSnapshot {
+    /// Metric key.
+    pub key: CustomMetricKey,
+    /// Simulation the metric was recorded for.
+    pub simulation_id: String,
+    /// Time of the last update.
+    pub timestamp: SystemTime,
+    /// Metric value.
+    pub value: CustomMetricValue,
+}
+
+/// Histogram of arbitrary values (e.g. cart size) with explicit bucket bounds.
+///
+/// Bucket `i` counts the values up to `bounds[i]` and above the previous bound, the last bucket
+/// counts the values above the last bound.
+///
+/// # Examples
+///
+/// ```
+/// use hailstorm::agent::metrics::custom::ValueHistogram;
+///
+/// let mut histogram = ValueHistogram::new(vec![1.0, 5.0, 10.0]);
+/// for value in [1.0, 3.0, 4.0, 7.0, 12.0] {
+///     histogram.record(value);
+/// }
+/// assert_eq!(histogram.buckets(), &[1, 2, 1, 1]);
+/// assert_eq!(histogram.mean(), Some(5.4));
+/// assert_eq!(histogram.max(), Some(12.0));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueHistogram {
+    bounds: Vec<f64>,
+    buckets: Vec<u64>,
+    sum: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Default for ValueHistogram {
+    fn default() -> Self {
+        Self::new(DEFAULT_VALUE_BOUNDS.to_vec())
+    }
+}
+
+impl ValueHistogram {
+    /// Empty histogram with the given upper bounds, sorted and without the non finite ones
+    pub fn new(mut bounds: Vec<f64>) -> Self {
+        bounds.retain(|bound| bound.is_finite());
+        bounds.sort_by(f64::total_cmp);
+        bounds.dedup();
+        Self {
+            buckets: vec![0; bounds.len() + 1],
+            bounds,
+            sum: 0.0,
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Histogram with the given content, buckets are padded or truncated to match the bounds
+    pub fn from_parts(
+        bounds: Vec<f64>,
+        mut buckets: Vec<u64>,
+        sum: f64,
+        min: Option<f64>,
+        max: Option<f64>,
+    ) -> Self {
+        let mut histogram = Self::new(bounds);
+        if buckets.len() > histogram.buckets.len() {
+            let overflow: u64 = buckets.drain(histogram.buckets.len()..).sum();
+            if let Some(last) = buckets.last_mut() {
+                *last += overflow;
+            }
+        }
+        histogram.buckets[..buckets.len()].copy_from_slice(&buckets);
+        histogram.sum = sum;
+        if histogram.count() > 0 {
+            histogram.min = min;
+            histogram.max = max;
+        }
+        histogram
+    }
+
+    /// Record a value, non finite values are ignored
+    pub fn record(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        let idx = self.bounds.partition_point(|bound| *bound < value);
+        self.buckets[idx] += 1;
+        self.sum += value;
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+    }
+
+    /// Upper bounds of the buckets, the last bucket is unbounded
+    pub fn bounds(&self) -> &[f64] {
+        &self.bounds
+    }
+
+    /// Counts of each bucket
+    pub fn buckets(&self) -> &[u64] {
+        &self.buckets
+    }
+
+    /// Number of recorded values
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Sum of the recorded values
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Average of the recorded values, `None` if empty
+    pub fn mean(&self) -> Option<f64> {
+        let count = self.count();
+        (count > 0).then(|| self.sum / count as f64)
+    }
+
+    /// Smallest recorded value, `None` if empty or unknown
+    pub fn min(&self) -> Option<f64> {
+        self.min
+    }
+
+    /// Largest recorded value, `None` if empty or unknown
+    pub fn max(&self) -> Option<f64> {
+        self.max
+    }
+
+    /// Lower and upper bound of a bucket, clamped to the recorded min and max when known
+    fn bucket_range(&self, idx: usize) -> (f64, f64) {
+        let lower = idx
+            .checked_sub(1)
+            .and_then(|prev| self.bounds.get(prev).copied());
+        let upper = self.bounds.get(idx).copied();
+        let lower = match (lower, self.min) {
+            (Some(lower), Some(min)) => lower.max(min),
+            (lower, min) => lower.or(min).unwrap_or(0.0),
+        };
+        let upper = match (upper, self.max) {
+            (Some(upper), Some(max)) => upper.min(max),
+            (upper, max) => upper.or(max).unwrap_or(lower),
+        };
+        (lower.min(upper), upper)
+    }
+
+    /// Estimated value at the given quantile (0.0 to 1.0), interpolating inside the bucket it
+    /// falls into; `None` if empty
+    pub fn percentile(&self, quantile: f64) -> Option<f64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = quantile.clamp(0.0, 1.0) * count as f64;
+        let mut cumulated = 0;
+        for (idx, bucket) in self.buckets.iter().enumerate() {
+            if *bucket == 0 {
+                continue;
+            }
+            if (cumulated + bucket) as f64 >= rank {
+                let (lower, upper) = self.bucket_range(idx);
+                let progress = (rank - cumulated as f64) / *bucket as f64;
+                return Some(lower + (upper - lower) * progress);
+            }
+            cumulated += bucket;
+        }
+        self.max
+    }
+
+    /// Add the content of another histogram, an empty histogram takes the bounds of the other
+    /// one. Values of histograms with other bounds are re-binned at the upper bound of their
+    /// bucket (clamped to its recorded range)
+    pub fn merge(&mut self, other: &Self) {
+        if other.count() == 0 {
+            return;
+        }
+        if self.count() == 0 {
+            *self = other.clone();
+            return;
+        }
+        if self.bounds == other.bounds {
+            for (bucket, other) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+                *bucket += other;
+            }
+        } else {
+            self.rebin(other);
+        }
+        self.sum += other.sum;
+        self.min = self.min.zip(other.min).map(|(a, b)| a.min(b));
+        self.max = self.max.zip(other.max).map(|(a, b)| a.max(b));
+    }
+
+    fn rebin(&mut self, other: &Self) {
+        for (idx, count) in other.buckets.iter().enumerate().filter(|(_, c)| **c > 0) {
+            let (_, upper) = other.bucket_range(idx);
+            let target = self.bounds.partition_point(|bound| *bound < upper);
+            self.buckets[target] += count;
+        }
+    }
+
+    /// Values recorded since a previous snapshot of the same cumulative histogram, the histogram
+    /// is considered reset if the bounds changed or a bucket count went backwards
+    pub fn delta(&self, previous: &Self) -> Self {
+        let reset = self.bounds != previous.bounds
+            || self
+                .buckets
+                .iter()
+                .zip(previous.buckets.iter())
+                .any(|(current, previous)| current < previous);
+        if reset {
+            return self.clone();
+        }
+        let buckets = self
+            .buckets
+            .iter()
+            .zip(previous.buckets.iter())
+            .map(|(current, previous)| current - previous)
+            .collect::<Vec<_>>();
+        let first = buckets.iter().position(|count| *count > 0);
+        let last = buckets.iter().rposition(|count| *count > 0);
+        let min = first.map(|idx| self.bucket_range(idx).0);
+        let max = last.map(|idx| self.bucket_range(idx).1);
+        Self {
+            bounds: self.bounds.clone(),
+            buckets,
+            sum: self.sum - previous.sum,
+            min,
+            max,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn percentiles_stay_in_the_recorded_range() {
+        let mut histogram = ValueHistogram::new(vec![10.0, 100.0]);
+        for value in [20.0, 30.0, 40.0, 50.0] {
+            histogram.record(value);
+        }
+        assert_eq!(histogram.buckets(), &[0, 4, 0]);
+        assert_eq!(histogram.percentile(0.0), Some(20.0));
+        assert_eq!(histogram.percentile(0.5), Some(35.0));
+        assert_eq!(histogram.percentile(1.0), Some(50.0));
+        assert_eq!(ValueHistogram::default().percentile(0.5), None);
+    }
+
+    #[test]
+    fn merge_and_delta() {
+        let mut first = ValueHistogram::new(vec![1.0, 10.0]);
+        first.record(0.5);
+        first.record(5.0);
+        let mut second = ValueHistogram::new(vec![2.0, 20.0]);
+        second.record(1.5);
+        second.record(15.0);
+
+        let mut merged = first.clone();
+        merged.merge(&second);
+        assert_eq!(merged.bounds(), &[1.0, 10.0]);
+        assert_eq!(merged.buckets(), &[1, 2, 1]);
+        assert_eq!(merged.sum(), 22.0);
+        assert_eq!((merged.min(), merged.max()), (Some(0.5), Some(15.0)));
+
+        let mut later = first.clone();
+        later.record(7.0);
+        let delta = later.delta(&first);
+        assert_eq!(delta.buckets(), &[0, 1, 0]);
+        assert_eq!(delta.sum(), 7.0);
+        assert_eq!(first.delta(&later), first);
+    }
+
+    #[test]
+    fn check_keys() {
+        let key = CustomMetricKey::check("Demo", "status is 200", false);
+        assert_eq!(key.name, CHECKS_METRIC);
+        assert_eq!(key.as_check(), Some(("status is 200", false)));
+        assert_eq!(
+            CustomMetricKey {
+                name: String::from("orders"),
+                ..key
+            }
+            .as_check(),
+            None
+        );
+    }
+
+    #[test]
+    fn updates_of_another_kind_are_rejected() {
+        let update = CustomMetricUpdate::Increment(2.0);
+        let mut counter = CustomMetricValue::new(&update);
+        assert!(counter.apply(&update));
+        assert!(!counter.apply(&CustomMetricUpdate::Set(5.0)));
+        assert_eq!(counter, CustomMetricValue::Counter(2.0));
+        assert_eq!(
+            counter.delta(&CustomMetricValue::Counter(3.0)),
+            CustomMetricValue::Counter(2.0)
+        );
+    }
+}
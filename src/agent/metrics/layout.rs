@@ -0,0 +1,223 @@
+use std::time::Duration;
+
+/// Buckets count of the default log2 layout.
+pub(crate) const DEFAULT_LOG2_BUCKETS: u32 = 20;
+
+/// Unit of the default log2 layout.
+const DEFAULT_UNIT: Duration = Duration::from_millis(10);
+
+/// Bucket boundaries of the latency histograms recorded by the agents.
+///
+/// Latencies are truncated to whole `unit`s before being recorded. The layout travels with every
+/// histogram sent upstream, so that histograms recorded with different layouts can be merged.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use hailstorm::agent::metrics::layout::HistogramLayout;
+///
+/// // 1.5ms and 1.9ms fall in the same log2 bucket of 1µs units, (1024µs, 2048µs]
+/// let layout = HistogramLayout::micros();
+/// assert_eq!(
+///     layout.bucket_index(Duration::from_micros(1500)),
+///     layout.bucket_index(Duration::from_micros(1900))
+/// );
+///
+/// // with 2 significant digits they are told apart
+/// let layout = HistogramLayout::hdr(Duration::from_micros(1), 2);
+/// assert_ne!(
+///     layout.bucket_index(Duration::from_micros(1500)),
+///     layout.bucket_index(Duration::from_micros(1900))
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HistogramLayout {
+    /// Bucket `i` counts the latencies in `(2^(i-1), 2^i]` units, bucket 0 those up to one unit
+    /// and the last one is unbounded.
+    Log2 {
+        /// Width of the histogram unit.
+        unit: Duration,
+        /// Number of buckets, including the unbounded one.
+        buckets: u32,
+    },
+    /// HdrHistogram-style log-linear buckets: each power of two range is split in linear
+    /// sub-buckets, keeping `significant_digits` decimal digits of precision with no upper bound.
+    Hdr {
+        /// Width of the histogram unit.
+        unit: Duration,
+        /// Decimal digits of precision (1 to 5).
+        significant_digits: u8,
+    },
+}
+
+impl Default for HistogramLayout {
+    /// 20 log2 buckets of 10ms units, bounded from 10ms to ~43min
+    fn default() -> Self {
+        Self::Log2 {
+            unit: DEFAULT_UNIT,
+            buckets: DEFAULT_LOG2_BUCKETS,
+        }
+    }
+}
+
+impl HistogramLayout {
+    /// Log2 layout with the given unit and number of buckets (at least 2)
+    pub fn log2(unit: Duration, buckets: u32) -> Self {
+        Self::Log2 {
+            unit: Self::valid_unit(unit),
+            buckets: buckets.clamp(2, 64),
+        }
+    }
+
+    /// 32 log2 buckets of microsecond units, bounded from 1µs to ~18min
+    pub fn micros() -> Self {
+        Self::log2(Duration::from_micros(1), 32)
+    }
+
+    /// HDR layout with the given unit and decimal digits of precision (clamped to 1..=5)
+    pub fn hdr(unit: Duration, significant_digits: u8) -> Self {
+        Self::Hdr {
+            unit: Self::valid_unit(unit),
+            significant_digits: significant_digits.clamp(1, 5),
+        }
+    }
+
+    fn valid_unit(unit: Duration) -> Duration {
+        unit.max(Duration::from_nanos(1))
+    }
+
+    /// Width of the histogram unit
+    pub fn unit(&self) -> Duration {
+        match self {
+            Self::Log2 { unit, .. } | Self::Hdr { unit, .. } => Self::valid_unit(*unit),
+        }
+    }
+
+    /// Number of buckets when the layout has a fixed size, HDR histograms grow with the recorded
+    /// values
+    pub fn fixed_len(&self) -> Option<usize> {
+        match self {
+            Self::Log2 { buckets, .. } => Some(*buckets as usize),
+            Self::Hdr { .. } => None,
+        }
+    }
+
+    /// Whether the bucket with the given index has no upper bound
+    pub fn is_unbounded(&self, idx: usize) -> bool {
+        self.fixed_len().is_some_and(|len| idx + 1 >= len)
+    }
+
+    /// Latency truncated to whole units
+    pub fn to_units(&self, latency: Duration) -> u64 {
+        u64::try_from(latency.as_nanos() / self.unit().as_nanos()).unwrap_or(u64::MAX)
+    }
+
+    /// Latency of the given number of units
+    pub fn from_units(&self, units: u64) -> Duration {
+        let nanos = self.unit().as_nanos().saturating_mul(units as u128);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+
+    /// Index of the bucket counting the given latency
+    pub fn bucket_index(&self, latency: Duration) -> usize {
+        self.units_bucket_index(self.to_units(latency))
+    }
+
+    pub(crate) fn units_bucket_index(&self, units: u64) -> usize {
+        match self {
+            Self::Log2 { buckets, .. } => Some(units)
+                .filter(|units| *units > 0)
+                .map(|units| (64 - (units - 1).leading_zeros()).min((*buckets).max(2) - 1) as usize)
+                .unwrap_or(0),
+            Self::Hdr {
+                significant_digits, ..
+            } => {
+                let sub_bucket_bits = Self::sub_bucket_bits(*significant_digits);
+                let magnitude = (63 - (units | ((1 << sub_bucket_bits) - 1)).leading_zeros())
+                    - (sub_bucket_bits - 1);
+                let sub_bucket = units >> magnitude;
+                ((magnitude as u64) << (sub_bucket_bits - 1)) as usize + sub_bucket as usize
+            }
+        }
+    }
+
+    /// Lower and upper bound of the bucket with the given index, the upper bound of the
+    /// unbounded bucket is nominal
+    pub fn bucket_bounds(&self, idx: usize) -> (Duration, Duration) {
+        let (lower, upper) = match self {
+            Self::Log2 { .. } => {
+                let upper = 1u64.checked_shl(idx as u32).unwrap_or(u64::MAX);
+                let lower = if idx == 0 { 0 } else { upper / 2 };
+                (lower, upper)
+            }
+            Self::Hdr {
+                significant_digits, ..
+            } => {
+                let sub_bucket_bits = Self::sub_bucket_bits(*significant_digits);
+                let half_count = 1usize << (sub_bucket_bits - 1);
+                let magnitude = (idx / half_count).saturating_sub(1);
+                let sub_bucket = (idx - magnitude * half_count) as u64;
+                let width = 1u64.checked_shl(magnitude as u32).unwrap_or(u64::MAX);
+                (
+                    sub_bucket.saturating_mul(width),
+                    (sub_bucket + 1).saturating_mul(width),
+                )
+            }
+        };
+        (self.from_units(lower), self.from_units(upper))
+    }
+
+    /// Bits of the linear sub-buckets count, enough to tell apart values differing on the last
+    /// significant digit
+    fn sub_bucket_bits(significant_digits: u8) -> u32 {
+        let largest_single_unit = 2 * 10u64.pow(significant_digits.clamp(1, 5) as u32);
+        64 - (largest_single_unit - 1).leading_zeros()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hdr_buckets_are_contiguous() {
+        let layout = HistogramLayout::hdr(Duration::from_micros(1), 2);
+        let mut prev_idx = 0;
+        for units in 0..100_000 {
+            let idx = layout.units_bucket_index(units);
+            assert!(idx == prev_idx || idx == prev_idx + 1, "units = {units}");
+            let (lower, upper) = layout.bucket_bounds(idx);
+            let latency = Duration::from_micros(units);
+            assert!(lower <= latency && latency < upper, "units = {units}");
+            prev_idx = idx;
+        }
+    }
+
+    #[test]
+    fn hdr_keeps_significant_digits() {
+        let layout = HistogramLayout::hdr(Duration::from_micros(1), 3);
+        for micros in [1_234, 56_789, 3_456_789] {
+            let (lower, upper) =
+                layout.bucket_bounds(layout.bucket_index(Duration::from_micros(micros)));
+            let width = (upper - lower).as_secs_f64() * 1e6;
+            assert!(
+                width / micros as f64 <= 1e-3,
+                "micros = {micros}, width = {width}"
+            );
+        }
+    }
+
+    #[test]
+    fn log2_bounds() {
+        let layout = HistogramLayout::default();
+        assert_eq!(layout.bucket_index(Duration::from_millis(9)), 0);
+        assert_eq!(layout.bucket_index(Duration::from_millis(30)), 2);
+        assert_eq!(layout.bucket_index(Duration::from_secs(100_000)), 19);
+        assert!(layout.is_unbounded(19));
+        assert_eq!(
+            layout.bucket_bounds(2),
+            (Duration::from_millis(20), Duration::from_millis(40))
+        );
+    }
+}
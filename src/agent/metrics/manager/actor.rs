@@ -1,23 +1,60 @@
 use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 
-use actix::{Actor, Context, Handler, ResponseFuture};
+use actix::{Actor, Context, Handler, MessageResult, ResponseFuture};
 use futures::future::join_all;
 use futures::FutureExt;
 
+use crate::agent::metrics::custom::{CustomMetricKey, CustomMetricSnapshot, CustomMetricValue};
+use crate::agent::metrics::layout::HistogramLayout;
 use crate::agent::metrics::manager::message::{
-    ActionMetricsFamilySnapshot, ActionTimerError, FetchActionMetrics, StartActionTimer,
-    StartedActionTimer, StopActionTimer, StorageKey,
+    ActionMetricsFamilySnapshot, ActionTimerError, FetchActionMetrics, FetchCustomMetrics,
+    RecordCustomMetric, ResetSimulationMetrics, StartActionTimer, StartedActionTimer,
+    StopActionTimer, StorageKey, UpdateExpectedIntervals,
 };
 use crate::agent::metrics::storage::facade::MetricsStorage;
-use crate::agent::metrics::storage::message::{FetchMetrics, StartedTimer};
+use crate::agent::metrics::storage::message::{FetchMetrics, ResetMetrics, StartedTimer};
 
 /// Actor that manages per-action metrics storage instances.
 ///
 /// Routes start/stop timer messages to the appropriate [`MetricsStorage`] based on
 /// the (model, action) key, and aggregates metric snapshots for upstream reporting.
+///
+/// With [`coordinated_omission_correction`](Self::coordinated_omission_correction) enabled, an
+/// action slower than the expected interval of its model also records the latencies the
+/// executions delayed by it would have observed, as HdrHistogram `recordValueWithExpectedInterval`.
+///
+/// Latencies are recorded with the [`HistogramLayout`] set by
+/// [`histogram_layout`](Self::histogram_layout), 20 log2 buckets of 10ms by default.
+///
+/// Custom metrics recorded by the scripts ([`RecordCustomMetric`]) are aggregated per model,
+/// name and labels since the simulation load.
+///
+/// Latencies and custom metrics are discarded when a simulation with a different id is loaded
+/// ([`ResetSimulationMetrics`]), their snapshots are tagged with the id of the loaded simulation.
 #[derive(Default)]
 pub struct MetricsManagerActor {
+    simulation_id: String,
     storages: HashMap<StorageKey, MetricsStorage>,
+    custom_metrics: HashMap<CustomMetricKey, (SystemTime, CustomMetricValue)>,
+    layout: HistogramLayout,
+    correct_omission: bool,
+    expected_intervals: HashMap<String, Duration>,
+}
+
+impl MetricsManagerActor {
+    /// Back-fill the latencies of the executions skipped by slow actions (default disabled)
+    pub fn coordinated_omission_correction(self, enabled: bool) -> Self {
+        Self {
+            correct_omission: enabled,
+            ..self
+        }
+    }
+
+    /// Set the bucket boundaries of the recorded latency histograms
+    pub fn histogram_layout(self, layout: HistogramLayout) -> Self {
+        Self { layout, ..self }
+    }
 }
 
 impl Actor for MetricsManagerActor {
@@ -40,7 +77,11 @@ impl Handler<StartActionTimer> for MetricsManagerActor {
             model: msg.model,
             action: msg.action,
         };
-        let metrics_storage = self.storages.entry(key.clone()).or_default();
+        let layout = self.layout;
+        let metrics_storage = self
+            .storages
+            .entry(key.clone())
+            .or_insert_with(|| MetricsStorage::new(layout));
         let out = metrics_storage.start_timer();
         Box::pin(async move {
             match out.await {
@@ -59,10 +100,15 @@ impl Handler<StopActionTimer> for MetricsManagerActor {
         StopActionTimer { timer, execution }: StopActionTimer,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let expected_interval = self
+            .expected_intervals
+            .get(&timer.key.model)
+            .copied()
+            .filter(|_| self.correct_omission);
         let stop_req = self
             .storages
             .get_mut(&timer.key)
-            .map(|ms| ms.stop_timer(timer.into(), execution));
+            .map(|ms| ms.stop_timer(timer.into(), execution, expected_interval));
         Box::pin(async move {
             if let Some(fut) = stop_req {
                 fut.await
@@ -76,10 +122,77 @@ impl Handler<StopActionTimer> for MetricsManagerActor {
     }
 }
 
+impl Handler<UpdateExpectedIntervals> for MetricsManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UpdateExpectedIntervals, _ctx: &mut Self::Context) -> Self::Result {
+        self.expected_intervals = msg.intervals;
+    }
+}
+
+impl Handler<ResetSimulationMetrics> for MetricsManagerActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: ResetSimulationMetrics, _ctx: &mut Self::Context) -> Self::Result {
+        if msg.simulation_id == self.simulation_id {
+            return;
+        }
+        log::debug!("Resetting metrics for simulation '{}'", msg.simulation_id);
+        self.simulation_id = msg.simulation_id;
+        self.custom_metrics.clear();
+        for storage in self.storages.values() {
+            storage.addr.do_send(ResetMetrics);
+        }
+    }
+}
+
+impl Handler<RecordCustomMetric> for MetricsManagerActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        RecordCustomMetric { key, update }: RecordCustomMetric,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let (timestamp, value) = self
+            .custom_metrics
+            .entry(key)
+            .or_insert_with(|| (SystemTime::now(), CustomMetricValue::new(&update)));
+        if value.apply(&update) {
+            *timestamp = SystemTime::now();
+        } else {
+            log::warn!(
+                "Ignoring update of custom {} metric - {update:?}",
+                value.kind()
+            );
+        }
+    }
+}
+
+impl Handler<FetchCustomMetrics> for MetricsManagerActor {
+    type Result = MessageResult<FetchCustomMetrics>;
+
+    fn handle(&mut self, _msg: FetchCustomMetrics, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(
+            self.custom_metrics
+                .iter()
+                .map(|(key, (timestamp, value))| CustomMetricSnapshot {
+                    key: key.clone(),
+                    simulation_id: self.simulation_id.clone(),
+                    timestamp: *timestamp,
+                    value: value.clone(),
+                })
+                .collect(),
+        )
+    }
+}
+
 impl Handler<FetchActionMetrics> for MetricsManagerActor {
     type Result = ResponseFuture<Vec<ActionMetricsFamilySnapshot>>;
 
     fn handle(&mut self, _msg: FetchActionMetrics, _ctx: &mut Self::Context) -> Self::Result {
+        let layout = self.layout;
+        let simulation_id = self.simulation_id.clone();
         let fut_metrics = self
             .storages
             .iter()
@@ -100,7 +213,12 @@ impl Handler<FetchActionMetrics> for MetricsManagerActor {
                         None
                     }
                 })
-                .map(|(key, metrics)| ActionMetricsFamilySnapshot { key, metrics })
+                .map(|(key, metrics)| ActionMetricsFamilySnapshot {
+                    key,
+                    simulation_id: simulation_id.clone(),
+                    layout,
+                    metrics,
+                })
                 .collect()
         })
     }
@@ -1,7 +1,10 @@
+use crate::agent::metrics::custom::{CustomMetricKey, CustomMetricSnapshot, CustomMetricUpdate};
+use crate::agent::metrics::layout::HistogramLayout;
 use crate::agent::metrics::storage::message::{MetricsFamilySnapshot, StartedTimer};
 use crate::agent::metrics::timer::ExecutionInfo;
 use actix::Message;
-use std::time::SystemTime;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -82,12 +85,57 @@ impl StopActionTimer {
     }
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+/// Message used to communicate to metrics manager an update of a custom metric recorded by a
+/// script
+pub struct RecordCustomMetric {
+    pub(super) key: CustomMetricKey,
+    pub(super) update: CustomMetricUpdate,
+}
+
+impl RecordCustomMetric {
+    pub fn new(key: CustomMetricKey, update: CustomMetricUpdate) -> Self {
+        Self { key, update }
+    }
+
+    pub fn key(&self) -> &CustomMetricKey {
+        &self.key
+    }
+
+    pub fn update(&self) -> &CustomMetricUpdate {
+        &self.update
+    }
+}
+
 /// Snapshot of metrics for a single (model, action) pair, containing multiple time-windowed snapshots.
 pub(crate) struct ActionMetricsFamilySnapshot {
     pub key: StorageKey,
+    pub simulation_id: String,
+    pub layout: HistogramLayout,
     pub metrics: Vec<MetricsFamilySnapshot>,
 }
 
 #[derive(Message)]
 #[rtype(result = "Vec<ActionMetricsFamilySnapshot>")]
 pub(crate) struct FetchActionMetrics;
+
+#[derive(Message)]
+#[rtype(result = "Vec<CustomMetricSnapshot>")]
+pub(crate) struct FetchCustomMetrics;
+
+#[derive(Message)]
+#[rtype(result = "()")]
+/// Message used to communicate to metrics manager the interval expected between two actions of
+/// each model of the loaded simulation, models without an interval are not corrected
+pub(crate) struct UpdateExpectedIntervals {
+    pub intervals: HashMap<String, Duration>,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+/// Message used to communicate to metrics manager the id of the loaded simulation. When it changes
+/// the metrics recorded so far are discarded and the following snapshots are tagged with the new id
+pub(crate) struct ResetSimulationMetrics {
+    pub simulation_id: String,
+}
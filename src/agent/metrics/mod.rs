@@ -1,3 +1,7 @@
+pub mod custom;
+pub mod layout;
 pub mod manager;
+#[cfg(feature = "http-api")]
+pub(crate) mod prometheus;
 mod storage;
 pub(crate) mod timer;
@@ -0,0 +1,88 @@
+use std::net::SocketAddr;
+
+use actix::Addr;
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+
+use crate::agent::metrics::manager::actor::MetricsManagerActor;
+use crate::agent::metrics::manager::message::{FetchActionMetrics, FetchCustomMetrics};
+use crate::communication::protobuf::grpc::{CustomMetric, ModelStateSnapshot, ModelStats};
+use crate::controller::http::server::PROMETHEUS_CONTENT_TYPE;
+use crate::controller::metrics::prometheus::PrometheusExposition;
+use crate::simulation::actor::simulation::{FetchSimulationStats, SimulationActor};
+
+/// Sources of the metrics exposed by an agent.
+#[derive(Clone)]
+pub(crate) struct AgentMetricsEndpoint {
+    metrics_addr: Addr<MetricsManagerActor>,
+    simulation_addr: Addr<SimulationActor>,
+}
+
+impl AgentMetricsEndpoint {
+    pub(crate) fn new(
+        metrics_addr: Addr<MetricsManagerActor>,
+        simulation_addr: Addr<SimulationActor>,
+    ) -> Self {
+        Self {
+            metrics_addr,
+            simulation_addr,
+        }
+    }
+
+    /// Serve the bots running on this agent metrics on `/metrics` in Prometheus text format
+    pub(crate) async fn serve(self, address: SocketAddr) -> std::io::Result<()> {
+        let router = Router::new()
+            .route("/metrics", get(export_prometheus))
+            .with_state(self);
+        let listener = tokio::net::TcpListener::bind(address).await?;
+        axum::serve(listener, router).await
+    }
+}
+
+async fn export_prometheus(State(endpoint): State<AgentMetricsEndpoint>) -> Response {
+    let (perf_res, custom_res, state_res) = futures::join!(
+        endpoint.metrics_addr.send(FetchActionMetrics),
+        endpoint.metrics_addr.send(FetchCustomMetrics),
+        endpoint.simulation_addr.send(FetchSimulationStats)
+    );
+    let (perf, custom, sim_stats) = match (perf_res, custom_res, state_res) {
+        (Ok(perf), Ok(custom), Ok(sim_stats)) => (perf, custom, sim_stats),
+        (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => {
+            log::error!("Error fetching stats for prometheus exposition - {err}");
+            return (StatusCode::SERVICE_UNAVAILABLE, err.to_string()).into_response();
+        }
+    };
+
+    let mut exposition = PrometheusExposition::default();
+    for client_stats in sim_stats.stats {
+        let model = client_stats.model.clone();
+        let dropped_iterations = client_stats.dropped_iterations;
+        let skipped_ticks = client_stats.skipped_ticks;
+        exposition.add_model_stats(&ModelStats {
+            performance: perf
+                .iter()
+                .filter(|p| p.key.model.eq(&model))
+                .flat_map(|metr_fam| metr_fam.to_protobuf())
+                .collect(),
+            custom_metrics: custom
+                .iter()
+                .filter(|metr| metr.key.model.eq(&model))
+                .map(CustomMetric::from)
+                .collect(),
+            states: vec![ModelStateSnapshot::from(client_stats)],
+            dropped_iterations,
+            skipped_ticks,
+            model,
+        });
+    }
+
+    (
+        [(CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+        exposition.render(),
+    )
+        .into_response()
+}
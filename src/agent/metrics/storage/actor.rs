@@ -1,14 +1,15 @@
 use super::super::timer::Timer;
+use crate::agent::metrics::layout::HistogramLayout;
 use crate::agent::metrics::storage::message::{
-    FetchMetrics, MetricsFamily, MetricsFamilySnapshot, StartTimer, StartedTimer, StopTimer,
+    FetchMetrics, MetricsFamily, MetricsFamilySnapshot, ResetMetrics, StartTimer, StartedTimer,
+    StopTimer,
 };
 use actix::{Actor, Context, Handler, MessageResult};
 use lazy_static::lazy_static;
 use ringbuf::traits::{Consumer, Observer, Producer, Split};
 use ringbuf::HeapRb;
-use std::cmp::min;
 use std::collections::BTreeMap;
-use std::ops::{Add, Div};
+use std::ops::Add;
 use std::time::{Duration, SystemTime};
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
@@ -43,6 +44,11 @@ impl MFSnapshotStorage {
         }
     }
 
+    pub fn clear(&mut self) {
+        self.buf_consumer.clear();
+        self.last_snapshot = None;
+    }
+
     pub fn is_elapsed(&self, delta: Duration, query_ts: SystemTime) -> bool {
         if let Some(ref last_ts) = self.last_snapshot {
             last_ts.add(delta) < query_ts
@@ -55,8 +61,10 @@ impl MFSnapshotStorage {
 #[derive(Default)]
 pub struct MetricsStorageActor {
     snapshots: MFSnapshotStorage,
+    layout: HistogramLayout,
     histogram: MetricsFamily,
     pending: BTreeMap<SystemTime, Vec<Timer>>,
+    reset_ts: Option<SystemTime>,
 }
 
 impl Actor for MetricsStorageActor {
@@ -76,6 +84,13 @@ lazy_static! {
 }
 
 impl MetricsStorageActor {
+    pub fn new(layout: HistogramLayout) -> Self {
+        Self {
+            layout,
+            ..Default::default()
+        }
+    }
+
     fn get_timer_mut(&mut self, ts: SystemTime, id: u32) -> Option<&mut Timer> {
         self.pending
             .get_mut(&ts)
@@ -91,15 +106,18 @@ impl MetricsStorageActor {
                 {
                     fst_incomplete_ts = Some(*ts);
                     true
+                } else if self.reset_ts.is_some_and(|reset_ts| *ts < reset_ts) {
+                    // timers started before a reset belong to the previous simulation
+                    false
                 } else {
                     for timer in timers {
                         if let Some(execution) = timer.get_execution() {
                             let status = self.histogram.entry(execution.outcome).or_default();
-                            let cs = execution.elapsed.as_millis().div(10) as u64;
-                            let idx = compute_bucket_idx(cs);
-
-                            status.histogram[idx] += 1;
-                            status.sum += cs;
+                            for elapsed in
+                                corrected_samples(execution.elapsed, timer.get_expected_interval())
+                            {
+                                status.record(&self.layout, elapsed);
+                            }
                         } else {
                             log::warn!(
                                 "dropping pending timer '{}'",
@@ -121,11 +139,20 @@ impl MetricsStorageActor {
     }
 }
 
-fn compute_bucket_idx(value: u64) -> usize {
-    Some(value)
-        .filter(|cs| *cs > 0)
-        .map(|cs| min(64 - (cs - 1).leading_zeros(), 19) as usize)
-        .unwrap_or(0)
+/// Latencies recorded for an execution: the measured one, followed by the ones the executions
+/// expected meanwhile would have observed (`elapsed - interval`, `elapsed - 2 * interval`, ...
+/// down to `interval`) when an expected interval is given
+fn corrected_samples(
+    elapsed: Duration,
+    expected_interval: Option<Duration>,
+) -> impl Iterator<Item = Duration> {
+    let interval = expected_interval.filter(|interval| !interval.is_zero());
+    std::iter::successors(Some(elapsed), move |sample| {
+        let interval = interval?;
+        sample
+            .checked_sub(interval)
+            .filter(|missing| *missing >= interval)
+    })
 }
 
 impl Handler<StartTimer> for MetricsStorageActor {
@@ -149,6 +176,7 @@ impl Handler<StopTimer> for MetricsStorageActor {
     fn handle(&mut self, msg: StopTimer, _ctx: &mut Self::Context) -> Self::Result {
         if let Some(timer) = self.get_timer_mut(msg.timer.timestamp, msg.timer.id) {
             timer.set_execution(msg.execution.elapsed, msg.execution.outcome);
+            timer.set_expected_interval(msg.expected_interval);
             self.process_pending();
         } else {
             log::error!(
@@ -160,6 +188,16 @@ impl Handler<StopTimer> for MetricsStorageActor {
     }
 }
 
+impl Handler<ResetMetrics> for MetricsStorageActor {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ResetMetrics, _ctx: &mut Self::Context) -> Self::Result {
+        self.histogram.clear();
+        self.snapshots.clear();
+        self.reset_ts = Some(SystemTime::now());
+    }
+}
+
 impl Handler<FetchMetrics> for MetricsStorageActor {
     type Result = MessageResult<FetchMetrics>;
 
@@ -174,12 +212,20 @@ impl Handler<FetchMetrics> for MetricsStorageActor {
 
 #[cfg(test)]
 mod test {
-    use crate::agent::metrics::storage::actor::compute_bucket_idx;
+    use crate::agent::metrics::layout::HistogramLayout;
+    use crate::agent::metrics::storage::actor::{corrected_samples, MetricsStorageActor};
+    use crate::agent::metrics::storage::message::{
+        FetchMetrics, Metrics, ResetMetrics, StartTimer, StartedTimer, StopTimer,
+    };
+    use crate::agent::metrics::timer::ExecutionInfo;
+    use actix::Actor;
+    use std::time::Duration;
 
     #[test]
     fn test_compute_bucket_idx() {
+        let layout = HistogramLayout::default();
         for v in 0..100 {
-            let idx = compute_bucket_idx(v);
+            let idx = layout.units_bucket_index(v);
             assert!(v <= 2u64.pow(idx as u32), "v = {v}, idx = {idx}");
             assert!(
                 idx == 0 || v > 2u64.pow(idx as u32 - 1),
@@ -187,4 +233,63 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_corrected_samples() {
+        let ms = Duration::from_millis;
+        let samples = |elapsed, interval| corrected_samples(elapsed, interval).collect::<Vec<_>>();
+
+        assert_eq!(samples(ms(350), None), vec![ms(350)]);
+        assert_eq!(samples(ms(80), Some(ms(100))), vec![ms(80)]);
+        assert_eq!(
+            samples(ms(350), Some(ms(100))),
+            vec![ms(350), ms(250), ms(150)]
+        );
+        assert_eq!(samples(ms(200), Some(ms(100))), vec![ms(200), ms(100)]);
+        assert_eq!(samples(ms(350), Some(Duration::ZERO)), vec![ms(350)]);
+    }
+
+    #[test]
+    fn records_in_the_storage_layout() {
+        let mut metrics = Metrics::default();
+        let layout = HistogramLayout::micros();
+        metrics.record(&layout, Duration::from_micros(2500));
+        metrics.record(&layout, Duration::from_micros(900));
+        assert_eq!(metrics.histogram.len(), 32);
+        assert_eq!(metrics.histogram[10], 1);
+        assert_eq!(metrics.histogram[12], 1);
+        assert_eq!((metrics.count, metrics.sum), (2, 3400));
+        assert_eq!((metrics.min, metrics.max), (900, 2500));
+
+        let mut metrics = Metrics::default();
+        let layout = HistogramLayout::hdr(Duration::from_micros(1), 2);
+        metrics.record(&layout, Duration::from_micros(150));
+        assert_eq!(metrics.histogram.len(), 151);
+    }
+
+    #[actix::test]
+    async fn reset_discards_recorded_metrics() {
+        let storage = MetricsStorageActor::new(HistogramLayout::default()).start();
+        let stop = |timer: StartedTimer| StopTimer {
+            timer,
+            execution: ExecutionInfo {
+                elapsed: Duration::from_millis(20),
+                outcome: 200,
+            },
+            expected_interval: None,
+        };
+
+        let previous = storage.send(StartTimer).await.unwrap();
+        let recorded = storage.send(StartTimer).await.unwrap();
+        storage.send(stop(recorded)).await.unwrap();
+        storage.send(ResetMetrics).await.unwrap();
+        // the timers started before the reset are not recorded
+        storage.send(stop(previous)).await.unwrap();
+        let current = storage.send(StartTimer).await.unwrap();
+        storage.send(stop(current)).await.unwrap();
+
+        let snapshots = storage.send(FetchMetrics).await.unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].metrics[&200].count, 1);
+    }
 }
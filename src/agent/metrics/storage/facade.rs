@@ -1,9 +1,10 @@
 use super::actor::MetricsStorageActor;
+use crate::agent::metrics::layout::HistogramLayout;
 use crate::agent::metrics::storage::message::{StartTimer, StartedTimer, StopTimer};
 use crate::agent::metrics::timer::ExecutionInfo;
 use actix::{Actor, Addr, MailboxError};
 use std::future::Future;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 pub(in crate::agent::metrics) struct MetricsStorage {
     pub(in crate::agent::metrics) ts_last_received_metric: SystemTime,
@@ -11,6 +12,13 @@ pub(in crate::agent::metrics) struct MetricsStorage {
 }
 
 impl MetricsStorage {
+    pub fn new(layout: HistogramLayout) -> Self {
+        Self {
+            ts_last_received_metric: SystemTime::now(),
+            addr: MetricsStorageActor::new(layout).start(),
+        }
+    }
+
     pub fn start_timer(&mut self) -> impl Future<Output = Result<StartedTimer, MailboxError>> {
         self.ts_last_received_metric = SystemTime::now();
         self.addr.send(StartTimer)
@@ -19,17 +27,13 @@ impl MetricsStorage {
         &mut self,
         timer: StartedTimer,
         execution: ExecutionInfo,
+        expected_interval: Option<Duration>,
     ) -> impl Future<Output = Result<(), MailboxError>> {
         self.ts_last_received_metric = SystemTime::now();
-        self.addr.send(StopTimer { timer, execution })
-    }
-}
-
-impl Default for MetricsStorage {
-    fn default() -> Self {
-        Self {
-            ts_last_received_metric: SystemTime::now(),
-            addr: MetricsStorageActor::start_default(),
-        }
+        self.addr.send(StopTimer {
+            timer,
+            execution,
+            expected_interval,
+        })
     }
 }
@@ -1,18 +1,45 @@
+use crate::agent::metrics::layout::HistogramLayout;
 use crate::agent::metrics::timer::{ActionOutcome, ExecutionInfo};
 use actix::Message;
 use std::collections::HashMap;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 /// Histogram-based metrics for a single action outcome.
 ///
-/// The `histogram` array contains 20 logarithmically-spaced buckets tracking
-/// response time distribution. `sum` accumulates the total centiseconds across
-/// all observations.
+/// The `histogram` buckets and the other values are expressed in units of the
+/// [`HistogramLayout`] of the storage recording them.
 #[derive(Clone, Default)]
 pub struct Metrics {
-    /// 20-bucket histogram of response times (logarithmic scale, units of 10ms).
-    pub histogram: [u64; 20],
-    /// Sum of all observed values in centiseconds.
+    /// Observations count of each bucket of the layout.
+    pub histogram: Vec<u64>,
+    /// Sum of all observed values.
     pub sum: u64,
+    /// Number of observations.
+    pub count: u64,
+    /// Smallest observed value.
+    pub min: u64,
+    /// Largest observed value.
+    pub max: u64,
+}
+
+impl Metrics {
+    /// Record an observed latency in the given layout
+    pub(crate) fn record(&mut self, layout: &HistogramLayout, latency: Duration) {
+        let units = layout.to_units(latency);
+        let idx = layout.units_bucket_index(units);
+        let len = layout.fixed_len().unwrap_or_default().max(idx + 1);
+        if self.histogram.len() < len {
+            self.histogram.resize(len, 0);
+        }
+        self.histogram[idx] += 1;
+        self.sum = self.sum.saturating_add(units);
+        self.min = if self.count == 0 {
+            units
+        } else {
+            self.min.min(units)
+        };
+        self.max = self.max.max(units);
+        self.count += 1;
+    }
 }
 
 /// A collection of [`Metrics`] keyed by action outcome (e.g., HTTP status code).
@@ -43,8 +70,16 @@ pub struct StartTimer;
 pub struct StopTimer {
     pub timer: StartedTimer,
     pub execution: ExecutionInfo,
+    /// Interval expected between two executions, used to back-fill the skipped ones.
+    pub expected_interval: Option<Duration>,
 }
 
 #[derive(Message)]
 #[rtype(result = "Vec<MetricsFamilySnapshot>")]
 pub struct FetchMetrics;
+
+/// Discard the recorded metrics and the pending snapshots, the timers started so far are not
+/// recorded once stopped.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ResetMetrics;
@@ -7,6 +7,7 @@ pub type ActionOutcome = i64;
 pub struct Timer {
     id: u32,
     execution_info: Option<ExecutionInfo>,
+    expected_interval: Option<Duration>,
 }
 
 /// Information collected upon completion of an action execution.
@@ -23,6 +24,7 @@ impl Timer {
         Self {
             id,
             execution_info: None,
+            expected_interval: None,
         }
     }
 
@@ -30,9 +32,16 @@ impl Timer {
         self.execution_info = Some(ExecutionInfo { elapsed, outcome })
     }
 
+    pub fn set_expected_interval(&mut self, expected_interval: Option<Duration>) {
+        self.expected_interval = expected_interval
+    }
+
     pub fn get_execution(&self) -> Option<ExecutionInfo> {
         self.execution_info.clone()
     }
+    pub fn get_expected_interval(&self) -> Option<Duration> {
+        self.expected_interval
+    }
     pub fn get_id(&self) -> u32 {
         self.id
     }
@@ -1,5 +1,5 @@
 use crate::communication::protobuf::grpc::AgentMessage;
-use crate::communication::protobuf::grpc::{AgentUpdate, ControllerCommand};
+use crate::communication::protobuf::grpc::{AgentUpdate, CommandAck, ControllerCommand};
 
 /// Wraps a [`ControllerCommand`] for delivery via actix messaging.
 #[derive(actix::Message)]
@@ -11,6 +11,11 @@ pub struct ControllerCommandMessage(pub ControllerCommand);
 #[rtype(result = "()")]
 pub struct MultiAgentUpdateMessage(pub Vec<AgentUpdate>);
 
+/// Wraps a batch of [`CommandAck`]s for delivery via actix messaging.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct MultiCommandAckMessage(pub Vec<CommandAck>);
+
 /// Wraps an [`AgentMessage`] for sending upstream via actix messaging.
 #[derive(actix::Message)]
 #[rtype(result = "()")]
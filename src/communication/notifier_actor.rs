@@ -1,5 +1,7 @@
-use crate::communication::message::{MultiAgentUpdateMessage, SendAgentMessage};
-use crate::communication::protobuf::grpc::{AgentMessage, AgentUpdate};
+use crate::communication::message::{
+    MultiAgentUpdateMessage, MultiCommandAckMessage, SendAgentMessage,
+};
+use crate::communication::protobuf::grpc::{AgentMessage, AgentUpdate, CommandAck};
 use actix::{Actor, AsyncContext, Context, Handler, Recipient};
 use std::collections::HashMap;
 use std::time::Duration;
@@ -7,6 +9,7 @@ use std::time::Duration;
 #[derive(Default)]
 pub struct UpdatesNotifierActor {
     frames: HashMap<u64, AgentUpdate>,
+    acks: Vec<CommandAck>,
     connected_clients: Vec<Recipient<SendAgentMessage>>,
 }
 
@@ -26,6 +29,7 @@ impl UpdatesNotifierActor {
     fn send_data(&mut self) {
         let message = AgentMessage {
             updates: self.frames.drain().map(|(_idx, frame)| frame).collect(),
+            acks: std::mem::take(&mut self.acks),
         };
 
         for client in self.connected_clients.iter() {
@@ -65,3 +69,15 @@ impl Handler<MultiAgentUpdateMessage> for UpdatesNotifierActor {
         }
     }
 }
+
+impl Handler<MultiCommandAckMessage> for UpdatesNotifierActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        MultiCommandAckMessage(acks): MultiCommandAckMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.acks.extend(acks);
+    }
+}
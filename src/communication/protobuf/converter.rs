@@ -31,10 +31,15 @@ impl ActionMetricsFamilySnapshot {
                     .iter()
                     .map(|(out, hist)| PerformanceHistogram {
                         status: *out,
-                        buckets: hist.histogram.to_vec(),
+                        buckets: hist.histogram.clone(),
                         sum: hist.sum,
+                        layout: Some(self.layout.into()),
+                        count: hist.count,
+                        min: hist.min,
+                        max: hist.max,
                     })
                     .collect(),
+                simulation_id: self.simulation_id.clone(),
             })
             .collect()
     }
@@ -1,9 +1,15 @@
+use crate::agent::metrics::{custom, layout};
 use crate::communication::protobuf::grpc::controller_command::Target;
-use std::time::SystemTime;
+use crate::simulation::executor::ExecutionMode;
+use crate::simulation::series::{Interpolation, SeriesPoint};
+use crate::simulation::shape::{Easing, ModelShape};
+use crate::simulation::{series, shape};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 tonic::include_proto!("hailstorm");
 
 impl Target {
-    pub(crate) fn includes_agent(&self, agent_id: u32) -> bool {
+    pub(crate) fn includes_agent(&self, agent_id: u32, labels: &HashMap<String, String>) -> bool {
         match self {
             Target::Group(grp_id) => match AgentGroup::try_from(*grp_id) {
                 Ok(AgentGroup::All) => true,
@@ -13,6 +19,290 @@ impl Target {
             Target::Agents(MultiAgent { agent_ids }) => agent_ids
                 .iter()
                 .any(|target_agent_id| agent_id.eq(target_agent_id)),
+            Target::Labels(selector) => selector.matches(labels),
+        }
+    }
+}
+
+impl LabelSelector {
+    /// Selector matching the agents having all the given labels
+    pub fn new(match_labels: HashMap<String, String>) -> Self {
+        Self { match_labels }
+    }
+
+    /// check whether the given agent labels satisfy the selector
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.match_labels
+            .iter()
+            .all(|(key, value)| labels.get(key) == Some(value))
+    }
+}
+
+impl From<&shape::LoadStage> for LoadStage {
+    fn from(stage: &shape::LoadStage) -> Self {
+        let easing = match stage.easing {
+            Easing::Linear => StageEasing::Linear,
+            Easing::Step => StageEasing::Step,
+            Easing::Cosine => StageEasing::Cosine,
+        };
+        Self {
+            target: stage.target,
+            duration: stage.duration.try_into().ok(),
+            easing: easing.into(),
+        }
+    }
+}
+
+impl From<&LoadStage> for shape::LoadStage {
+    fn from(stage: &LoadStage) -> Self {
+        let easing = match stage.easing() {
+            StageEasing::Linear => Easing::Linear,
+            StageEasing::Step => Easing::Step,
+            StageEasing::Cosine => Easing::Cosine,
+        };
+        Self {
+            target: stage.target,
+            duration: stage
+                .duration
+                .and_then(|duration| duration.try_into().ok())
+                .unwrap_or_default(),
+            easing,
+        }
+    }
+}
+
+impl From<&series::TimeSeries> for TimeSeries {
+    fn from(series: &series::TimeSeries) -> Self {
+        let interpolation = match series.interpolation_ref() {
+            Interpolation::Linear => SeriesInterpolation::InterpolationLinear,
+            Interpolation::Step => SeriesInterpolation::InterpolationStep,
+        };
+        Self {
+            times: series.points_ref().iter().map(|point| point.t).collect(),
+            values: series
+                .points_ref()
+                .iter()
+                .map(|point| point.value)
+                .collect(),
+            interpolation: interpolation.into(),
+            time_scale: series.time_scale_ref(),
+            amplitude_scale: series.amplitude_scale_ref(),
+        }
+    }
+}
+
+impl From<&TimeSeries> for series::TimeSeries {
+    fn from(series: &TimeSeries) -> Self {
+        let interpolation = match series.interpolation() {
+            SeriesInterpolation::InterpolationLinear => Interpolation::Linear,
+            SeriesInterpolation::InterpolationStep => Interpolation::Step,
+        };
+        let scale = |factor: f64| if factor == 0.0 { 1.0 } else { factor };
+        Self::lenient(
+            series
+                .times
+                .iter()
+                .zip(series.values.iter())
+                .map(|(t, value)| SeriesPoint {
+                    t: *t,
+                    value: *value,
+                }),
+        )
+        .interpolation(interpolation)
+        .time_scale(scale(series.time_scale))
+        .amplitude_scale(scale(series.amplitude_scale))
+    }
+}
+
+impl From<ExecutionMode> for Option<ArrivalRate> {
+    fn from(execution: ExecutionMode) -> Self {
+        match execution {
+            ExecutionMode::ConstantBots => None,
+            ExecutionMode::ArrivalRate { max_bots } => Some(ArrivalRate {
+                max_bots: max_bots.try_into().unwrap_or(u32::MAX),
+            }),
+        }
+    }
+}
+
+impl From<&ClientDistribution> for ExecutionMode {
+    fn from(distribution: &ClientDistribution) -> Self {
+        match distribution.arrival_rate {
+            Some(ArrivalRate { max_bots }) => ExecutionMode::ArrivalRate {
+                max_bots: max_bots as usize,
+            },
+            None => ExecutionMode::ConstantBots,
+        }
+    }
+}
+
+impl From<layout::HistogramLayout> for HistogramLayout {
+    fn from(layout: layout::HistogramLayout) -> Self {
+        let unit_nanos = u64::try_from(layout.unit().as_nanos()).unwrap_or(u64::MAX);
+        match layout {
+            layout::HistogramLayout::Log2 { buckets, .. } => Self {
+                encoding: HistogramEncoding::EncodingLog2.into(),
+                unit_nanos,
+                buckets,
+                significant_digits: 0,
+            },
+            layout::HistogramLayout::Hdr {
+                significant_digits, ..
+            } => Self {
+                encoding: HistogramEncoding::EncodingHdr.into(),
+                unit_nanos,
+                buckets: 0,
+                significant_digits: significant_digits.into(),
+            },
+        }
+    }
+}
+
+impl From<&HistogramLayout> for layout::HistogramLayout {
+    fn from(layout: &HistogramLayout) -> Self {
+        let unit = match layout.unit_nanos {
+            0 => layout::HistogramLayout::default().unit(),
+            nanos => Duration::from_nanos(nanos),
+        };
+        match layout.encoding() {
+            HistogramEncoding::EncodingLog2 => match layout.buckets {
+                0 => Self::log2(unit, layout::DEFAULT_LOG2_BUCKETS),
+                buckets => Self::log2(unit, buckets),
+            },
+            HistogramEncoding::EncodingHdr => Self::hdr(
+                unit,
+                u8::try_from(layout.significant_digits).unwrap_or(u8::MAX),
+            ),
+        }
+    }
+}
+
+impl From<&custom::ValueHistogram> for ValueHistogram {
+    fn from(histogram: &custom::ValueHistogram) -> Self {
+        Self {
+            bounds: histogram.bounds().to_vec(),
+            buckets: histogram.buckets().to_vec(),
+            sum: histogram.sum(),
+            count: histogram.count(),
+            min: histogram.min().unwrap_or_default(),
+            max: histogram.max().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<&ValueHistogram> for custom::ValueHistogram {
+    fn from(histogram: &ValueHistogram) -> Self {
+        Self::from_parts(
+            histogram.bounds.clone(),
+            histogram.buckets.clone(),
+            histogram.sum,
+            Some(histogram.min),
+            Some(histogram.max),
+        )
+    }
+}
+
+impl From<&custom::CustomMetricSnapshot> for CustomMetric {
+    fn from(snapshot: &custom::CustomMetricSnapshot) -> Self {
+        Self {
+            name: snapshot.key.name.clone(),
+            labels: snapshot.key.labels.clone().into_iter().collect(),
+            timestamp: Some(snapshot.timestamp.into()),
+            value: Some(match &snapshot.value {
+                custom::CustomMetricValue::Counter(total) => custom_metric::Value::Counter(*total),
+                custom::CustomMetricValue::Gauge(value) => custom_metric::Value::Gauge(*value),
+                custom::CustomMetricValue::Histogram(histogram) => {
+                    custom_metric::Value::Histogram(histogram.into())
+                }
+            }),
+            simulation_id: snapshot.simulation_id.clone(),
+        }
+    }
+}
+
+impl CustomMetric {
+    /// Key of the metric recorded by the given model
+    pub fn key(&self, model: &str) -> custom::CustomMetricKey {
+        custom::CustomMetricKey {
+            model: model.to_string(),
+            name: self.name.clone(),
+            labels: self.labels.clone().into_iter().collect(),
+        }
+    }
+
+    /// Value of the metric, `None` if missing
+    pub fn metric_value(&self) -> Option<custom::CustomMetricValue> {
+        self.value.as_ref().map(|value| match value {
+            custom_metric::Value::Counter(total) => custom::CustomMetricValue::Counter(*total),
+            custom_metric::Value::Gauge(value) => custom::CustomMetricValue::Gauge(*value),
+            custom_metric::Value::Histogram(histogram) => {
+                custom::CustomMetricValue::Histogram(histogram.into())
+            }
+        })
+    }
+}
+
+impl From<&ClientDistribution> for ModelShape {
+    fn from(distribution: &ClientDistribution) -> Self {
+        if let Some(series) = &distribution.series {
+            ModelShape::Series(series.into())
+        } else if distribution.stages.is_empty() {
+            ModelShape::Expression(distribution.shape.clone())
+        } else {
+            ModelShape::Stages(distribution.stages.iter().map(Into::into).collect())
+        }
+    }
+}
+
+impl AgentOrdinals {
+    /// Dense ordinals of the given agents, assigned in agent id order, and their share of the
+    /// load weighted by the declared capacity (`weight * max_running`)
+    pub(crate) fn from_capacities(capacities: &HashMap<u32, AgentCapacity>) -> Self {
+        let mut agent_ids = capacities.keys().copied().collect::<Vec<_>>();
+        agent_ids.sort_unstable();
+
+        // agents without a running bots limit count as the average of the bounded ones
+        let bounded = capacities
+            .values()
+            .map(|capacity| capacity.max_running)
+            .filter(|max_running| *max_running > 0)
+            .collect::<Vec<_>>();
+        let default_max_running = if bounded.is_empty() {
+            1.0
+        } else {
+            bounded
+                .iter()
+                .map(|max_running| *max_running as f64)
+                .sum::<f64>()
+                / bounded.len() as f64
+        };
+        let weighted = capacities
+            .iter()
+            .map(|(agent_id, capacity)| {
+                let max_running = match capacity.max_running {
+                    0 => default_max_running,
+                    max_running => max_running as f64,
+                };
+                let weight = if capacity.weight > 0.0 {
+                    capacity.weight
+                } else {
+                    1.0
+                };
+                (*agent_id, weight * max_running)
+            })
+            .collect::<HashMap<_, _>>();
+        let total_capacity: f64 = weighted.values().sum();
+
+        Self {
+            ordinals: agent_ids
+                .into_iter()
+                .enumerate()
+                .map(|(ordinal, agent_id)| (agent_id, ordinal as u32))
+                .collect(),
+            weights: weighted
+                .into_iter()
+                .map(|(agent_id, capacity)| (agent_id, capacity / total_capacity))
+                .collect(),
         }
     }
 }
@@ -29,7 +319,6 @@ impl AgentUpdate {
     /// agent update timestamp
     pub fn update_ts(&self) -> Option<SystemTime> {
         self.timestamp
-            .clone()
             .map(TryInto::try_into)
             .transpose()
             .ok()
@@ -43,17 +332,103 @@ impl ModelStats {
         let max_states_ts = self
             .states
             .iter()
-            .flat_map(|state| state.timestamp.clone())
+            .flat_map(|state| state.timestamp)
             .flat_map(|ts| SystemTime::try_from(ts).ok())
             .max();
 
         let max_perf_ts = self
             .performance
             .iter()
-            .flat_map(|perf| perf.timestamp.clone())
+            .flat_map(|perf| perf.timestamp)
             .flat_map(|ts| SystemTime::try_from(ts).ok())
             .max();
 
         [max_states_ts, max_perf_ts].into_iter().flatten().max()
     }
 }
+
+/// Protobuf messages built by the tests, fields not given keep their default value so that the
+/// tests are not affected by new fields.
+#[cfg(test)]
+pub(crate) mod fixture {
+    use super::*;
+
+    /// Update of an agent at `ts`
+    pub(crate) fn agent_update(
+        agent_id: u32,
+        ts: SystemTime,
+        stats: Vec<ModelStats>,
+    ) -> AgentUpdate {
+        AgentUpdate {
+            agent_id,
+            timestamp: Some(ts.into()),
+            stats,
+            ..Default::default()
+        }
+    }
+
+    /// Stats of a model with the histograms of a single action recorded at `ts`
+    pub(crate) fn model_stats(
+        model: &str,
+        ts: SystemTime,
+        action: &str,
+        histograms: Vec<PerformanceHistogram>,
+    ) -> ModelStats {
+        ModelStats {
+            model: model.to_string(),
+            performance: vec![PerformanceSnapshot {
+                timestamp: Some(ts.into()),
+                action: action.to_string(),
+                histograms,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// Bots count of a model in a given state at `ts`
+    pub(crate) fn bot_states(ts: SystemTime, state_id: u32, count: u32) -> ModelStateSnapshot {
+        ModelStateSnapshot {
+            timestamp: Some(ts.into()),
+            states: vec![ClientGroupStates { state_id, count }],
+        }
+    }
+
+    /// Histogram with the default buckets layout
+    pub(crate) fn histogram(status: i64, buckets: Vec<u64>, sum: u64) -> PerformanceHistogram {
+        PerformanceHistogram {
+            status,
+            buckets,
+            sum,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn label_selector_target() {
+        let labels: HashMap<String, String> = [
+            (String::from("region"), String::from("eu-west")),
+            (String::from("pool"), String::from("mobile")),
+        ]
+        .into();
+        let selector = |match_labels: &[(&str, &str)]| {
+            Target::Labels(LabelSelector::new(
+                match_labels
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ))
+        };
+
+        assert!(selector(&[("region", "eu-west")]).includes_agent(1, &labels));
+        assert!(selector(&[("region", "eu-west"), ("pool", "mobile")]).includes_agent(1, &labels));
+        assert!(selector(&[]).includes_agent(1, &labels));
+        assert!(!selector(&[("region", "us-east")]).includes_agent(1, &labels));
+        assert!(!selector(&[("tier", "gold")]).includes_agent(1, &labels));
+    }
+}
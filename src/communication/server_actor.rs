@@ -1,5 +1,7 @@
 use crate::communication::downstream_agent_actor::DownstreamAgentActor;
-use crate::communication::message::{ControllerCommandMessage, MultiAgentUpdateMessage};
+use crate::communication::message::{
+    ControllerCommandMessage, MultiAgentUpdateMessage, MultiCommandAckMessage,
+};
 use crate::communication::protobuf::grpc::controller_command::Target;
 use crate::communication::protobuf::grpc::AgentMessage;
 use crate::communication::protobuf::grpc::MultiAgent;
@@ -16,6 +18,7 @@ use std::time::{Duration, SystemTime};
 
 struct ConnectedAgent {
     last_received_update: SystemTime,
+    labels: HashMap<String, String>,
 }
 
 struct DownstreamConnection {
@@ -25,6 +28,7 @@ struct DownstreamConnection {
 
 pub struct GrpcServerActor {
     agent_update_recipient: Recipient<MultiAgentUpdateMessage>,
+    command_ack_recipient: Option<Recipient<MultiCommandAckMessage>>,
     downstream_agents: HashMap<u64, DownstreamConnection>,
 }
 
@@ -36,10 +40,19 @@ impl GrpcServerActor {
     pub fn new(agent_update_recipient: Recipient<MultiAgentUpdateMessage>) -> Self {
         Self {
             agent_update_recipient,
+            command_ack_recipient: None,
             downstream_agents: Default::default(),
         }
     }
 
+    /// Forward the command acknowledgements received from downstream agents
+    pub fn command_acks(self, command_ack_recipient: Recipient<MultiCommandAckMessage>) -> Self {
+        Self {
+            command_ack_recipient: Some(command_ack_recipient),
+            ..self
+        }
+    }
+
     fn connections_cleanup(&mut self) {
         self.downstream_agents
             .retain(|_id, conn| conn.sender.connected())
@@ -96,7 +109,7 @@ impl StreamHandler<ConnectedAgentMessage> for GrpcServerActor {
                 .stats
                 .iter()
                 .flat_map(|stats| stats.states.iter())
-                .filter_map(|states| states.timestamp.clone())
+                .filter_map(|states| states.timestamp)
                 .filter_map(|ts| SystemTime::try_from(ts).ok())
                 .max()
                 .unwrap_or_else(SystemTime::now);
@@ -107,10 +120,12 @@ impl StreamHandler<ConnectedAgentMessage> for GrpcServerActor {
                     .entry(update_item.agent_id)
                     .or_insert(ConnectedAgent {
                         last_received_update: last_state_update_ts,
+                        labels: update_item.labels.clone(),
                     });
 
             if last_state_update_ts > agent_entry.last_received_update {
                 agent_entry.last_received_update = last_state_update_ts;
+                agent_entry.labels = update_item.labels.clone();
             }
         }
 
@@ -118,6 +133,13 @@ impl StreamHandler<ConnectedAgentMessage> for GrpcServerActor {
             .try_send(MultiAgentUpdateMessage(message.updates))
             .unwrap_or_else(|err| log::error!("Error sending update message {err:?}"));
 
+        match &self.command_ack_recipient {
+            Some(ack_recipient) if !message.acks.is_empty() => ack_recipient
+                .try_send(MultiCommandAckMessage(message.acks))
+                .unwrap_or_else(|err| log::error!("Error sending command acks {err:?}")),
+            _ => {}
+        }
+
         for (_, da) in self.downstream_agents.iter_mut() {
             da.agent_ids.retain(|_k, v| {
                 v.last_received_update.add(Duration::from_secs(60)) > SystemTime::now()
@@ -153,6 +175,10 @@ impl Handler<ControllerCommandMessage> for GrpcServerActor {
                 Some(Target::Agents(MultiAgent { ref agent_ids })) => {
                     agent_ids.iter().any(|id| conn.agent_ids.contains_key(id))
                 }
+                Some(Target::Labels(ref selector)) => conn
+                    .agent_ids
+                    .values()
+                    .any(|agent| selector.matches(&agent.labels)),
             })
             .map(|da| da.sender.clone())
             .collect::<Vec<_>>();
@@ -5,34 +5,82 @@ use std::time::{Duration, SystemTime};
 
 use actix::dev::RecipientRequest;
 use actix::{
-    Actor, ActorFutureExt, AtomicResponse, Context, Handler, Recipient, ResponseFuture, WrapFuture,
+    Actor, ActorFutureExt, Addr, AsyncContext, AtomicResponse, Context, Handler, MailboxError,
+    MessageResult, Recipient, ResponseFuture, SpawnHandle, WrapFuture,
 };
+use rand::Rng;
 
-use crate::communication::message::{ControllerCommandMessage, MultiAgentUpdateMessage};
+use crate::communication::message::{
+    ControllerCommandMessage, MultiAgentUpdateMessage, MultiCommandAckMessage,
+};
 use crate::communication::protobuf::grpc;
 use crate::communication::protobuf::grpc::command_item::Command;
 use crate::communication::protobuf::grpc::{
-    AgentUpdate, LaunchCommand, LoadSimCommand, StopCommand,
+    AgentOrdinals, AgentUpdate, CommandAck, LaunchCommand, PhaseCommand, StopCommand,
 };
 use crate::controller::client::downstream::DownstreamClient;
-use crate::controller::message::{LoadSimulation, StartSimulation};
-use crate::controller::model::simulation::SimulationState;
+use crate::controller::message::{
+    AbortSimulation, AgentInfo, FailSimulation, FetchAgents, FetchSimulationStatus, LoadSimulation,
+    SimulationStatus, StartSimulation, StopSimulation, ValidateSimulation,
+};
+use crate::controller::model::simulation::{CompletionReason, SimulationDef, SimulationState};
+use crate::controller::persistence::SimulationStateStore;
+use crate::controller::report::message::{AgentParticipation, GenerateRunReport};
+use crate::controller::slo::actor::SloWatcherActor;
+use crate::controller::slo::message::{UnwatchThresholds, WatchThresholds};
+use crate::controller::validation::{SimulationValidator, ValidationReport};
 
 #[derive(Clone, Debug)]
 struct AgentState {
     timestamp: SystemTime,
     state: grpc::AgentSimulationState,
+    name: String,
+    labels: HashMap<String, String>,
+    capacity: Option<grpc::AgentCapacity>,
+    simulation_id: String,
+}
+
+/// Last command rejected by an agent
+#[derive(Clone, Debug)]
+struct CommandRejection {
+    simulation_id: String,
+    errors: Vec<grpc::CommandError>,
+}
+
+/// How agents have to tear down running bots when the simulation is not launched.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum StopMode {
+    /// Bots run their `Stopping` hook and drain.
+    #[default]
+    Graceful,
+    /// Bots are stopped immediately, hooks are skipped.
+    Abort,
 }
 
 /// Core controller actor that orchestrates distributed simulations.
 ///
 /// Tracks connected agent states, distributes simulation commands, and ensures
-/// all agents converge to the desired simulation state (idle, ready, or launched).
+/// all agents converge to the desired simulation state (idle, ready, launched or completed).
+/// When a state store is configured the simulation state is persisted on every transition and
+/// a launched run is resumed, with its original start time, after a controller restart.
+/// Agents that rejected the loaded simulation are not realigned until a new one is loaded.
+/// Simulations are validated before being loaded, see [`SimulationValidator`].
+/// Simulations restricted to a set of agent labels run on the matching agents only, the other
+/// agents are kept idle.
 pub struct ControllerActor {
     downstream: DownstreamClient,
     metrics_storage: Recipient<MultiAgentUpdateMessage>,
     agents_state: HashMap<u32, AgentState>,
+    rejections: HashMap<u32, CommandRejection>,
     simulation: SimulationState,
+    stop_mode: StopMode,
+    reporter: Option<Recipient<GenerateRunReport>>,
+    participants: HashMap<u32, AgentParticipation>,
+    slo_watcher: Option<Addr<SloWatcherActor>>,
+    phase_timer: Option<SpawnHandle>,
+    end_timer: Option<SpawnHandle>,
+    state_store: Option<Box<dyn SimulationStateStore>>,
+    validator: SimulationValidator,
 }
 
 impl ControllerActor {
@@ -44,13 +92,71 @@ impl ControllerActor {
             downstream,
             metrics_storage,
             agents_state: Default::default(),
+            rejections: Default::default(),
             simulation: SimulationState::Idle,
+            stop_mode: StopMode::default(),
+            reporter: None,
+            participants: Default::default(),
+            slo_watcher: None,
+            phase_timer: None,
+            end_timer: None,
+            state_store: None,
+            validator: SimulationValidator::default(),
+        }
+    }
+
+    /// Validate the simulations with the given validator
+    pub fn validator(self, validator: SimulationValidator) -> Self {
+        Self { validator, ..self }
+    }
+
+    /// Generate a report at the end of each simulation run
+    pub fn run_reporter(self, reporter: Recipient<GenerateRunReport>) -> Self {
+        Self {
+            reporter: Some(reporter),
+            ..self
+        }
+    }
+
+    /// Watch the simulation thresholds during each run
+    pub fn slo_watcher(self, slo_watcher: Addr<SloWatcherActor>) -> Self {
+        Self {
+            slo_watcher: Some(slo_watcher),
+            ..self
+        }
+    }
+
+    /// Persist the simulation state in the given store, restoring the last saved one
+    pub fn state_store(self, store: Box<dyn SimulationStateStore>) -> Self {
+        let simulation = match store.load() {
+            Ok(Some(simulation)) => {
+                log::info!("Restored persisted simulation state");
+                simulation
+            }
+            Ok(None) => self.simulation,
+            Err(err) => {
+                log::error!("Error loading persisted simulation state - {err}");
+                self.simulation
+            }
+        };
+        Self {
+            simulation,
+            state_store: Some(store),
+            ..self
         }
     }
 }
 
 impl Actor for ControllerActor {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        // a run restored from the state store keeps its original start time
+        if let SimulationState::Launched { start_ts, .. } = &self.simulation {
+            log::info!("Resuming simulation launched at {start_ts:?}");
+            self.resume_run(ctx);
+        }
+    }
 }
 
 impl Handler<MultiAgentUpdateMessage> for ControllerActor {
@@ -62,24 +168,38 @@ impl Handler<MultiAgentUpdateMessage> for ControllerActor {
         _ctx: &mut Self::Context,
     ) -> Self::Result {
         let pre_handle_agents_count = self.count_agents();
+        let pre_handle_ordinals = self.agent_ordinals();
 
         let agent_alignment_fut = self.align_agents_simulation_state(&agent_updates);
+
+        // metrics of a previous simulation must not pollute the ones of the loaded one, agents
+        // restart theirs on load and the store merges the first ones tagged with the new id whole
+        let (agent_updates, stale_updates): (Vec<_>, Vec<_>) = agent_updates
+            .into_iter()
+            .partition(|update| !self.is_stale(&update.simulation_id));
+        for update in stale_updates {
+            log::debug!(
+                "Discarding update of agent {} for stale simulation '{}'",
+                update.agent_id,
+                update.simulation_id
+            );
+        }
         let send_metrics_fut = self
             .metrics_storage
             .send(MultiAgentUpdateMessage(agent_updates));
 
         let post_handle_agents_count = self.count_agents();
+        let post_handle_ordinals = self.agent_ordinals();
 
-        let cmd_fut = if pre_handle_agents_count != post_handle_agents_count {
+        // ordinals are reassigned whenever an agent joins or leaves
+        let cmd_fut = if pre_handle_ordinals != post_handle_ordinals {
             log::info!(
                 "Update agents count {pre_handle_agents_count} -> {post_handle_agents_count}"
             );
-            Some(
-                self.downstream
-                    .send_broadcast(vec![Command::UpdateAgentsCount(
-                        post_handle_agents_count as u32,
-                    )]),
-            )
+            Some(self.send_to_simulation_agents(vec![
+                Command::UpdateAgentsCount(post_handle_agents_count as u32),
+                Command::Ordinals(post_handle_ordinals),
+            ]))
         } else {
             None
         };
@@ -102,18 +222,107 @@ impl Handler<MultiAgentUpdateMessage> for ControllerActor {
 
 impl ControllerActor {
     fn count_agents(&self) -> usize {
-        self.agents_state.len()
+        self.simulation_agents().count()
     }
 
-    fn broadcast_simulation_state(&mut self) -> RecipientRequest<ControllerCommandMessage> {
-        self.downstream
-            .send_broadcast(self.generate_simulation_state_commands())
+    /// Dense ordinals of the live agents running the simulation, assigned in agent id order, and
+    /// their share of the load weighted by the declared capacity
+    fn agent_ordinals(&self) -> AgentOrdinals {
+        AgentOrdinals::from_capacities(
+            &self
+                .simulation_agents()
+                .map(|(agent_id, agent)| (*agent_id, agent.capacity.unwrap_or_default()))
+                .collect(),
+        )
     }
 
-    fn misaligned_agents(&self) -> HashMap<u32, AgentState> {
+    /// Live agents running the loaded simulation, all of them unless it selects agent labels
+    fn simulation_agents(&self) -> impl Iterator<Item = (&u32, &AgentState)> {
+        let selector = self
+            .simulation
+            .simulation_ref()
+            .and_then(SimulationDef::agent_selector);
+        self.agents_state.iter().filter(move |(_, agent)| {
+            selector
+                .as_ref()
+                .is_none_or(|selector| selector.matches(&agent.labels))
+        })
+    }
+
+    /// Send the commands to the agents running the loaded simulation, the ones matching its
+    /// agent labels or all of them
+    fn send_to_simulation_agents(
+        &self,
+        commands: Vec<Command>,
+    ) -> RecipientRequest<ControllerCommandMessage> {
+        match self
+            .simulation
+            .simulation_ref()
+            .and_then(SimulationDef::agent_selector)
+        {
+            Some(selector) => self.downstream.send_to_labels(selector, commands),
+            None => self.downstream.send_broadcast(commands),
+        }
+    }
+
+    fn broadcast_simulation_state(&mut self) -> impl Future<Output = Result<(), MailboxError>> {
+        let send_fut = self.send_to_simulation_agents(self.generate_simulation_state_commands());
+        // agents left out of a labelled simulation drop the previous one
+        let excluded = self.excluded_agents();
+        let excluded_fut = (!excluded.is_empty()).then(|| {
+            self.downstream
+                .send_to_agents(excluded, excluded_agent_commands())
+        });
+        async move {
+            if let Some(excluded_fut) = excluded_fut {
+                excluded_fut.await?;
+            }
+            send_fut.await
+        }
+    }
+
+    /// Live agents not running the loaded simulation that are not idle
+    fn excluded_agents(&self) -> Vec<u32> {
+        let simulation_agents: Vec<u32> = self.simulation_agents().map(|(id, _)| *id).collect();
         self.agents_state
             .iter()
-            .filter(|(_, agent)| !self.simulation.is_aligned(&agent.state))
+            .filter(|(agent_id, _)| !simulation_agents.contains(agent_id))
+            .filter(|(_, agent)| !SimulationState::Idle.is_aligned(&agent.state))
+            .map(|(agent_id, _)| *agent_id)
+            .collect()
+    }
+
+    /// Simulation id of the loaded simulation, `None` when idle
+    fn simulation_id(&self) -> Option<&str> {
+        self.simulation
+            .simulation_ref()
+            .map(SimulationDef::id_ref)
+            .filter(|id| !id.is_empty())
+    }
+
+    /// Whether the agent reported an update for a different simulation than the loaded one
+    fn is_stale(&self, simulation_id: &str) -> bool {
+        !simulation_id.is_empty()
+            && self
+                .simulation_id()
+                .is_some_and(|current_id| current_id != simulation_id)
+    }
+
+    /// Errors of the agent that rejected the loaded simulation, `None` if it was accepted
+    fn rejection_errors(&self, agent_id: u32) -> Option<&[grpc::CommandError]> {
+        self.rejections
+            .get(&agent_id)
+            .filter(|rejection| self.simulation_id() == Some(rejection.simulation_id.as_str()))
+            .map(|rejection| rejection.errors.as_slice())
+    }
+
+    fn misaligned_agents(&self) -> HashMap<u32, AgentState> {
+        self.simulation_agents()
+            // resending a rejected simulation would just get it rejected again
+            .filter(|(agent_id, _)| self.rejection_errors(**agent_id).is_none())
+            .filter(|(_, agent)| {
+                !self.simulation.is_aligned(&agent.state) || self.is_stale(&agent.simulation_id)
+            })
             .map(|(k, v)| (*k, v.clone()))
             .collect::<HashMap<_, _>>()
     }
@@ -122,31 +331,49 @@ impl ControllerActor {
         &mut self,
         updates: &[AgentUpdate],
     ) -> impl Future<Output = ()> {
+        let mut joined = Vec::new();
         for update in updates {
+            if !self.agents_state.contains_key(&update.agent_id) {
+                joined.push(update.agent_id);
+            }
             if let Some(timestamp) = update
                 .timestamp
-                .clone()
                 .map(SystemTime::try_from)
                 .transpose()
                 .ok()
                 .flatten()
             {
-                let entry = self
-                    .agents_state
-                    .entry(update.agent_id)
-                    .or_insert(AgentState {
-                        timestamp,
-                        state: update.state(),
-                    });
+                let entry =
+                    self.agents_state
+                        .entry(update.agent_id)
+                        .or_insert_with(|| AgentState {
+                            timestamp,
+                            state: update.state(),
+                            name: update.name.clone(),
+                            labels: update.labels.clone(),
+                            capacity: update.capacity,
+                            simulation_id: update.simulation_id.clone(),
+                        });
 
                 if entry.timestamp < timestamp {
                     entry.timestamp = timestamp;
                     entry.state = update.state();
+                    entry.name.clone_from(&update.name);
+                    entry.labels.clone_from(&update.labels);
+                    entry.capacity = update.capacity;
+                    entry.simulation_id.clone_from(&update.simulation_id);
+                }
+
+                if !self.is_stale(&update.simulation_id) {
+                    self.track_participant(update, timestamp);
                 }
             }
         }
         self.agents_state
             .retain(|_id, state| state.timestamp.add(Duration::from_secs(60)) > SystemTime::now());
+        let agents_state = &self.agents_state;
+        self.rejections
+            .retain(|agent_id, _| agents_state.contains_key(agent_id));
 
         let misaligned = self.misaligned_agents();
         let commands = self.generate_simulation_state_commands();
@@ -159,6 +386,29 @@ impl ControllerActor {
                     .send_to_agents(misaligned.keys().cloned().collect(), commands),
             )
         };
+        let excluded = self.excluded_agents();
+        let maybe_excluded_fut = (!excluded.is_empty()).then(|| {
+            self.downstream
+                .send_to_agents(excluded, excluded_agent_commands())
+        });
+
+        // aligned agents (re)joining a multi-stage run, e.g. after a controller restart, may have
+        // missed some phase transitions
+        joined.retain(|agent_id| {
+            self.agents_state.contains_key(agent_id) && !misaligned.contains_key(agent_id)
+        });
+        let maybe_phase_fut = match &self.simulation {
+            SimulationState::Launched {
+                start_ts,
+                simulation,
+            } if !joined.is_empty() => current_phase_command(simulation, *start_ts)
+                .filter(|phase_cmd| phase_cmd.index > 0)
+                .map(|phase_cmd| {
+                    self.downstream
+                        .send_to_agents(joined, vec![Command::Phase(phase_cmd)])
+                }),
+            _ => None,
+        };
 
         async move {
             if let Some(send_fut) = maybe_send_fut {
@@ -166,6 +416,59 @@ impl ControllerActor {
                     log::error!("Error aligning simulation state - {err}");
                 }
             }
+            if let Some(excluded_fut) = maybe_excluded_fut {
+                if let Err(err) = excluded_fut.await {
+                    log::error!("Error stopping agents out of the simulation - {err}");
+                }
+            }
+            if let Some(phase_fut) = maybe_phase_fut {
+                if let Err(err) = phase_fut.await {
+                    log::error!("Error aligning simulation phase - {err}");
+                }
+            }
+        }
+    }
+
+    fn track_participant(&mut self, update: &AgentUpdate, timestamp: SystemTime) {
+        let SimulationState::Launched { start_ts, .. } = &self.simulation else {
+            return;
+        };
+        if timestamp < *start_ts {
+            return;
+        }
+        let running = update.state() == grpc::AgentSimulationState::Running;
+        let participant = self
+            .participants
+            .entry(update.agent_id)
+            .or_insert(AgentParticipation {
+                agent_id: update.agent_id,
+                first_update: timestamp,
+                last_update: timestamp,
+                running,
+            });
+        participant.last_update = participant.last_update.max(timestamp);
+        participant.running |= running;
+    }
+
+    fn terminate_run(
+        &mut self,
+        simulation: SimulationDef,
+        start_ts: SystemTime,
+        end_ts: SystemTime,
+        reason: Option<&CompletionReason>,
+    ) {
+        if let Some(slo_watcher) = &self.slo_watcher {
+            slo_watcher.do_send(UnwatchThresholds::at(end_ts));
+        }
+        let agents = self.participants.drain().map(|(_, agent)| agent).collect();
+        if let Some(reporter) = &self.reporter {
+            let request = GenerateRunReport::new(simulation, start_ts, end_ts, agents);
+            reporter.do_send(match reason {
+                Some(CompletionReason::ThresholdBreached(threshold)) => {
+                    request.breached_threshold(threshold)
+                }
+                _ => request,
+            });
         }
     }
 
@@ -173,44 +476,246 @@ impl ControllerActor {
         let agents_count = self.count_agents();
         match &self.simulation {
             SimulationState::Idle => vec![
-                Command::Stop(StopCommand { reset: true }),
+                Command::Stop(StopCommand {
+                    reset: true,
+                    abort: self.stop_mode == StopMode::Abort,
+                }),
                 Command::UpdateAgentsCount(agents_count as u32),
+                Command::Ordinals(self.agent_ordinals()),
             ],
-            SimulationState::Ready { simulation } => vec![
-                Command::Stop(StopCommand { reset: true }),
-                Command::UpdateAgentsCount(agents_count as u32),
-                Command::Load(LoadSimCommand {
-                    clients_evolution: simulation.bots.iter().cloned().map(Into::into).collect(),
-                    script: simulation.script.clone(),
+            SimulationState::Ready { simulation }
+            | SimulationState::Completed { simulation, .. } => vec![
+                Command::Stop(StopCommand {
+                    reset: true,
+                    abort: false,
                 }),
+                Command::UpdateAgentsCount(agents_count as u32),
+                Command::Ordinals(self.agent_ordinals()),
+                Command::Load(simulation.clone().into()),
             ],
             SimulationState::Launched {
                 start_ts,
                 simulation,
-            } => vec![
-                Command::Stop(StopCommand { reset: true }),
-                Command::UpdateAgentsCount(agents_count as u32),
-                Command::Load(LoadSimCommand {
-                    clients_evolution: simulation.bots.iter().cloned().map(Into::into).collect(),
-                    script: simulation.script.clone(),
-                }),
-                Command::Launch(LaunchCommand {
-                    start_ts: Some((*start_ts).into()),
-                }),
-            ],
+            } => {
+                let mut commands = vec![
+                    Command::Stop(StopCommand {
+                        reset: true,
+                        abort: false,
+                    }),
+                    Command::UpdateAgentsCount(agents_count as u32),
+                    Command::Ordinals(self.agent_ordinals()),
+                    Command::Load(simulation.clone().into()),
+                    Command::Launch(LaunchCommand {
+                        start_ts: Some((*start_ts).into()),
+                        end_ts: simulation
+                            .run_duration()
+                            .map(|run_duration| (*start_ts + run_duration).into()),
+                        simulation_id: simulation.id_ref().to_string(),
+                    }),
+                ];
+                // agents joining a running multi-stage simulation directly enter the current phase
+                if let Some(phase_cmd) = current_phase_command(simulation, *start_ts)
+                    .filter(|phase_cmd| phase_cmd.index > 0)
+                {
+                    commands.push(Command::Phase(phase_cmd));
+                }
+                commands
+            }
+        }
+    }
+
+    fn persist_state(&self) {
+        if let Some(store) = &self.state_store {
+            if let Err(err) = store.save(&self.simulation) {
+                log::error!("Error persisting simulation state - {err}");
+            }
+        }
+    }
+
+    /// Watch the thresholds and schedule the timers of the launched simulation
+    fn resume_run(&mut self, ctx: &mut Context<Self>) {
+        if let (
+            SimulationState::Launched {
+                start_ts,
+                simulation,
+            },
+            Some(slo_watcher),
+        ) = (&self.simulation, &self.slo_watcher)
+        {
+            slo_watcher.do_send(WatchThresholds::new(
+                simulation.thresholds_ref().to_vec(),
+                *start_ts,
+            ));
+        }
+        self.schedule_next_phase(ctx);
+        self.schedule_run_end(ctx);
+    }
+
+    fn cancel_run_timers(&mut self, ctx: &mut Context<Self>) {
+        for timer in [self.phase_timer.take(), self.end_timer.take()]
+            .into_iter()
+            .flatten()
+        {
+            ctx.cancel_future(timer);
+        }
+    }
+
+    /// Schedule the completion of the launched simulation, if it has a finite duration
+    fn schedule_run_end(&mut self, ctx: &mut Context<Self>) {
+        let Some(end_ts) = (match &self.simulation {
+            SimulationState::Launched {
+                start_ts,
+                simulation,
+            } => simulation
+                .run_duration()
+                .map(|run_duration| *start_ts + run_duration),
+            _ => None,
+        }) else {
+            return;
+        };
+        let delay = end_ts.duration_since(SystemTime::now()).unwrap_or_default();
+        self.end_timer = Some(ctx.run_later(delay, move |act, _ctx| {
+            act.end_timer = None;
+            act.complete_run(CompletionReason::Elapsed, end_ts);
+        }));
+    }
+
+    /// Move the launched simulation to the completed state, returns whether a run was completed
+    fn complete_run(&mut self, reason: CompletionReason, end_ts: SystemTime) -> bool {
+        let SimulationState::Launched {
+            start_ts,
+            simulation,
+        } = self.simulation.clone()
+        else {
+            return false;
+        };
+        log::info!("Simulation run completed ({reason:?})");
+        self.simulation = SimulationState::Completed {
+            start_ts,
+            end_ts,
+            reason: reason.clone(),
+            simulation: simulation.clone(),
+        };
+        self.persist_state();
+        self.terminate_run(simulation, start_ts, end_ts, Some(&reason));
+        true
+    }
+
+    /// Schedule the transition to the next phase of the launched simulation, if any
+    fn schedule_next_phase(&mut self, ctx: &mut Context<Self>) {
+        let SimulationState::Launched {
+            start_ts,
+            simulation,
+        } = &self.simulation
+        else {
+            return;
+        };
+        let now = SystemTime::now();
+        let next_transition = simulation
+            .phases_ref()
+            .iter()
+            .scan(*start_ts, |phase_end, phase| {
+                *phase_end += phase.duration_ref();
+                Some(*phase_end)
+            })
+            .find(|phase_end| *phase_end > now);
+
+        if let Some(next_transition) = next_transition {
+            let delay = next_transition.duration_since(now).unwrap_or_default();
+            self.phase_timer = Some(ctx.run_later(delay, |act, ctx| act.enter_next_phase(ctx)));
+        }
+    }
+
+    fn enter_next_phase(&mut self, ctx: &mut Context<Self>) {
+        self.phase_timer = None;
+        let SimulationState::Launched {
+            start_ts,
+            simulation,
+        } = &self.simulation
+        else {
+            return;
+        };
+
+        // the run completion after the last phase is handled by the end timer
+        if let Some(phase_cmd) = current_phase_command(simulation, *start_ts) {
+            let phase_name = simulation.phases_ref()[phase_cmd.index as usize].name_ref();
+            log::info!(
+                "Entering simulation phase {} '{phase_name}'",
+                phase_cmd.index
+            );
+            let send_fut = self.send_to_simulation_agents(vec![Command::Phase(phase_cmd)]);
+            ctx.spawn(
+                async move {
+                    if let Err(err) = send_fut.await {
+                        log::error!("Error sending phase command - {err}");
+                    }
+                }
+                .into_actor(self),
+            );
+            self.schedule_next_phase(ctx);
         }
     }
 }
 
+/// Commands sent to the agents not running a labelled simulation, they drop any previous one
+fn excluded_agent_commands() -> Vec<Command> {
+    vec![Command::Stop(StopCommand {
+        reset: true,
+        abort: false,
+    })]
+}
+
+/// Command entering the phase running at the current time, `None` if the simulation has no
+/// phases or all of them are completed
+fn current_phase_command(simulation: &SimulationDef, start_ts: SystemTime) -> Option<PhaseCommand> {
+    let elapsed = SystemTime::now()
+        .duration_since(start_ts)
+        .unwrap_or_default();
+    simulation
+        .phase_at(elapsed)
+        .map(|(index, phase_offset)| PhaseCommand {
+            index: index as u32,
+            start_ts: Some((start_ts + phase_offset).into()),
+        })
+}
+
+impl Handler<ValidateSimulation> for ControllerActor {
+    type Result = MessageResult<ValidateSimulation>;
+
+    fn handle(
+        &mut self,
+        ValidateSimulation(simulation): ValidateSimulation,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        MessageResult(self.validator.validate(&simulation))
+    }
+}
+
 impl Handler<LoadSimulation> for ControllerActor {
-    type Result = AtomicResponse<Self, ()>;
+    type Result = AtomicResponse<Self, ValidationReport>;
 
     fn handle(
         &mut self,
         LoadSimulation(simulation): LoadSimulation,
-        _ctx: &mut Self::Context,
+        ctx: &mut Self::Context,
     ) -> Self::Result {
-        self.simulation = SimulationState::Ready { simulation };
+        let report = self.validator.validate(&simulation);
+        if !report.is_valid() {
+            log::error!("Rejecting invalid simulation - {:?}", report.issues);
+            return AtomicResponse::new(Box::pin(async move { report }.into_actor(self)));
+        }
+        if !report.warnings.is_empty() {
+            log::warn!("Loading simulation with warnings - {:?}", report.warnings);
+        }
+
+        self.cancel_run_timers(ctx);
+        let simulation_id = format!("{:016x}", rand::rng().random::<u64>());
+        log::info!("Loading simulation {simulation_id}");
+        self.simulation = SimulationState::Ready {
+            simulation: simulation.id(simulation_id),
+        };
+        self.stop_mode = StopMode::Graceful;
+        self.persist_state();
 
         AtomicResponse::new(Box::pin(
             async {}
@@ -220,6 +725,7 @@ impl Handler<LoadSimulation> for ControllerActor {
                     if let Err(err) = res {
                         log::error!("Error sending load-sim command - {err}");
                     }
+                    report
                 }),
         ))
     }
@@ -231,22 +737,24 @@ impl Handler<StartSimulation> for ControllerActor {
     fn handle(
         &mut self,
         StartSimulation(start_ts): StartSimulation,
-        _ctx: &mut Self::Context,
+        ctx: &mut Self::Context,
     ) -> Self::Result {
+        self.participants.clear();
+        self.cancel_run_timers(ctx);
         self.simulation = match &self.simulation {
             SimulationState::Idle => {
                 log::warn!("Ignoring StartSimulation command as state is idle");
                 SimulationState::Idle
             }
-            SimulationState::Ready { simulation } => SimulationState::Launched {
-                start_ts,
-                simulation: simulation.clone(),
-            },
-            SimulationState::Launched { simulation, .. } => SimulationState::Launched {
+            SimulationState::Ready { simulation }
+            | SimulationState::Launched { simulation, .. }
+            | SimulationState::Completed { simulation, .. } => SimulationState::Launched {
                 start_ts,
                 simulation: simulation.clone(),
             },
         };
+        self.persist_state();
+        self.resume_run(ctx);
 
         AtomicResponse::new(Box::pin(
             async move {}
@@ -261,13 +769,194 @@ impl Handler<StartSimulation> for ControllerActor {
     }
 }
 
+impl ControllerActor {
+    /// Complete the launched simulation for the given reason and drain the running bots
+    fn stop_run(
+        &mut self,
+        reason: CompletionReason,
+        ctx: &mut Context<Self>,
+    ) -> AtomicResponse<Self, ()> {
+        self.cancel_run_timers(ctx);
+        let stop_fut = if self.complete_run(reason, SystemTime::now()) {
+            self.stop_mode = StopMode::Graceful;
+            // running agents keep the loaded simulation and just drain their bots
+            Some(
+                self.send_to_simulation_agents(vec![Command::Stop(StopCommand {
+                    reset: false,
+                    abort: false,
+                })]),
+            )
+        } else {
+            log::warn!("Ignoring stop command as simulation is not launched");
+            None
+        };
+
+        AtomicResponse::new(Box::pin(
+            async move {
+                if let Some(stop_fut) = stop_fut {
+                    if let Err(err) = stop_fut.await {
+                        log::error!("Error sending stop command - {err}");
+                    }
+                }
+            }
+            .into_actor(self),
+        ))
+    }
+}
+
+impl Handler<StopSimulation> for ControllerActor {
+    type Result = AtomicResponse<Self, ()>;
+
+    fn handle(&mut self, _msg: StopSimulation, ctx: &mut Self::Context) -> Self::Result {
+        self.stop_run(CompletionReason::Stopped, ctx)
+    }
+}
+
+impl Handler<FailSimulation> for ControllerActor {
+    type Result = AtomicResponse<Self, ()>;
+
+    fn handle(&mut self, msg: FailSimulation, ctx: &mut Self::Context) -> Self::Result {
+        self.stop_run(CompletionReason::ThresholdBreached(msg.threshold), ctx)
+    }
+}
+
+impl Handler<AbortSimulation> for ControllerActor {
+    type Result = AtomicResponse<Self, ()>;
+
+    fn handle(&mut self, _msg: AbortSimulation, ctx: &mut Self::Context) -> Self::Result {
+        self.cancel_run_timers(ctx);
+        if let SimulationState::Launched {
+            simulation,
+            start_ts,
+        } = std::mem::replace(&mut self.simulation, SimulationState::Idle)
+        {
+            self.terminate_run(simulation, start_ts, SystemTime::now(), None);
+        }
+        self.stop_mode = StopMode::Abort;
+        self.persist_state();
+
+        AtomicResponse::new(Box::pin(
+            async {}
+                .into_actor(self)
+                .then(|_, act, _ctx| act.broadcast_simulation_state().into_actor(act))
+                .map(|res, _, _| {
+                    if let Err(err) = res {
+                        log::error!("Error sending abort command - {err}");
+                    }
+                }),
+        ))
+    }
+}
+
+impl Handler<FetchSimulationStatus> for ControllerActor {
+    type Result = MessageResult<FetchSimulationStatus>;
+
+    fn handle(&mut self, _msg: FetchSimulationStatus, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(match &self.simulation {
+            SimulationState::Idle => SimulationStatus::Idle,
+            SimulationState::Ready { .. } => SimulationStatus::Ready,
+            SimulationState::Launched {
+                start_ts,
+                simulation,
+            } => SimulationStatus::Launched {
+                start_ts: *start_ts,
+                end_ts: simulation
+                    .run_duration()
+                    .map(|run_duration| *start_ts + run_duration),
+            },
+            SimulationState::Completed {
+                start_ts,
+                end_ts,
+                reason,
+                ..
+            } => SimulationStatus::Completed {
+                start_ts: *start_ts,
+                end_ts: *end_ts,
+                reason: reason.clone(),
+            },
+        })
+    }
+}
+
+impl Handler<MultiCommandAckMessage> for ControllerActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        MultiCommandAckMessage(acks): MultiCommandAckMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        for CommandAck {
+            agent_id,
+            command_id,
+            simulation_id,
+            errors,
+        } in acks
+        {
+            if errors.is_empty() {
+                log::debug!("Agent {agent_id} acknowledged command {command_id:016x}");
+                // only commands loading or launching a simulation clear a previous rejection
+                if !simulation_id.is_empty() {
+                    self.rejections.remove(&agent_id);
+                }
+            } else {
+                for error in errors.iter() {
+                    log::error!(
+                        "Agent {agent_id} rejected command {command_id:016x} - {:?} {}",
+                        error.kind(),
+                        error.message
+                    );
+                }
+                self.rejections.insert(
+                    agent_id,
+                    CommandRejection {
+                        simulation_id,
+                        errors,
+                    },
+                );
+            }
+        }
+    }
+}
+
+impl Handler<FetchAgents> for ControllerActor {
+    type Result = MessageResult<FetchAgents>;
+
+    fn handle(&mut self, _msg: FetchAgents, _ctx: &mut Self::Context) -> Self::Result {
+        let mut agents = self
+            .agents_state
+            .iter()
+            .map(|(agent_id, agent)| AgentInfo {
+                agent_id: *agent_id,
+                name: agent.name.clone(),
+                labels: agent.labels.clone(),
+                capacity: agent.capacity,
+                state: agent.state,
+                last_update: agent.timestamp,
+                errors: self
+                    .rejection_errors(*agent_id)
+                    .map(<[_]>::to_vec)
+                    .unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        agents.sort_by_key(|agent| agent.agent_id);
+        MessageResult(agents)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::communication::message::MultiAgentUpdateMessage;
-    use crate::communication::protobuf::grpc::AgentUpdate;
+    use crate::communication::protobuf::grpc::controller_command::Target;
+    use crate::communication::protobuf::grpc::{fixture, AgentUpdate, LabelSelector, MultiAgent};
     use crate::communication::server_actor::GrpcServerActor;
-    use actix::{Actor, Context, Handler};
+    use crate::controller::model::simulation::{BotDef, PhaseDef, SimulationDef};
+    use crate::controller::persistence::StateStoreError;
+    use crate::controller::report::actor::RunReportError;
+    use crate::controller::report::model::RunReport;
+    use actix::{Actor, Addr, Context, Handler};
+    use std::sync::{Arc, Mutex};
 
     struct MockMetricsStorage;
     impl Actor for MockMetricsStorage {
@@ -278,6 +967,679 @@ mod test {
         fn handle(&mut self, _msg: MultiAgentUpdateMessage, _ctx: &mut Self::Context) {}
     }
 
+    /// Commands sent downstream in a single message, with their target
+    type RecordedCommands = (Option<Target>, Vec<Command>);
+
+    #[derive(Default, Clone)]
+    struct RecordingDownstream(Arc<Mutex<Vec<RecordedCommands>>>);
+    impl Actor for RecordingDownstream {
+        type Context = Context<Self>;
+    }
+    impl Handler<ControllerCommandMessage> for RecordingDownstream {
+        type Result = ();
+        fn handle(&mut self, msg: ControllerCommandMessage, _ctx: &mut Self::Context) {
+            self.0.lock().unwrap().push((
+                msg.0.target,
+                msg.0
+                    .commands
+                    .into_iter()
+                    .filter_map(|item| item.command)
+                    .collect(),
+            ));
+        }
+    }
+
+    impl RecordingDownstream {
+        fn last_stop(&self) -> Option<StopCommand> {
+            self.0.lock().unwrap().last().and_then(|(_, cmds)| {
+                cmds.iter().find_map(|cmd| match cmd {
+                    Command::Stop(stop) => Some(*stop),
+                    _ => None,
+                })
+            })
+        }
+
+        /// Commands sent so far, selected and mapped by `select`
+        fn commands<T>(&self, select: impl Fn(&Command) -> Option<T>) -> Vec<T> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .flat_map(|(_, cmds)| cmds)
+                .filter_map(select)
+                .collect()
+        }
+
+        /// Last command sent, amongst the ones selected and mapped by `select`
+        fn last_command<T>(&self, select: impl Fn(&Command) -> Option<T>) -> Option<T> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .flat_map(|(_, cmds)| cmds)
+                .rev()
+                .find_map(select)
+        }
+
+        /// Targets of the commands sent so far, selected and mapped by `select`
+        fn targets<T>(&self, select: impl Fn(&Command) -> Option<T>) -> Vec<(Target, T)> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .flat_map(|(target, cmds)| {
+                    cmds.iter()
+                        .filter_map(&select)
+                        .filter_map(move |selected| Some((target.clone()?, selected)))
+                })
+                .collect()
+        }
+    }
+
+    fn agent_update(
+        agent_id: u32,
+        state: grpc::AgentSimulationState,
+        simulation_id: &str,
+    ) -> AgentUpdate {
+        AgentUpdate {
+            update_id: 1,
+            state: state as i32,
+            simulation_id: simulation_id.to_string(),
+            ..fixture::agent_update(agent_id, SystemTime::now(), vec![])
+        }
+    }
+
+    #[derive(Default, Clone)]
+    struct RecordingReporter(Arc<Mutex<Vec<GenerateRunReport>>>);
+    impl Actor for RecordingReporter {
+        type Context = Context<Self>;
+    }
+    impl Handler<GenerateRunReport> for RecordingReporter {
+        type Result = Result<RunReport, RunReportError>;
+        fn handle(&mut self, msg: GenerateRunReport, _ctx: &mut Self::Context) -> Self::Result {
+            let report = RunReport::new(
+                &msg.simulation,
+                msg.start_ts,
+                msg.end_ts,
+                msg.agents.clone(),
+                Default::default(),
+                &[],
+                msg.breached_threshold.clone(),
+            );
+            self.0.lock().unwrap().push(msg);
+            Ok(report)
+        }
+    }
+
+    const DEMO_SCRIPT: &str =
+        "struct Demo {} impl Demo { pub fn new(p) { Self {} } pub fn register_bot(bot) {} }";
+
+    /// Simulation passing the controller validation
+    fn demo_simulation() -> SimulationDef {
+        SimulationDef::default().script(DEMO_SCRIPT.to_string())
+    }
+
+    fn start_recording_controller() -> (Addr<ControllerActor>, RecordingDownstream) {
+        let downstream = RecordingDownstream::default();
+        let controller = ControllerActor::new(
+            DownstreamClient::new(downstream.clone().start().recipient()),
+            MockMetricsStorage.start().recipient(),
+        );
+        (controller.start(), downstream)
+    }
+
+    #[actix::test]
+    async fn stop_drains_launched_simulation() {
+        let (controller_addr, downstream) = start_recording_controller();
+
+        controller_addr
+            .send(LoadSimulation::new(demo_simulation()))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(StartSimulation::at(SystemTime::now()))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(StopSimulation)
+            .await
+            .expect("send failed");
+
+        let stop = downstream.last_stop().expect("no stop command sent");
+        assert!(!stop.reset);
+        assert!(!stop.abort);
+        assert!(matches!(
+            controller_addr.send(FetchSimulationStatus).await,
+            Ok(SimulationStatus::Completed {
+                reason: CompletionReason::Stopped,
+                ..
+            })
+        ));
+    }
+
+    #[actix::test]
+    async fn breached_threshold_fails_launched_simulation() {
+        let downstream = RecordingDownstream::default();
+        let reporter = RecordingReporter::default();
+        let controller_addr = ControllerActor::new(
+            DownstreamClient::new(downstream.clone().start().recipient()),
+            MockMetricsStorage.start().recipient(),
+        )
+        .run_reporter(reporter.clone().start().recipient())
+        .start();
+
+        controller_addr
+            .send(LoadSimulation::new(demo_simulation()))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(StartSimulation::at(SystemTime::now()))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(FailSimulation {
+                threshold: String::from("error_rate < 1%"),
+            })
+            .await
+            .expect("send failed");
+
+        // bots drain as on a stop and the simulation stays loaded
+        let stop = downstream.last_stop().expect("no stop command sent");
+        assert!(!stop.reset);
+        assert!(!stop.abort);
+        assert!(matches!(
+            controller_addr.send(FetchSimulationStatus).await,
+            Ok(SimulationStatus::Completed {
+                reason: CompletionReason::ThresholdBreached(threshold),
+                ..
+            }) if threshold == "error_rate < 1%"
+        ));
+
+        // let the reporter mailbox process the request
+        actix::clock::sleep(Duration::from_millis(50)).await;
+        let requests = reporter.0.lock().unwrap();
+        assert_eq!(
+            requests[0].breached_threshold.as_deref(),
+            Some("error_rate < 1%")
+        );
+    }
+
+    #[actix::test]
+    async fn stop_generates_run_report() {
+        let downstream = RecordingDownstream::default();
+        let reporter = RecordingReporter::default();
+        let controller_addr = ControllerActor::new(
+            DownstreamClient::new(downstream.start().recipient()),
+            MockMetricsStorage.start().recipient(),
+        )
+        .run_reporter(reporter.clone().start().recipient())
+        .start();
+
+        let start_ts = SystemTime::now();
+        controller_addr
+            .send(LoadSimulation::new(demo_simulation()))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(StartSimulation::at(start_ts))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(MultiAgentUpdateMessage(vec![agent_update(
+                7,
+                grpc::AgentSimulationState::Running,
+                "",
+            )]))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(StopSimulation)
+            .await
+            .expect("send failed");
+        // let the reporter mailbox process the request
+        actix::clock::sleep(Duration::from_millis(50)).await;
+
+        let requests = reporter.0.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].start_ts, start_ts);
+        assert_eq!(requests[0].agents.len(), 1);
+        assert!(requests[0].agents[0].running);
+    }
+
+    #[actix::test]
+    async fn phases_are_entered_in_sequence() {
+        let (controller_addr, downstream) = start_recording_controller();
+
+        let phase = |name: &str| {
+            PhaseDef::default()
+                .name(name)
+                .duration(Duration::from_millis(100))
+                .bots(vec![BotDef::default().model("Demo").shape("10")])
+        };
+        controller_addr
+            .send(LoadSimulation::new(
+                demo_simulation().phases(vec![phase("warm-up"), phase("steady")]),
+            ))
+            .await
+            .expect("send failed");
+        let start_ts = SystemTime::now();
+        controller_addr
+            .send(StartSimulation::at(start_ts))
+            .await
+            .expect("send failed");
+        actix::clock::sleep(Duration::from_millis(350)).await;
+
+        let phases = downstream.commands(|cmd| match cmd {
+            Command::Phase(phase) => Some(*phase),
+            _ => None,
+        });
+        assert_eq!(phases.len(), 1);
+        assert_eq!(phases[0].index, 1);
+        assert_eq!(
+            phases[0].start_ts,
+            Some((start_ts + Duration::from_millis(100)).into())
+        );
+
+        // the run completes once the last phase has elapsed
+        let status = controller_addr
+            .send(FetchSimulationStatus)
+            .await
+            .expect("send failed");
+        assert_eq!(
+            status,
+            SimulationStatus::Completed {
+                start_ts,
+                end_ts: start_ts + Duration::from_millis(200),
+                reason: CompletionReason::Elapsed,
+            }
+        );
+    }
+
+    #[derive(Default, Clone)]
+    struct MemoryStateStore(Arc<Mutex<Option<SimulationState>>>);
+    impl SimulationStateStore for MemoryStateStore {
+        fn load(&self) -> Result<Option<SimulationState>, StateStoreError> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+        fn save(&self, state: &SimulationState) -> Result<(), StateStoreError> {
+            *self.0.lock().unwrap() = Some(state.clone());
+            Ok(())
+        }
+    }
+
+    #[actix::test]
+    async fn restarted_controller_resumes_launched_simulation() {
+        let store = MemoryStateStore::default();
+        let start_ts = SystemTime::now() - Duration::from_secs(1);
+        let controller_addr = ControllerActor::new(
+            DownstreamClient::new(RecordingDownstream::default().start().recipient()),
+            MockMetricsStorage.start().recipient(),
+        )
+        .state_store(Box::new(store.clone()))
+        .start();
+        controller_addr
+            .send(LoadSimulation::new(demo_simulation()))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(StartSimulation::at(start_ts))
+            .await
+            .expect("send failed");
+
+        let downstream = RecordingDownstream::default();
+        let restarted_addr = ControllerActor::new(
+            DownstreamClient::new(downstream.clone().start().recipient()),
+            MockMetricsStorage.start().recipient(),
+        )
+        .state_store(Box::new(store))
+        .start();
+        let status = restarted_addr
+            .send(FetchSimulationStatus)
+            .await
+            .expect("send failed");
+        assert_eq!(
+            status,
+            SimulationStatus::Launched {
+                start_ts,
+                end_ts: None
+            }
+        );
+
+        restarted_addr
+            .send(MultiAgentUpdateMessage(vec![agent_update(
+                7,
+                grpc::AgentSimulationState::Running,
+                "",
+            )]))
+            .await
+            .expect("send failed");
+        // running agents are aligned with the resumed simulation and are not reset
+        assert!(downstream
+            .last_command(|cmd| matches!(cmd, Command::Stop(_)).then_some(()))
+            .is_none());
+    }
+
+    #[actix::test]
+    async fn live_agents_get_dense_ordinals() {
+        let (controller_addr, downstream) = start_recording_controller();
+        let idle_update =
+            |agent_id: u32| agent_update(agent_id, grpc::AgentSimulationState::Idle, "");
+        let last_ordinals = || {
+            downstream
+                .last_command(|cmd| match cmd {
+                    Command::Ordinals(ordinals) => Some(ordinals.ordinals.clone()),
+                    _ => None,
+                })
+                .expect("no ordinals sent")
+        };
+
+        controller_addr
+            .send(MultiAgentUpdateMessage(vec![
+                idle_update(4_000_000_000),
+                idle_update(17),
+            ]))
+            .await
+            .expect("send failed");
+        assert_eq!(last_ordinals(), [(17, 0), (4_000_000_000, 1)].into());
+
+        controller_addr
+            .send(MultiAgentUpdateMessage(vec![idle_update(3)]))
+            .await
+            .expect("send failed");
+        assert_eq!(
+            last_ordinals(),
+            [(3, 0), (17, 1), (4_000_000_000, 2)].into()
+        );
+    }
+
+    #[actix::test]
+    async fn agent_shares_follow_capacity() {
+        let (controller_addr, downstream) = start_recording_controller();
+        let capacity_update = |agent_id: u32, capacity: Option<grpc::AgentCapacity>| AgentUpdate {
+            capacity,
+            ..agent_update(agent_id, grpc::AgentSimulationState::Idle, "")
+        };
+        let capacity = |max_running: u32, weight: f64| grpc::AgentCapacity {
+            max_running,
+            max_rate: 0,
+            weight,
+        };
+
+        controller_addr
+            .send(MultiAgentUpdateMessage(vec![
+                capacity_update(1, Some(capacity(100, 0.0))),
+                capacity_update(2, Some(capacity(100, 3.0))),
+                capacity_update(3, Some(capacity(0, 0.0))),
+                capacity_update(4, None),
+            ]))
+            .await
+            .expect("send failed");
+
+        let weights = downstream
+            .last_command(|cmd| match cmd {
+                Command::Ordinals(ordinals) => Some(ordinals.weights.clone()),
+                _ => None,
+            })
+            .expect("no ordinals sent");
+        // unbounded agents count as the average max_running of the bounded ones
+        for (agent_id, expected) in [(1, 1.0 / 6.0), (2, 0.5), (3, 1.0 / 6.0), (4, 1.0 / 6.0)] {
+            assert!((weights[&agent_id] - expected).abs() < 1e-9, "{weights:?}");
+        }
+    }
+
+    #[actix::test]
+    async fn labelled_simulation_only_targets_matching_agents() {
+        let (controller_addr, downstream) = start_recording_controller();
+        let pool = |name: &str| HashMap::from([(String::from("pool"), name.to_string())]);
+        controller_addr
+            .send(MultiAgentUpdateMessage(vec![
+                AgentUpdate {
+                    labels: pool("mobile"),
+                    ..agent_update(1, grpc::AgentSimulationState::Idle, "")
+                },
+                AgentUpdate {
+                    labels: pool("web"),
+                    ..agent_update(2, grpc::AgentSimulationState::Ready, "")
+                },
+            ]))
+            .await
+            .expect("send failed");
+
+        controller_addr
+            .send(LoadSimulation::new(
+                demo_simulation().agent_labels(pool("mobile")),
+            ))
+            .await
+            .expect("send failed");
+
+        let load_targets = downstream.targets(|cmd| match cmd {
+            Command::Load(_) => Some(()),
+            _ => None,
+        });
+        assert_eq!(
+            load_targets,
+            vec![(Target::Labels(LabelSelector::new(pool("mobile"))), ())]
+        );
+        let ordinals = downstream
+            .last_command(|cmd| match cmd {
+                Command::Ordinals(ordinals) => Some(ordinals.ordinals.clone()),
+                _ => None,
+            })
+            .expect("no ordinals sent");
+        assert_eq!(ordinals, HashMap::from([(1, 0)]));
+
+        // the agent out of the pool drops the simulation it had loaded
+        let stop_targets = downstream.targets(|cmd| match cmd {
+            Command::Stop(stop) => Some(stop.reset),
+            _ => None,
+        });
+        assert_eq!(
+            stop_targets.last(),
+            Some(&(Target::Agents(MultiAgent { agent_ids: vec![2] }), true))
+        );
+    }
+
+    #[derive(Default, Clone)]
+    struct RecordingMetricsStorage(Arc<Mutex<Vec<AgentUpdate>>>);
+    impl Actor for RecordingMetricsStorage {
+        type Context = Context<Self>;
+    }
+    impl Handler<MultiAgentUpdateMessage> for RecordingMetricsStorage {
+        type Result = ();
+        fn handle(&mut self, msg: MultiAgentUpdateMessage, _ctx: &mut Self::Context) {
+            self.0.lock().unwrap().extend(msg.0);
+        }
+    }
+
+    #[actix::test]
+    async fn stale_simulation_updates_are_discarded() {
+        let downstream = RecordingDownstream::default();
+        let metrics = RecordingMetricsStorage::default();
+        let controller_addr = ControllerActor::new(
+            DownstreamClient::new(downstream.clone().start().recipient()),
+            metrics.clone().start().recipient(),
+        )
+        .start();
+        controller_addr
+            .send(LoadSimulation::new(demo_simulation()))
+            .await
+            .expect("send failed");
+
+        controller_addr
+            .send(MultiAgentUpdateMessage(vec![
+                agent_update(1, grpc::AgentSimulationState::Ready, "previous-run"),
+                agent_update(2, grpc::AgentSimulationState::Ready, ""),
+            ]))
+            .await
+            .expect("send failed");
+
+        let forwarded = metrics
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|update| update.agent_id)
+            .collect::<Vec<_>>();
+        assert_eq!(forwarded, vec![2]);
+
+        // besides the initial broadcast, the agent still on the previous simulation is reloaded
+        let load_ids = downstream.commands(|cmd| match cmd {
+            Command::Load(load) => Some(load.simulation_id.clone()),
+            _ => None,
+        });
+        assert_eq!(load_ids.len(), 2);
+        assert_eq!(load_ids[0].len(), 16);
+        assert_eq!(load_ids[0], load_ids[1]);
+    }
+
+    #[actix::test]
+    async fn stale_agent_is_relaunched_before_taking_part() {
+        let downstream = RecordingDownstream::default();
+        let reporter = RecordingReporter::default();
+        let controller_addr = ControllerActor::new(
+            DownstreamClient::new(downstream.clone().start().recipient()),
+            MockMetricsStorage.start().recipient(),
+        )
+        .run_reporter(reporter.clone().start().recipient())
+        .start();
+        controller_addr
+            .send(LoadSimulation::new(demo_simulation()))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(StartSimulation::at(SystemTime::now()))
+            .await
+            .expect("send failed");
+
+        controller_addr
+            .send(MultiAgentUpdateMessage(vec![agent_update(
+                7,
+                grpc::AgentSimulationState::Running,
+                "previous-run",
+            )]))
+            .await
+            .expect("send failed");
+
+        let launch_ids = downstream.targets(|cmd| match cmd {
+            Command::Launch(launch) => Some(launch.simulation_id.clone()),
+            _ => None,
+        });
+        let (target, simulation_id) = launch_ids.last().expect("no launch resent");
+        assert_eq!(target, &Target::Agents(MultiAgent { agent_ids: vec![7] }));
+
+        controller_addr
+            .send(StopSimulation)
+            .await
+            .expect("send failed");
+        actix::clock::sleep(Duration::from_millis(50)).await;
+        assert!(reporter.0.lock().unwrap()[0].agents.is_empty());
+
+        // once relaunched the agent takes part in the next run
+        controller_addr
+            .send(StartSimulation::at(SystemTime::now()))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(MultiAgentUpdateMessage(vec![AgentUpdate {
+                timestamp: Some((SystemTime::now() + Duration::from_millis(1)).into()),
+                ..agent_update(7, grpc::AgentSimulationState::Running, simulation_id)
+            }]))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(StopSimulation)
+            .await
+            .expect("send failed");
+        actix::clock::sleep(Duration::from_millis(50)).await;
+        let requests = reporter.0.lock().unwrap();
+        assert_eq!(requests[1].agents.len(), 1);
+        assert!(requests[1].agents[0].running);
+    }
+
+    #[actix::test]
+    async fn rejected_simulation_is_not_reloaded() {
+        let downstream = RecordingDownstream::default();
+        let controller_addr = ControllerActor::new(
+            DownstreamClient::new(downstream.clone().start().recipient()),
+            MockMetricsStorage.start().recipient(),
+        )
+        .start();
+        controller_addr
+            .send(LoadSimulation::new(demo_simulation()))
+            .await
+            .expect("send failed");
+
+        // an agent failing to load the script stays idle
+        let idle_update = || agent_update(1, grpc::AgentSimulationState::Idle, "");
+        let load_ids = || {
+            downstream.commands(|cmd| match cmd {
+                Command::Load(load) => Some(load.simulation_id.clone()),
+                _ => None,
+            })
+        };
+
+        controller_addr
+            .send(MultiAgentUpdateMessage(vec![idle_update()]))
+            .await
+            .expect("send failed");
+        assert_eq!(load_ids().len(), 2);
+
+        controller_addr
+            .send(MultiCommandAckMessage(vec![CommandAck {
+                agent_id: 1,
+                command_id: 1,
+                simulation_id: load_ids()[0].clone(),
+                errors: vec![grpc::CommandError {
+                    kind: grpc::CommandErrorKind::UnknownModel.into(),
+                    message: String::from("No bot-model defined with name A"),
+                    model: String::from("A"),
+                }],
+            }]))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(MultiAgentUpdateMessage(vec![idle_update()]))
+            .await
+            .expect("send failed");
+        assert_eq!(load_ids().len(), 2);
+
+        let agents = controller_addr
+            .send(FetchAgents)
+            .await
+            .expect("send failed");
+        assert_eq!(agents[0].errors.len(), 1);
+        assert_eq!(agents[0].errors[0].model, "A");
+
+        // a new simulation is sent to the agent again
+        controller_addr
+            .send(LoadSimulation::new(demo_simulation()))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(MultiAgentUpdateMessage(vec![idle_update()]))
+            .await
+            .expect("send failed");
+        assert_eq!(load_ids().len(), 4);
+    }
+
+    #[actix::test]
+    async fn abort_resets_agents_skipping_hooks() {
+        let (controller_addr, downstream) = start_recording_controller();
+
+        controller_addr
+            .send(LoadSimulation::new(demo_simulation()))
+            .await
+            .expect("send failed");
+        controller_addr
+            .send(AbortSimulation)
+            .await
+            .expect("send failed");
+
+        let stop = downstream.last_stop().expect("no stop command sent");
+        assert!(stop.reset);
+        assert!(stop.abort);
+    }
+
     #[actix::test]
     async fn controller_tracks_new_agents() {
         let metrics = MockMetricsStorage.start();
@@ -292,15 +1654,21 @@ mod test {
         let controller_addr = controller.start();
 
         let update = MultiAgentUpdateMessage(vec![AgentUpdate {
-            agent_id: 42,
-            stats: vec![],
-            update_id: 1,
-            timestamp: Some(SystemTime::now().into()),
-            name: String::new(),
-            state: grpc::AgentSimulationState::Idle as i32,
-            simulation_id: String::new(),
+            name: String::from("eu-agent"),
+            labels: [(String::from("region"), String::from("eu-west"))].into(),
+            ..agent_update(42, grpc::AgentSimulationState::Idle, "")
         }]);
 
         controller_addr.send(update).await.expect("send failed");
+
+        let agents = controller_addr
+            .send(FetchAgents)
+            .await
+            .expect("send failed");
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0].agent_id, 42);
+        assert_eq!(agents[0].state, grpc::AgentSimulationState::Idle);
+        assert_eq!(agents[0].name, "eu-agent");
+        assert_eq!(agents[0].labels["region"], "eu-west");
     }
 }
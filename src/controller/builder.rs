@@ -3,8 +3,13 @@ use crate::communication::server::HailstormGrpcServer;
 use crate::communication::server_actor::GrpcServerActor;
 use crate::controller::actor::ControllerActor;
 use crate::controller::client::downstream::DownstreamClient;
+use crate::controller::metrics::message::{FetchMetricsWindows, QueryMetrics};
+use crate::controller::persistence::SimulationStateStore;
+use crate::controller::report::actor::{RunReportActor, RunReportConfig};
+use crate::controller::slo::actor::{SloWatcherActor, SloWatcherConfig};
+use crate::controller::validation::SimulationValidator;
 use crate::MultiAgentUpdateMessage;
-use actix::{Actor, Addr, AsyncContext, Context, Handler};
+use actix::{Actor, Addr, AsyncContext, Context, Handler, Recipient};
 use std::net::SocketAddr;
 use tonic::transport::server::Router;
 use tonic::transport::Server;
@@ -12,12 +17,20 @@ use tonic::transport::Server;
 /// Struct used to build a controller instance
 pub struct ControllerBuilder<MetricsStorage> {
     metrics_storage: MetricsStorage,
+    run_reporter: Option<RunReportActor>,
+    slo_watcher: Option<(SloWatcherConfig, Recipient<QueryMetrics>)>,
+    state_store: Option<Box<dyn SimulationStateStore>>,
+    validator: Option<SimulationValidator>,
 }
 
 impl Default for ControllerBuilder<()> {
     fn default() -> Self {
         Self {
             metrics_storage: (),
+            run_reporter: None,
+            slo_watcher: None,
+            state_store: None,
+            validator: None,
         }
     }
 }
@@ -34,6 +47,10 @@ impl<MetricsStorage> ControllerBuilder<MetricsStorage> {
     {
         ControllerBuilder {
             metrics_storage: metrics_storage_addr,
+            run_reporter: None,
+            slo_watcher: None,
+            state_store: self.state_store,
+            validator: self.validator,
         }
     }
 }
@@ -43,16 +60,85 @@ where
     MetricsStorageAct:
         Actor<Context = Context<MetricsStorageAct>> + Handler<MultiAgentUpdateMessage>,
 {
+    /// Generate a report (see [`RunReportConfig`]) each time a launched simulation is stopped
+    pub fn run_report(self, config: RunReportConfig) -> Self
+    where
+        MetricsStorageAct: Handler<QueryMetrics> + Handler<FetchMetricsWindows>,
+    {
+        let run_reporter = RunReportActor::new(config, self.metrics_storage.clone());
+        Self {
+            run_reporter: Some(run_reporter),
+            ..self
+        }
+    }
+
+    /// Evaluate the simulation thresholds (see [`SloWatcherConfig`]) and stop the runs
+    /// breaching a critical one as failed
+    pub fn slo_watcher(self, config: SloWatcherConfig) -> Self
+    where
+        MetricsStorageAct: Handler<QueryMetrics>,
+    {
+        let metrics_recipient = self.metrics_storage.clone().recipient();
+        Self {
+            slo_watcher: Some((config, metrics_recipient)),
+            ..self
+        }
+    }
+
+    /// Persist the simulation state in the given store (e.g.
+    /// [`FileStateStore`](crate::controller::persistence::file::FileStateStore)) so that a
+    /// restarted controller resumes the loaded or launched simulation
+    pub fn state_store(self, store: impl SimulationStateStore + 'static) -> Self {
+        Self {
+            state_store: Some(Box::new(store)),
+            ..self
+        }
+    }
+
+    /// Compile the scripts of the loaded simulations with the rune context returned by the given
+    /// builder, needed when bot scripts rely on custom modules (e.g. `env` or `storage`); scripts
+    /// that do not compile are then rejected, otherwise they only raise validation warnings
+    pub fn rune_context_builder(
+        self,
+        context_builder: impl Fn() -> rune::Context + Send + 'static,
+    ) -> Self {
+        Self {
+            validator: Some(SimulationValidator::new(context_builder)),
+            ..self
+        }
+    }
+
     /// Build controller app
     pub async fn build(self) -> ControllerApp {
         let controller_ctx: Context<ControllerActor> = Context::new();
         let grpc_server_ctx: Context<GrpcServerActor> = Context::new();
 
-        let controller_actor = ControllerActor::new(
+        let mut controller_actor = ControllerActor::new(
             DownstreamClient::new(grpc_server_ctx.address().recipient()),
             self.metrics_storage.recipient(),
         );
-        let grpc_server_actor = GrpcServerActor::new(controller_ctx.address().recipient());
+        if let Some(validator) = self.validator {
+            controller_actor = controller_actor.validator(validator);
+        }
+        if let Some(state_store) = self.state_store {
+            controller_actor = controller_actor.state_store(state_store);
+        }
+        if let Some(run_reporter) = self.run_reporter {
+            controller_actor = controller_actor.run_reporter(run_reporter.start().recipient());
+        }
+        let slo_watcher_addr = self.slo_watcher.map(|(config, metrics_recipient)| {
+            SloWatcherActor::new(
+                config,
+                metrics_recipient,
+                controller_ctx.address().recipient(),
+            )
+            .start()
+        });
+        if let Some(slo_watcher_addr) = &slo_watcher_addr {
+            controller_actor = controller_actor.slo_watcher(slo_watcher_addr.clone());
+        }
+        let grpc_server_actor = GrpcServerActor::new(controller_ctx.address().recipient())
+            .command_acks(controller_ctx.address().recipient());
 
         let server_addr = grpc_server_ctx.run(grpc_server_actor);
         let controller_addr = controller_ctx.run(controller_actor);
@@ -64,6 +150,7 @@ where
 
         ControllerApp {
             controller_addr,
+            slo_watcher_addr,
             grpc_router: router,
         }
     }
@@ -72,6 +159,7 @@ where
 /// Controller application state
 pub struct ControllerApp {
     controller_addr: Addr<ControllerActor>,
+    slo_watcher_addr: Option<Addr<SloWatcherActor>>,
     grpc_router: Router,
 }
 
@@ -81,6 +169,21 @@ impl ControllerApp {
         self.controller_addr.clone()
     }
 
+    /// Get address of the thresholds watcher, if enabled
+    pub fn slo_watcher_addr(&self) -> Option<Addr<SloWatcherActor>> {
+        self.slo_watcher_addr.clone()
+    }
+
+    /// Create an HTTP admin API bound to this controller
+    #[cfg(feature = "http-api")]
+    pub fn http_api(&self) -> crate::controller::http::server::HttpApi {
+        let api = crate::controller::http::server::HttpApi::new(self.controller_addr());
+        match self.slo_watcher_addr() {
+            Some(slo_watcher_addr) => api.slo_watcher(slo_watcher_addr),
+            None => api,
+        }
+    }
+
     /// Launch the controller and expose the gRPC API.
     ///
     /// # Errors
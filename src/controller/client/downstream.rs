@@ -1,9 +1,10 @@
 use crate::communication::message::ControllerCommandMessage;
 use crate::grpc::command_item::Command;
 use crate::grpc::controller_command::Target;
-use crate::grpc::{AgentGroup, CommandItem, ControllerCommand, MultiAgent};
+use crate::grpc::{AgentGroup, CommandItem, ControllerCommand, LabelSelector, MultiAgent};
 use actix::dev::RecipientRequest;
 use actix::Recipient;
+use rand::Rng;
 
 pub struct DownstreamClient {
     recipient: Recipient<ControllerCommandMessage>,
@@ -13,9 +14,11 @@ impl DownstreamClient {
     pub fn new(recipient: Recipient<ControllerCommandMessage>) -> Self {
         Self { recipient }
     }
-    pub fn send_to_agent(
-        &mut self,
-        agent_id: u32,
+
+    /// Send the commands to the given target, with a random id used by agents to acknowledge them
+    fn send(
+        &self,
+        target: Target,
         commands: Vec<Command>,
     ) -> RecipientRequest<ControllerCommandMessage> {
         self.recipient
@@ -24,36 +27,39 @@ impl DownstreamClient {
                     .into_iter()
                     .map(|cmd| CommandItem { command: Some(cmd) })
                     .collect(),
-                target: Some(Target::AgentId(agent_id)),
+                target: Some(target),
+                command_id: rand::rng().random_range(1..=u64::MAX),
             }))
     }
 
+    pub fn send_to_agent(
+        &mut self,
+        agent_id: u32,
+        commands: Vec<Command>,
+    ) -> RecipientRequest<ControllerCommandMessage> {
+        self.send(Target::AgentId(agent_id), commands)
+    }
+
     pub fn send_to_agents(
         &mut self,
         agent_ids: Vec<u32>,
         commands: Vec<Command>,
     ) -> RecipientRequest<ControllerCommandMessage> {
-        self.recipient
-            .send(ControllerCommandMessage(ControllerCommand {
-                commands: commands
-                    .into_iter()
-                    .map(|cmd| CommandItem { command: Some(cmd) })
-                    .collect(),
-                target: Some(Target::Agents(MultiAgent { agent_ids })),
-            }))
+        self.send(Target::Agents(MultiAgent { agent_ids }), commands)
+    }
+
+    pub fn send_to_labels(
+        &self,
+        selector: LabelSelector,
+        commands: Vec<Command>,
+    ) -> RecipientRequest<ControllerCommandMessage> {
+        self.send(Target::Labels(selector), commands)
     }
 
     pub fn send_broadcast(
         &self,
         commands: Vec<Command>,
     ) -> RecipientRequest<ControllerCommandMessage> {
-        self.recipient
-            .send(ControllerCommandMessage(ControllerCommand {
-                commands: commands
-                    .into_iter()
-                    .map(|cmd| CommandItem { command: Some(cmd) })
-                    .collect(),
-                target: Some(Target::Group(AgentGroup::All.into())),
-            }))
+        self.send(Target::Group(AgentGroup::All.into()), commands)
     }
 }
@@ -0,0 +1,575 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime, TryFromFloatSecsError, UNIX_EPOCH};
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::agent::metrics::custom::{CustomMetricKey, CustomMetricValue};
+use crate::communication::protobuf::grpc::CommandError;
+use crate::controller::message::{AgentInfo, SimulationStatus};
+use crate::controller::metrics::message::{ActionStats, CheckStats, MetricsSummary};
+use crate::controller::model::simulation::{BotDef, CompletionReason, PhaseDef, SimulationDef};
+use crate::controller::model::threshold::{Threshold, ThresholdParseError};
+use crate::simulation::executor::ExecutionMode;
+use crate::simulation::series::TimeSeries;
+use crate::simulation::shape::{Easing, LoadStage};
+
+/// Body of the load simulation request.
+#[derive(Deserialize, Debug)]
+pub struct LoadSimulationRequest {
+    /// Rune script source code defining bot behaviors.
+    pub script: String,
+    /// Bot types and their load shape expressions.
+    pub bots: Vec<BotDefRequest>,
+    /// Thresholds evaluated against the run metrics.
+    #[serde(default)]
+    pub thresholds: Vec<ThresholdRequest>,
+    /// Sequence of phases, takes precedence over `bots` when not empty.
+    #[serde(default)]
+    pub phases: Vec<PhaseRequest>,
+    /// Maximum run duration in seconds, the run lasts until stopped if missing.
+    pub duration_secs: Option<u64>,
+    /// Labels the agents running the simulation must have, all the agents when empty.
+    #[serde(default)]
+    pub agent_labels: HashMap<String, String>,
+}
+
+/// Bot definition as received from the HTTP API.
+#[derive(Deserialize, Debug)]
+pub struct BotDefRequest {
+    /// Name of the bot model.
+    pub model: String,
+    /// Load shape expression.
+    #[serde(default)]
+    pub shape: String,
+    /// Stage-based load profile, used instead of the shape expression when not empty.
+    #[serde(default)]
+    pub stages: Vec<LoadStageRequest>,
+    /// Recorded traffic curve (`points` of `t` and `value`, optional `interpolation`,
+    /// `time_scale` and `amplitude_scale`), used instead of the stages and of the shape expression.
+    #[serde(default)]
+    pub series: Option<TimeSeries>,
+    /// Execution mode, `{"kind": "arrival_rate", "max_bots": <n>}` to read the shape as
+    /// iterations per second instead of concurrent bots.
+    #[serde(default)]
+    pub execution: ExecutionMode,
+}
+
+/// Stage of a load profile as received from the HTTP API.
+#[derive(Deserialize, Debug)]
+pub struct LoadStageRequest {
+    /// Bots count at the end of the stage.
+    pub target: f64,
+    /// Stage duration in seconds.
+    pub duration_secs: f64,
+    /// Interpolation from the previous target (`linear`, `step` or `cosine`).
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+impl TryFrom<LoadStageRequest> for LoadStage {
+    type Error = TryFromFloatSecsError;
+
+    fn try_from(stage: LoadStageRequest) -> Result<Self, Self::Error> {
+        Ok(LoadStage::new(
+            stage.target,
+            Duration::try_from_secs_f64(stage.duration_secs.max(0.0))?,
+        )
+        .easing(stage.easing))
+    }
+}
+
+/// Simulation phase as received from the HTTP API.
+#[derive(Deserialize, Debug)]
+pub struct PhaseRequest {
+    /// Name of the phase.
+    pub name: String,
+    /// Phase duration in seconds.
+    pub duration_secs: u64,
+    /// Bot types and their load shape expressions during the phase.
+    pub bots: Vec<BotDefRequest>,
+}
+
+impl TryFrom<BotDefRequest> for BotDef {
+    type Error = TryFromFloatSecsError;
+
+    fn try_from(bot: BotDefRequest) -> Result<Self, Self::Error> {
+        let def = BotDef::default()
+            .model(&bot.model)
+            .shape(&bot.shape)
+            .stages(
+                bot.stages
+                    .into_iter()
+                    .map(LoadStage::try_from)
+                    .collect::<Result<_, _>>()?,
+            )
+            .execution(bot.execution);
+        Ok(match bot.series {
+            Some(series) => def.series(series),
+            None => def,
+        })
+    }
+}
+
+impl TryFrom<PhaseRequest> for PhaseDef {
+    type Error = TryFromFloatSecsError;
+
+    fn try_from(phase: PhaseRequest) -> Result<Self, Self::Error> {
+        Ok(PhaseDef::default()
+            .name(&phase.name)
+            .duration(Duration::from_secs(phase.duration_secs))
+            .bots(
+                phase
+                    .bots
+                    .into_iter()
+                    .map(BotDef::try_from)
+                    .collect::<Result<_, _>>()?,
+            ))
+    }
+}
+
+/// Threshold definition as received from the HTTP API.
+#[derive(Deserialize, Debug)]
+pub struct ThresholdRequest {
+    /// Threshold expression (e.g. `p95 < 300ms`).
+    pub expression: String,
+    /// Only check the actions of the given model.
+    pub model: Option<String>,
+    /// Only check the actions with the given name.
+    pub action: Option<String>,
+    /// Only check the evaluations of the script check with the given name (`checks` thresholds).
+    pub check: Option<String>,
+    /// Abort the simulation when breached over the given trailing window (seconds).
+    pub critical_window_secs: Option<u64>,
+}
+
+impl TryFrom<ThresholdRequest> for Threshold {
+    type Error = ThresholdParseError;
+
+    fn try_from(req: ThresholdRequest) -> Result<Self, Self::Error> {
+        let mut threshold = Threshold::parse(&req.expression)?;
+        if let Some(model) = &req.model {
+            threshold = threshold.model(model);
+        }
+        if let Some(action) = &req.action {
+            threshold = threshold.action(action);
+        }
+        if let Some(check) = &req.check {
+            threshold = threshold.check(check);
+        }
+        if let Some(window) = req.critical_window_secs {
+            threshold = threshold.critical(Duration::from_secs(window));
+        }
+        Ok(threshold)
+    }
+}
+
+impl TryFrom<LoadSimulationRequest> for SimulationDef {
+    type Error = HttpApiError;
+
+    fn try_from(req: LoadSimulationRequest) -> Result<Self, Self::Error> {
+        let thresholds = req
+            .thresholds
+            .into_iter()
+            .map(Threshold::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        let simulation = SimulationDef::default()
+            .script(req.script)
+            .bots(
+                req.bots
+                    .into_iter()
+                    .map(BotDef::try_from)
+                    .collect::<Result<_, _>>()?,
+            )
+            .phases(
+                req.phases
+                    .into_iter()
+                    .map(PhaseDef::try_from)
+                    .collect::<Result<_, _>>()?,
+            )
+            .thresholds(thresholds)
+            .agent_labels(req.agent_labels);
+        Ok(match req.duration_secs {
+            Some(duration) => simulation.duration(Duration::from_secs(duration)),
+            None => simulation,
+        })
+    }
+}
+
+/// Body of the start simulation request.
+#[derive(Deserialize, Debug, Default)]
+pub struct StartSimulationRequest {
+    /// Start timestamp in milliseconds since unix epoch, the simulation starts immediately if missing.
+    pub start_ts: Option<u64>,
+}
+
+impl StartSimulationRequest {
+    /// Requested start time
+    pub fn start_time(&self) -> SystemTime {
+        self.start_ts
+            .map(|ts| UNIX_EPOCH + Duration::from_millis(ts))
+            .unwrap_or_else(SystemTime::now)
+    }
+}
+
+/// Query parameters of the stats request.
+#[derive(Deserialize, Debug, Default)]
+pub struct StatsQuery {
+    /// Only include actions of the given model.
+    pub model: Option<String>,
+    /// Only include actions with the given name.
+    pub action: Option<String>,
+    /// Only include windows ending after the given timestamp (milliseconds since unix epoch).
+    pub since: Option<u64>,
+    /// Only include windows starting before the given timestamp (milliseconds since unix epoch).
+    pub until: Option<u64>,
+}
+
+/// Connected agent as returned by the HTTP API.
+#[derive(Serialize, Debug)]
+pub struct AgentResponse {
+    /// Agent identifier.
+    pub agent_id: u32,
+    /// Name declared by the agent.
+    pub name: String,
+    /// Labels declared by the agent.
+    pub labels: HashMap<String, String>,
+    /// Maximum number of running bots declared by the agent, `None` if unbounded.
+    pub max_running: Option<u32>,
+    /// Maximum spawn rate declared by the agent, `None` if unbounded.
+    pub max_rate: Option<u32>,
+    /// Relative weight declared by the agent.
+    pub weight: f64,
+    /// Simulation state reported by the agent (e.g. `RUNNING`).
+    pub state: String,
+    /// Last update timestamp in milliseconds since unix epoch.
+    pub last_update: u64,
+    /// Errors reported by the agent rejecting the loaded simulation.
+    pub errors: Vec<AgentErrorResponse>,
+}
+
+/// Error reported by an agent as returned by the HTTP API.
+#[derive(Serialize, Debug)]
+pub struct AgentErrorResponse {
+    /// Error kind (e.g. `UNKNOWN_MODEL`).
+    pub kind: String,
+    /// Model the error refers to, `None` if not model specific.
+    pub model: Option<String>,
+    /// Error description.
+    pub message: String,
+}
+
+impl From<CommandError> for AgentErrorResponse {
+    fn from(error: CommandError) -> Self {
+        Self {
+            kind: error.kind().as_str_name().to_string(),
+            model: Some(error.model).filter(|model| !model.is_empty()),
+            message: error.message,
+        }
+    }
+}
+
+impl From<AgentInfo> for AgentResponse {
+    fn from(agent: AgentInfo) -> Self {
+        let capacity = agent.capacity;
+        Self {
+            agent_id: agent.agent_id,
+            name: agent.name,
+            labels: agent.labels,
+            max_running: capacity.map(|c| c.max_running).filter(|limit| *limit > 0),
+            max_rate: capacity.map(|c| c.max_rate).filter(|limit| *limit > 0),
+            weight: capacity.map_or(1.0, |c| if c.weight > 0.0 { c.weight } else { 1.0 }),
+            state: agent.state.as_str_name().to_string(),
+            last_update: to_epoch_millis(agent.last_update),
+            errors: agent
+                .errors
+                .into_iter()
+                .map(AgentErrorResponse::from)
+                .collect(),
+        }
+    }
+}
+
+/// Simulation status as returned by the HTTP API.
+#[derive(Serialize, Debug)]
+pub struct SimulationStatusResponse {
+    /// Lifecycle state (`idle`, `ready`, `launched` or `completed`).
+    pub state: &'static str,
+    /// Start of the current or last run in milliseconds since unix epoch.
+    pub start_ts: Option<u64>,
+    /// End of the current or last run in milliseconds since unix epoch.
+    pub end_ts: Option<u64>,
+    /// Why the last run ended (`elapsed`, `stopped` or `threshold_breached`).
+    pub reason: Option<&'static str>,
+    /// Critical threshold breached by the last run, if it failed.
+    pub breached_threshold: Option<String>,
+}
+
+impl From<SimulationStatus> for SimulationStatusResponse {
+    fn from(status: SimulationStatus) -> Self {
+        let (state, start_ts, end_ts, reason) = match status {
+            SimulationStatus::Idle => ("idle", None, None, None),
+            SimulationStatus::Ready => ("ready", None, None, None),
+            SimulationStatus::Launched { start_ts, end_ts } => {
+                ("launched", Some(start_ts), end_ts, None)
+            }
+            SimulationStatus::Completed {
+                start_ts,
+                end_ts,
+                reason,
+            } => ("completed", Some(start_ts), Some(end_ts), Some(reason)),
+        };
+        let (reason, breached_threshold) = match reason {
+            None => (None, None),
+            Some(CompletionReason::Elapsed) => (Some("elapsed"), None),
+            Some(CompletionReason::Stopped) => (Some("stopped"), None),
+            Some(CompletionReason::ThresholdBreached(threshold)) => {
+                (Some("threshold_breached"), Some(threshold))
+            }
+        };
+        Self {
+            state,
+            start_ts: start_ts.map(to_epoch_millis),
+            end_ts: end_ts.map(to_epoch_millis),
+            reason,
+            breached_threshold,
+        }
+    }
+}
+
+/// Aggregated metrics as returned by the HTTP API.
+#[derive(Serialize, Debug)]
+pub struct StatsResponse {
+    /// Start of the summarized interval in milliseconds since unix epoch.
+    pub from: Option<u64>,
+    /// End of the summarized interval in milliseconds since unix epoch.
+    pub to: Option<u64>,
+    /// Stats for each action.
+    pub actions: Vec<ActionStatsResponse>,
+    /// Most recent bots count per model and state id.
+    pub bots: HashMap<String, BTreeMap<u32, u64>>,
+    /// Arrival-rate iterations dropped per model.
+    pub dropped_iterations: HashMap<String, u64>,
+    /// Interval ticks skipped by the paced bots per model.
+    pub skipped_ticks: HashMap<String, u64>,
+    /// Metrics recorded by the scripts.
+    pub custom_metrics: Vec<CustomMetricResponse>,
+    /// Passes and failures of the script checks.
+    pub checks: Vec<CheckStatsResponse>,
+}
+
+/// Passes and failures of a script check.
+#[derive(Serialize, Debug)]
+pub struct CheckStatsResponse {
+    /// Name of the bot model.
+    pub model: String,
+    /// Name of the check.
+    pub name: String,
+    /// Number of evaluations that passed.
+    pub passes: u64,
+    /// Number of evaluations that failed.
+    pub fails: u64,
+    /// Fraction of evaluations that passed.
+    pub pass_rate: Option<f64>,
+}
+
+impl From<CheckStats> for CheckStatsResponse {
+    fn from(stats: CheckStats) -> Self {
+        Self {
+            pass_rate: stats.pass_rate(),
+            model: stats.model,
+            name: stats.name,
+            passes: stats.passes,
+            fails: stats.fails,
+        }
+    }
+}
+
+/// Metric recorded by the scripts, histogram fields are missing for counters and gauges.
+#[derive(Serialize, Debug)]
+pub struct CustomMetricResponse {
+    /// Name of the bot model.
+    pub model: String,
+    /// Name of the metric.
+    pub name: String,
+    /// Labels of the metric.
+    pub labels: BTreeMap<String, String>,
+    /// `counter`, `gauge` or `histogram`.
+    pub kind: &'static str,
+    /// Counter total, gauge value or sum of the histogram values.
+    pub value: f64,
+    /// Number of values recorded by the histogram.
+    pub count: Option<u64>,
+    /// Average recorded value.
+    pub mean: Option<f64>,
+    /// Smallest recorded value.
+    pub min: Option<f64>,
+    /// Largest recorded value.
+    pub max: Option<f64>,
+    /// 50th percentile value.
+    pub p50: Option<f64>,
+    /// 90th percentile value.
+    pub p90: Option<f64>,
+    /// 95th percentile value.
+    pub p95: Option<f64>,
+    /// 99th percentile value.
+    pub p99: Option<f64>,
+}
+
+impl From<(CustomMetricKey, CustomMetricValue)> for CustomMetricResponse {
+    fn from((key, value): (CustomMetricKey, CustomMetricValue)) -> Self {
+        let response = Self {
+            model: key.model,
+            name: key.name,
+            labels: key.labels,
+            kind: value.kind(),
+            value: 0.0,
+            count: None,
+            mean: None,
+            min: None,
+            max: None,
+            p50: None,
+            p90: None,
+            p95: None,
+            p99: None,
+        };
+        match value {
+            CustomMetricValue::Counter(value) | CustomMetricValue::Gauge(value) => {
+                Self { value, ..response }
+            }
+            CustomMetricValue::Histogram(histogram) => Self {
+                value: histogram.sum(),
+                count: Some(histogram.count()),
+                mean: histogram.mean(),
+                min: histogram.min(),
+                max: histogram.max(),
+                p50: histogram.percentile(0.5),
+                p90: histogram.percentile(0.9),
+                p95: histogram.percentile(0.95),
+                p99: histogram.percentile(0.99),
+                ..response
+            },
+        }
+    }
+}
+
+/// Aggregated statistics of a single action, latencies are expressed in milliseconds.
+#[derive(Serialize, Debug)]
+pub struct ActionStatsResponse {
+    /// Name of the bot model.
+    pub model: String,
+    /// Name of the action.
+    pub action: String,
+    /// Number of executions.
+    pub count: u64,
+    /// Number of executions with an error outcome.
+    pub errors: u64,
+    /// Fraction of executions with an error outcome.
+    pub error_rate: f64,
+    /// Executions per second.
+    pub throughput: f64,
+    /// Average latency.
+    pub mean: Option<f64>,
+    /// Smallest latency, missing if not reported by the agents.
+    pub min: Option<f64>,
+    /// Largest latency, missing if not reported by the agents.
+    pub max: Option<f64>,
+    /// 50th percentile latency.
+    pub p50: Option<f64>,
+    /// 90th percentile latency.
+    pub p90: Option<f64>,
+    /// 95th percentile latency.
+    pub p95: Option<f64>,
+    /// 99th percentile latency.
+    pub p99: Option<f64>,
+    /// Executions count grouped by outcome.
+    pub outcomes: BTreeMap<i64, u64>,
+}
+
+impl From<ActionStats> for ActionStatsResponse {
+    fn from(stats: ActionStats) -> Self {
+        let millis = |d: Duration| d.as_secs_f64() * 1000.0;
+        Self {
+            error_rate: stats.error_rate(),
+            mean: stats.histogram.mean().map(millis),
+            min: stats.histogram.min().map(millis),
+            max: stats.histogram.max().map(millis),
+            p50: stats.percentile(0.5).map(millis),
+            p90: stats.percentile(0.9).map(millis),
+            p95: stats.percentile(0.95).map(millis),
+            p99: stats.percentile(0.99).map(millis),
+            model: stats.key.model,
+            action: stats.key.action,
+            count: stats.count,
+            errors: stats.errors,
+            throughput: stats.throughput,
+            outcomes: stats.outcomes,
+        }
+    }
+}
+
+impl From<MetricsSummary> for StatsResponse {
+    fn from(summary: MetricsSummary) -> Self {
+        let checks = summary.checks().into_iter().map(Into::into).collect();
+        Self {
+            from: summary.from.map(to_epoch_millis),
+            to: summary.to.map(to_epoch_millis),
+            actions: summary.actions.into_iter().map(Into::into).collect(),
+            bots: summary.bots,
+            dropped_iterations: summary.dropped_iterations,
+            skipped_ticks: summary.skipped_ticks,
+            custom_metrics: summary.custom_metrics.into_iter().map(Into::into).collect(),
+            checks,
+        }
+    }
+}
+
+pub(super) fn to_epoch_millis(ts: SystemTime) -> u64 {
+    ts.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Error returned by the HTTP API handlers.
+#[derive(Error, Debug)]
+pub enum HttpApiError {
+    #[error("Controller is unreachable - {0}")]
+    Mailbox(#[from] actix::MailboxError),
+    #[error("No queryable metrics storage configured")]
+    MetricsUnavailable,
+    #[error("No thresholds watcher configured")]
+    SloUnavailable,
+    #[error("{0}")]
+    InvalidThreshold(#[from] ThresholdParseError),
+    #[error("Invalid duration - {0}")]
+    InvalidDuration(#[from] TryFromFloatSecsError),
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+impl IntoResponse for HttpApiError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            HttpApiError::Mailbox(_) => StatusCode::SERVICE_UNAVAILABLE,
+            HttpApiError::MetricsUnavailable | HttpApiError::SloUnavailable => {
+                StatusCode::NOT_FOUND
+            }
+            HttpApiError::InvalidThreshold(_) | HttpApiError::InvalidDuration(_) => {
+                StatusCode::BAD_REQUEST
+            }
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: self.to_string(),
+            }),
+        )
+            .into_response()
+    }
+}
@@ -0,0 +1,372 @@
+use std::net::SocketAddr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use actix::{Actor, Addr, Context, Handler, Recipient};
+use axum::extract::{Query, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderName, StatusCode};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+
+use crate::controller::actor::ControllerActor;
+use crate::controller::http::model::{
+    AgentResponse, HttpApiError, LoadSimulationRequest, SimulationStatusResponse,
+    StartSimulationRequest, StatsQuery, StatsResponse,
+};
+use crate::controller::message::{
+    AbortSimulation, FetchAgents, FetchSimulationStatus, LoadSimulation, StartSimulation,
+    StopSimulation, ValidateSimulation,
+};
+use crate::controller::metrics::message::{ExportPrometheus, QueryMetrics};
+use crate::controller::slo::actor::SloWatcherActor;
+use crate::controller::slo::message::{FetchVerdict, SloVerdict};
+use crate::controller::validation::ValidationReport;
+
+/// Content type of the Prometheus text exposition format.
+pub(crate) const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// HTTP/JSON admin API driving a controller.
+///
+/// | Method | Path                  | Description                                              |
+/// |--------|-----------------------|----------------------------------------------------------|
+/// | POST   | `/simulation`         | Load a simulation (`{"script": "...", "bots": [{"model": "...", "shape": "..."}], "thresholds": [{"expression": "p95 < 300ms"}]}`) |
+/// | POST   | `/simulation/validate`| Validate a simulation (same body as `POST /simulation`) without loading it |
+/// | GET    | `/simulation`         | Simulation lifecycle state, with start, end and completion reason of the last run |
+/// | POST   | `/simulation/start`   | Start the loaded simulation (`{"start_ts": <epoch millis>}`, now if missing) |
+/// | POST   | `/simulation/stop`    | Gracefully stop the launched simulation                  |
+/// | POST   | `/simulation/abort`   | Immediately tear down the simulation                     |
+/// | GET    | `/agents`             | List connected agents and their simulation state         |
+/// | GET    | `/stats`              | Aggregated metrics, filtered by `model`, `action`, `since` and `until` query params |
+/// | GET    | `/metrics`            | Metrics in Prometheus text format                        |
+/// | GET    | `/verdict`            | Thresholds verdict of the last terminated run            |
+///
+/// Loading a simulation returns its validation report, with status `422` when it is invalid and
+/// is therefore not loaded.
+///
+/// The `/stats` and `/metrics` endpoints require a metrics storage handling [`QueryMetrics`] and
+/// [`ExportPrometheus`] (e.g. [`MetricsStoreActor`](crate::controller::metrics::actor::MetricsStoreActor)),
+/// the `/verdict` endpoint requires a [`SloWatcherActor`].
+#[derive(Clone)]
+pub struct HttpApi {
+    controller: Addr<ControllerActor>,
+    metrics: Option<Recipient<QueryMetrics>>,
+    prometheus: Option<Recipient<ExportPrometheus>>,
+    slo_watcher: Option<Addr<SloWatcherActor>>,
+}
+
+impl HttpApi {
+    /// Create a new HTTP API for the given controller
+    pub fn new(controller: Addr<ControllerActor>) -> Self {
+        Self {
+            controller,
+            metrics: None,
+            prometheus: None,
+            slo_watcher: None,
+        }
+    }
+
+    /// Set the metrics storage queried by the `/stats` and `/metrics` endpoints
+    pub fn metrics_storage<MetricsStorageAct>(
+        self,
+        metrics_storage_addr: Addr<MetricsStorageAct>,
+    ) -> Self
+    where
+        MetricsStorageAct: Actor<Context = Context<MetricsStorageAct>>
+            + Handler<QueryMetrics>
+            + Handler<ExportPrometheus>,
+    {
+        Self {
+            metrics: Some(metrics_storage_addr.clone().recipient()),
+            prometheus: Some(metrics_storage_addr.recipient()),
+            ..self
+        }
+    }
+
+    /// Set the thresholds watcher queried by the `/verdict` endpoint
+    pub fn slo_watcher(self, slo_watcher_addr: Addr<SloWatcherActor>) -> Self {
+        Self {
+            slo_watcher: Some(slo_watcher_addr),
+            ..self
+        }
+    }
+
+    /// Build the axum router exposing the API, can be merged into an existing server
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/simulation", get(simulation_status).post(load_simulation))
+            .route("/simulation/validate", post(validate_simulation))
+            .route("/simulation/start", post(start_simulation))
+            .route("/simulation/stop", post(stop_simulation))
+            .route("/simulation/abort", post(abort_simulation))
+            .route("/agents", get(list_agents))
+            .route("/stats", get(fetch_stats))
+            .route("/metrics", get(export_prometheus))
+            .route("/verdict", get(fetch_verdict))
+            .with_state(self)
+    }
+
+    /// Serve the API on the given address.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server fails to bind or serve.
+    pub async fn serve(self, address: SocketAddr) -> std::io::Result<()> {
+        let listener = tokio::net::TcpListener::bind(address).await?;
+        axum::serve(listener, self.router()).await
+    }
+}
+
+async fn load_simulation(
+    State(api): State<HttpApi>,
+    Json(req): Json<LoadSimulationRequest>,
+) -> Result<(StatusCode, Json<ValidationReport>), HttpApiError> {
+    let report = api
+        .controller
+        .send(LoadSimulation::new(req.try_into()?))
+        .await?;
+    let status = if report.is_valid() {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+    Ok((status, Json(report)))
+}
+
+async fn validate_simulation(
+    State(api): State<HttpApi>,
+    Json(req): Json<LoadSimulationRequest>,
+) -> Result<Json<ValidationReport>, HttpApiError> {
+    let report = api
+        .controller
+        .send(ValidateSimulation::new(req.try_into()?))
+        .await?;
+    Ok(Json(report))
+}
+
+async fn simulation_status(
+    State(api): State<HttpApi>,
+) -> Result<Json<SimulationStatusResponse>, HttpApiError> {
+    let status = api.controller.send(FetchSimulationStatus).await?;
+    Ok(Json(status.into()))
+}
+
+async fn start_simulation(
+    State(api): State<HttpApi>,
+    req: Option<Json<StartSimulationRequest>>,
+) -> Result<StatusCode, HttpApiError> {
+    let Json(req) = req.unwrap_or_default();
+    api.controller
+        .send(StartSimulation::at(req.start_time()))
+        .await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn stop_simulation(State(api): State<HttpApi>) -> Result<StatusCode, HttpApiError> {
+    api.controller.send(StopSimulation).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn abort_simulation(State(api): State<HttpApi>) -> Result<StatusCode, HttpApiError> {
+    api.controller.send(AbortSimulation).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn list_agents(State(api): State<HttpApi>) -> Result<Json<Vec<AgentResponse>>, HttpApiError> {
+    let agents = api.controller.send(FetchAgents).await?;
+    Ok(Json(agents.into_iter().map(Into::into).collect()))
+}
+
+async fn fetch_stats(
+    State(api): State<HttpApi>,
+    Query(query): Query<StatsQuery>,
+) -> Result<Json<StatsResponse>, HttpApiError> {
+    let metrics = api.metrics.ok_or(HttpApiError::MetricsUnavailable)?;
+
+    let mut msg = QueryMetrics::default();
+    if let Some(model) = &query.model {
+        msg = msg.model(model);
+    }
+    if let Some(action) = &query.action {
+        msg = msg.action(action);
+    }
+    if let Some(since) = query.since {
+        msg = msg.since(UNIX_EPOCH + Duration::from_millis(since));
+    }
+    if let Some(until) = query.until {
+        msg = msg.until(UNIX_EPOCH + Duration::from_millis(until));
+    }
+
+    let summary = metrics.send(msg).await?;
+    Ok(Json(summary.into()))
+}
+
+async fn export_prometheus(
+    State(api): State<HttpApi>,
+) -> Result<([(HeaderName, &'static str); 1], String), HttpApiError> {
+    let prometheus = api.prometheus.ok_or(HttpApiError::MetricsUnavailable)?;
+    let text = prometheus.send(ExportPrometheus).await?;
+    Ok(([(CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], text))
+}
+
+async fn fetch_verdict(
+    State(api): State<HttpApi>,
+) -> Result<Json<Option<SloVerdict>>, HttpApiError> {
+    let slo_watcher = api.slo_watcher.ok_or(HttpApiError::SloUnavailable)?;
+    Ok(Json(slo_watcher.send(FetchVerdict).await?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::communication::message::ControllerCommandMessage;
+    use crate::controller::client::downstream::DownstreamClient;
+    use crate::controller::metrics::actor::MetricsStoreActor;
+    use crate::MultiAgentUpdateMessage;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    struct NoopDownstream;
+    impl Actor for NoopDownstream {
+        type Context = Context<Self>;
+    }
+    impl Handler<ControllerCommandMessage> for NoopDownstream {
+        type Result = ();
+        fn handle(&mut self, _msg: ControllerCommandMessage, _ctx: &mut Self::Context) {}
+    }
+
+    fn start_api() -> Router {
+        let metrics = MetricsStoreActor::default().start();
+        let controller = ControllerActor::new(
+            DownstreamClient::new(NoopDownstream.start().recipient()),
+            metrics.clone().recipient::<MultiAgentUpdateMessage>(),
+        )
+        .start();
+        HttpApi::new(controller).metrics_storage(metrics).router()
+    }
+
+    async fn call(router: &Router, method: &str, uri: &str, body: &str) -> (StatusCode, String) {
+        let response = router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[actix::test]
+    async fn load_and_start_simulation() {
+        let router = start_api();
+
+        let (status, _) = call(
+            &router,
+            "POST",
+            "/simulation",
+            &load_request(r#"[{"model": "Demo", "shape": "10"}]"#),
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let (status, _) = call(&router, "POST", "/simulation/start", "{}").await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let (status, _) = call(&router, "POST", "/simulation/stop", "").await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let (status, body) = call(&router, "GET", "/simulation", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let simulation: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(simulation["state"], "completed");
+        assert_eq!(simulation["reason"], "stopped");
+    }
+
+    fn load_request(bots: &str) -> String {
+        let script =
+            "struct Demo {} impl Demo { pub fn new(p) { Self {} } pub fn register_bot(bot) {} }";
+        format!(r#"{{"script": "{script}", "bots": {bots}}}"#)
+    }
+
+    #[actix::test]
+    async fn reject_invalid_simulation() {
+        let router = start_api();
+        let bots = r#"[{"model": "Demo", "shape": "10 *"}, {"model": "Other", "shape": "1"}]"#;
+
+        let (status, body) =
+            call(&router, "POST", "/simulation/validate", &load_request(bots)).await;
+        assert_eq!(status, StatusCode::OK);
+        let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(report["models"], serde_json::json!(["Demo"]));
+        assert_eq!(report["issues"][0]["kind"], "bad_shape");
+        assert_eq!(report["issues"][1]["kind"], "unknown_model");
+        assert_eq!(report["issues"][1]["model"], "Other");
+
+        let (status, _) = call(&router, "POST", "/simulation", &load_request(bots)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (_, body) = call(&router, "GET", "/simulation", "").await;
+        let simulation: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(simulation["state"], "idle");
+    }
+
+    #[actix::test]
+    async fn reject_invalid_threshold() {
+        let router = start_api();
+
+        let (status, body) = call(
+            &router,
+            "POST",
+            "/simulation",
+            r#"{"script": "", "bots": [], "thresholds": [{"expression": "latency < 1s"}]}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("Unknown threshold metric"));
+
+        let (status, _) = call(&router, "GET", "/verdict", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[actix::test]
+    async fn reject_out_of_range_stage_duration() {
+        let router = start_api();
+
+        let (status, body) = call(
+            &router,
+            "POST",
+            "/simulation",
+            r#"{"script": "", "bots": [{"model": "A", "shape": "", "stages": [{"target": 10, "duration_secs": 1e30}]}]}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("Invalid duration"));
+    }
+
+    #[actix::test]
+    async fn list_agents_and_stats() {
+        let router = start_api();
+
+        let (status, body) = call(&router, "GET", "/agents", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "[]");
+
+        let (status, body) = call(&router, "GET", "/stats?model=Demo", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(stats["actions"], serde_json::json!([]));
+
+        let (status, body) = call(&router, "GET", "/metrics", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("# TYPE hailstorm_bots gauge"));
+    }
+}
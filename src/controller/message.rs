@@ -1,9 +1,14 @@
-use crate::controller::model::simulation::SimulationDef;
+use crate::communication::protobuf::grpc::{AgentCapacity, AgentSimulationState, CommandError};
+use crate::controller::model::simulation::{CompletionReason, SimulationDef};
+use crate::controller::validation::ValidationReport;
+use std::collections::HashMap;
 use std::time::SystemTime;
 
 #[derive(actix::Message)]
-#[rtype(result = "()")]
-/// Load a new simulation
+#[rtype(result = "ValidationReport")]
+/// Load a new simulation.
+///
+/// The simulation is validated first, an invalid one is not loaded nor sent to the agents.
 pub struct LoadSimulation(pub(super) SimulationDef);
 
 impl LoadSimulation {
@@ -18,6 +23,18 @@ impl LoadSimulation {
     }
 }
 
+#[derive(actix::Message)]
+#[rtype(result = "ValidationReport")]
+/// Validate a simulation without loading it
+pub struct ValidateSimulation(pub(super) SimulationDef);
+
+impl ValidateSimulation {
+    /// Create a new ValidateSimulation message with given simulation definition
+    pub fn new(def: SimulationDef) -> Self {
+        Self(def)
+    }
+}
+
 #[derive(actix::Message)]
 #[rtype(result = "()")]
 /// Start the loaded simulation at a specific time
@@ -34,3 +51,73 @@ impl StartSimulation {
         self.0
     }
 }
+
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+/// Gracefully stop the launched simulation.
+///
+/// Bots run their `Stopping` hook and drain, the simulation stays loaded and can be started again.
+pub struct StopSimulation;
+
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+/// Immediately tear down the current simulation, skipping bots `Stopping` hooks, and unload it.
+pub struct AbortSimulation;
+
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+/// Stop the launched simulation as [`StopSimulation`] does, completing the run as failed because
+/// the given critical threshold was breached.
+pub struct FailSimulation {
+    /// Breached threshold.
+    pub threshold: String,
+}
+
+/// Agent connected to the controller, as seen from its last update.
+#[derive(Clone, Debug)]
+pub struct AgentInfo {
+    /// Agent identifier.
+    pub agent_id: u32,
+    /// Name declared by the agent, empty if not set.
+    pub name: String,
+    /// Labels declared by the agent.
+    pub labels: HashMap<String, String>,
+    /// Capacity declared by the agent, used to weight its share of the load.
+    pub capacity: Option<AgentCapacity>,
+    /// Simulation state reported by the agent.
+    pub state: AgentSimulationState,
+    /// Timestamp of the last update received from the agent.
+    pub last_update: SystemTime,
+    /// Errors reported by the agent rejecting the loaded simulation, empty if it was accepted.
+    pub errors: Vec<CommandError>,
+}
+
+#[derive(actix::Message)]
+#[rtype(result = "Vec<AgentInfo>")]
+/// Fetch the agents currently connected to the controller
+pub struct FetchAgents;
+
+/// Lifecycle status of the controller simulation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SimulationStatus {
+    /// No simulation is loaded.
+    Idle,
+    /// A simulation is loaded and ready to be launched.
+    Ready,
+    /// The simulation is launched, `end_ts` is set when the run has a finite duration.
+    Launched {
+        start_ts: SystemTime,
+        end_ts: Option<SystemTime>,
+    },
+    /// The last run ended at `end_ts` for the given reason.
+    Completed {
+        start_ts: SystemTime,
+        end_ts: SystemTime,
+        reason: CompletionReason,
+    },
+}
+
+#[derive(actix::Message)]
+#[rtype(result = "SimulationStatus")]
+/// Fetch the lifecycle status of the simulation
+pub struct FetchSimulationStatus;
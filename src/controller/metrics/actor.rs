@@ -0,0 +1,772 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Add;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix::{Actor, Context, Handler, MessageResult};
+
+use crate::agent::metrics::custom::{CustomMetricKey, CustomMetricValue};
+use crate::communication::message::MultiAgentUpdateMessage;
+use crate::communication::protobuf::grpc::{AgentUpdate, ModelStats};
+use crate::controller::metrics::histogram::LatencyHistogram;
+use crate::controller::metrics::message::{
+    is_error_outcome, ActionKey, ActionOutcome, ActionStats, ExportPrometheus, FetchMetricsWindows,
+    MetricsSummary, MetricsWindow, QueryMetrics, ResetMetrics,
+};
+use crate::controller::metrics::prometheus::PrometheusExposition;
+
+/// Cumulative histogram last received from an agent for a given action outcome.
+struct ReceivedHistogram {
+    simulation_id: String,
+    timestamp: SystemTime,
+    histogram: LatencyHistogram,
+}
+
+/// Bots count last reported by an agent.
+#[derive(Default)]
+struct AgentBotStates {
+    timestamp: Option<SystemTime>,
+    counts: HashMap<(String, u32), u32>,
+}
+
+/// Cumulative script metric last received from an agent.
+struct ReceivedCustomMetric {
+    simulation_id: String,
+    timestamp: SystemTime,
+    value: CustomMetricValue,
+}
+
+/// Merge a script metric into the ones with the same key.
+fn merge_custom_metric(
+    metrics: &mut HashMap<CustomMetricKey, CustomMetricValue>,
+    key: &CustomMetricKey,
+    value: &CustomMetricValue,
+) {
+    match metrics.get_mut(key) {
+        Some(current) => current.merge(value),
+        None => {
+            metrics.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+/// Cumulative per model counter reported by the agents.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum ModelCounter {
+    DroppedIterations,
+    SkippedTicks,
+}
+
+/// Built-in metrics storage for the controller.
+///
+/// Agents send cumulative histograms, the store keeps the last one received from each agent
+/// and merges the deltas into time-aligned windows per model, action and outcome. Agents restart
+/// their histograms and counters from zero when a new simulation is loaded, the first ones tagged
+/// with a new simulation id are merged whole. Bot counts
+/// reported by the agents are summed and recorded into the same windows, as are the increases of
+/// the script metrics; script gauges are the sum of the last value reported by each agent.
+///
+/// Deltas are also accumulated into totals since startup, rendered by [`ExportPrometheus`].
+///
+/// Use it with [`ControllerBuilder::metrics_storage`](crate::controller::builder::ControllerBuilder::metrics_storage)
+/// and query it with [`QueryMetrics`] and [`FetchMetricsWindows`].
+pub struct MetricsStoreActor {
+    window: Duration,
+    retention: Duration,
+    agent_timeout: Duration,
+    received: HashMap<(u32, ActionKey, ActionOutcome), ReceivedHistogram>,
+    agent_bots: HashMap<u32, AgentBotStates>,
+    received_counters: HashMap<(u32, String, ModelCounter), (String, u64)>,
+    received_custom: HashMap<(u32, CustomMetricKey), ReceivedCustomMetric>,
+    windows: BTreeMap<SystemTime, MetricsWindow>,
+    totals: HashMap<(ActionKey, ActionOutcome), LatencyHistogram>,
+    dropped_totals: HashMap<String, u64>,
+    skipped_totals: HashMap<String, u64>,
+    custom_totals: HashMap<CustomMetricKey, CustomMetricValue>,
+}
+
+impl Default for MetricsStoreActor {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(5),
+            retention: Duration::from_secs(6 * 3600),
+            agent_timeout: Duration::from_secs(60),
+            received: Default::default(),
+            agent_bots: Default::default(),
+            received_counters: Default::default(),
+            received_custom: Default::default(),
+            windows: Default::default(),
+            totals: Default::default(),
+            dropped_totals: Default::default(),
+            skipped_totals: Default::default(),
+            custom_totals: Default::default(),
+        }
+    }
+}
+
+impl Actor for MetricsStoreActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _ctx: &mut Self::Context) {
+        log::debug!("MetricsStoreActor started");
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        log::debug!("MetricsStoreActor stopped");
+    }
+}
+
+impl MetricsStoreActor {
+    /// Set the length of the aggregation windows (default 5s)
+    pub fn window(self, window: Duration) -> Self {
+        Self {
+            window: window.max(Duration::from_secs(1)),
+            ..self
+        }
+    }
+
+    /// Set for how long the windows are kept (default 6h)
+    pub fn retention(self, retention: Duration) -> Self {
+        Self { retention, ..self }
+    }
+
+    fn window_start(&self, ts: SystemTime) -> SystemTime {
+        let since_epoch = ts.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let window_millis = self.window.as_millis();
+        let aligned = since_epoch.as_millis() / window_millis * window_millis;
+        UNIX_EPOCH.add(Duration::from_millis(aligned as u64))
+    }
+
+    fn window_mut(&mut self, ts: SystemTime) -> &mut MetricsWindow {
+        let start = self.window_start(ts);
+        let duration = self.window;
+        self.windows.entry(start).or_insert_with(|| MetricsWindow {
+            start,
+            duration,
+            actions: Default::default(),
+            bots: Default::default(),
+            dropped_iterations: Default::default(),
+            skipped_ticks: Default::default(),
+            custom_metrics: Default::default(),
+        })
+    }
+
+    fn record_performance(&mut self, agent_id: u32, stats: &ModelStats) {
+        for snapshot in stats.performance.iter() {
+            let Some(timestamp) = snapshot
+                .timestamp
+                .and_then(|ts| SystemTime::try_from(ts).ok())
+            else {
+                continue;
+            };
+            let key = ActionKey {
+                model: stats.model.clone(),
+                action: snapshot.action.clone(),
+            };
+            for perf_hist in snapshot.histograms.iter() {
+                let histogram = LatencyHistogram::from(perf_hist);
+                let received_key = (agent_id, key.clone(), perf_hist.status);
+                let delta = match self.received.get(&received_key) {
+                    Some(received) if received.simulation_id != snapshot.simulation_id => {
+                        histogram.clone()
+                    }
+                    Some(received) if received.timestamp >= timestamp => continue,
+                    Some(received) => histogram.delta(&received.histogram),
+                    None => histogram.clone(),
+                };
+                self.received.insert(
+                    received_key,
+                    ReceivedHistogram {
+                        simulation_id: snapshot.simulation_id.clone(),
+                        timestamp,
+                        histogram,
+                    },
+                );
+                self.totals
+                    .entry((key.clone(), perf_hist.status))
+                    .or_default()
+                    .merge(&delta);
+                self.window_mut(timestamp)
+                    .actions
+                    .entry(key.clone())
+                    .or_default()
+                    .entry(perf_hist.status)
+                    .or_default()
+                    .merge(&delta);
+            }
+        }
+    }
+
+    fn record_counters(&mut self, update: &AgentUpdate, stats: &ModelStats, ts: SystemTime) {
+        for (counter, value) in [
+            (ModelCounter::DroppedIterations, stats.dropped_iterations),
+            (ModelCounter::SkippedTicks, stats.skipped_ticks),
+        ] {
+            let received_key = (update.agent_id, stats.model.clone(), counter);
+            let previous = self
+                .received_counters
+                .insert(received_key, (update.simulation_id.clone(), value));
+            // counters restart from zero when the agent loads a new simulation
+            let delta = match previous {
+                Some((simulation_id, previous))
+                    if simulation_id == update.simulation_id && previous <= value =>
+                {
+                    value - previous
+                }
+                _ => value,
+            };
+            if delta == 0 {
+                continue;
+            }
+            let model = stats.model.clone();
+            match counter {
+                ModelCounter::DroppedIterations => {
+                    *self.dropped_totals.entry(model.clone()).or_default() += delta;
+                    *self
+                        .window_mut(ts)
+                        .dropped_iterations
+                        .entry(model)
+                        .or_default() += delta;
+                }
+                ModelCounter::SkippedTicks => {
+                    *self.skipped_totals.entry(model.clone()).or_default() += delta;
+                    *self.window_mut(ts).skipped_ticks.entry(model).or_default() += delta;
+                }
+            }
+        }
+    }
+
+    fn record_custom_metrics(&mut self, agent_id: u32, stats: &ModelStats) {
+        for metric in stats.custom_metrics.iter() {
+            let (Some(timestamp), Some(value)) = (
+                metric
+                    .timestamp
+                    .and_then(|ts| SystemTime::try_from(ts).ok()),
+                metric.metric_value(),
+            ) else {
+                continue;
+            };
+            let key = metric.key(&stats.model);
+            let received_key = (agent_id, key.clone());
+            let delta = match self.received_custom.get(&received_key) {
+                Some(received) if received.simulation_id != metric.simulation_id => value.clone(),
+                Some(received) if received.timestamp >= timestamp => continue,
+                Some(received) => value.delta(&received.value),
+                None => value.clone(),
+            };
+            self.received_custom.insert(
+                received_key,
+                ReceivedCustomMetric {
+                    simulation_id: metric.simulation_id.clone(),
+                    timestamp,
+                    value,
+                },
+            );
+            if let CustomMetricValue::Gauge(_) = delta {
+                let gauge = self.agents_gauge(&key, &metric.simulation_id);
+                self.custom_totals.insert(key.clone(), gauge.clone());
+                self.window_mut(timestamp).custom_metrics.insert(key, gauge);
+            } else {
+                merge_custom_metric(&mut self.custom_totals, &key, &delta);
+                merge_custom_metric(&mut self.window_mut(timestamp).custom_metrics, &key, &delta);
+            }
+        }
+    }
+
+    /// Sum of the last values of a script gauge reported by the agents running the simulation
+    fn agents_gauge(&self, key: &CustomMetricKey, simulation_id: &str) -> CustomMetricValue {
+        CustomMetricValue::Gauge(
+            self.received_custom
+                .iter()
+                .filter(|((_agent_id, received_key), received)| {
+                    received_key == key && received.simulation_id == simulation_id
+                })
+                .filter_map(|(_k, received)| match received.value {
+                    CustomMetricValue::Gauge(value) => Some(value),
+                    _ => None,
+                })
+                .sum(),
+        )
+    }
+
+    fn record_bot_states(&mut self, update: &AgentUpdate) {
+        let agent_bots = self.agent_bots.entry(update.agent_id).or_default();
+        for stats in update.stats.iter() {
+            for snapshot in stats.states.iter() {
+                for group in snapshot.states.iter() {
+                    agent_bots
+                        .counts
+                        .insert((stats.model.clone(), group.state_id), group.count);
+                }
+            }
+        }
+        if let Some(ts) = update.update_ts() {
+            if agent_bots.timestamp.map(|prev| prev < ts).unwrap_or(true) {
+                agent_bots.timestamp = Some(ts);
+            }
+        }
+    }
+
+    fn total_bot_states(&self) -> HashMap<String, BTreeMap<u32, u64>> {
+        let mut totals: HashMap<String, BTreeMap<u32, u64>> = HashMap::new();
+        for agent_bots in self.agent_bots.values() {
+            for ((model, state_id), count) in agent_bots.counts.iter() {
+                *totals
+                    .entry(model.clone())
+                    .or_default()
+                    .entry(*state_id)
+                    .or_default() += *count as u64;
+            }
+        }
+        totals
+    }
+
+    fn cleanup(&mut self, now: SystemTime) {
+        let agent_timeout = self.agent_timeout;
+        self.agent_bots.retain(|_id, bots| {
+            bots.timestamp
+                .map(|ts| ts.add(agent_timeout) > now)
+                .unwrap_or(false)
+        });
+        self.received
+            .retain(|_k, received| received.timestamp.add(agent_timeout) > now);
+        let agent_bots = &self.agent_bots;
+        self.received_counters
+            .retain(|(agent_id, _model, _counter), _count| agent_bots.contains_key(agent_id));
+        self.received_custom
+            .retain(|(agent_id, _key), _metric| agent_bots.contains_key(agent_id));
+
+        let retention = self.retention;
+        self.windows
+            .retain(|start, window| start.add(window.duration).add(retention) > now);
+    }
+}
+
+impl Handler<MultiAgentUpdateMessage> for MetricsStoreActor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        MultiAgentUpdateMessage(updates): MultiAgentUpdateMessage,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let now = SystemTime::now();
+        for update in updates.iter() {
+            let update_ts = update.update_ts().unwrap_or(now);
+            for stats in update.stats.iter() {
+                self.record_performance(update.agent_id, stats);
+                self.record_counters(update, stats, update_ts);
+                self.record_custom_metrics(update.agent_id, stats);
+            }
+            self.record_bot_states(update);
+        }
+        self.cleanup(now);
+
+        let bots = self.total_bot_states();
+        self.window_mut(now).bots = bots;
+    }
+}
+
+impl Handler<QueryMetrics> for MetricsStoreActor {
+    type Result = MetricsSummary;
+
+    fn handle(&mut self, query: QueryMetrics, _ctx: &mut Self::Context) -> Self::Result {
+        let windows = self
+            .windows
+            .values()
+            .filter(|window| query.includes_window(window))
+            .collect::<Vec<_>>();
+
+        let from = windows.first().map(|window| window.start);
+        let to = windows.last().map(|window| window.start + window.duration);
+        let elapsed_secs = match (from, to) {
+            (Some(from), Some(to)) => to.duration_since(from).unwrap_or_default().as_secs_f64(),
+            _ => 0.0,
+        };
+
+        let mut actions: BTreeMap<ActionKey, ActionStats> = BTreeMap::new();
+        for window in windows.iter() {
+            for (key, outcomes) in window
+                .actions
+                .iter()
+                .filter(|(key, _)| query.includes_action(key))
+            {
+                let stats = actions.entry(key.clone()).or_insert_with(|| ActionStats {
+                    key: key.clone(),
+                    count: 0,
+                    errors: 0,
+                    throughput: 0.0,
+                    outcomes: Default::default(),
+                    histogram: Default::default(),
+                });
+                for (outcome, histogram) in outcomes.iter() {
+                    let count = histogram.count();
+                    stats.count += count;
+                    if is_error_outcome(*outcome) {
+                        stats.errors += count;
+                    }
+                    *stats.outcomes.entry(*outcome).or_default() += count;
+                    stats.histogram.merge(histogram);
+                }
+            }
+        }
+
+        let actions = actions
+            .into_values()
+            .map(|stats| ActionStats {
+                throughput: if elapsed_secs > 0.0 {
+                    stats.count as f64 / elapsed_secs
+                } else {
+                    0.0
+                },
+                ..stats
+            })
+            .collect();
+
+        let bots = windows
+            .iter()
+            .rev()
+            .find(|window| !window.bots.is_empty())
+            .map(|window| {
+                window
+                    .bots
+                    .iter()
+                    .filter(|(model, _)| query.model.as_ref().map(|m| m.eq(*model)).unwrap_or(true))
+                    .map(|(model, states)| (model.clone(), states.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let sum_counter = |counter: fn(&MetricsWindow) -> &HashMap<String, u64>| {
+            let mut totals: HashMap<String, u64> = HashMap::new();
+            for window in windows.iter() {
+                for (model, count) in counter(window)
+                    .iter()
+                    .filter(|(model, _)| query.model.as_ref().map(|m| m.eq(*model)).unwrap_or(true))
+                {
+                    *totals.entry(model.clone()).or_default() += count;
+                }
+            }
+            totals
+        };
+        let dropped_iterations = sum_counter(|window| &window.dropped_iterations);
+        let skipped_ticks = sum_counter(|window| &window.skipped_ticks);
+
+        let mut custom_metrics = HashMap::new();
+        for window in windows.iter() {
+            for (key, value) in window.custom_metrics.iter().filter(|(key, _)| {
+                query
+                    .model
+                    .as_ref()
+                    .map(|m| key.model.eq(m))
+                    .unwrap_or(true)
+            }) {
+                merge_custom_metric(&mut custom_metrics, key, value);
+            }
+        }
+
+        MetricsSummary {
+            from,
+            to,
+            actions,
+            bots,
+            dropped_iterations,
+            skipped_ticks,
+            custom_metrics: custom_metrics.into_iter().collect(),
+        }
+    }
+}
+
+impl Handler<FetchMetricsWindows> for MetricsStoreActor {
+    type Result = MessageResult<FetchMetricsWindows>;
+
+    fn handle(&mut self, msg: FetchMetricsWindows, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(
+            self.windows
+                .values()
+                .filter(|window| {
+                    msg.since
+                        .map(|since| window.start + window.duration > since)
+                        .unwrap_or(true)
+                })
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+impl Handler<ResetMetrics> for MetricsStoreActor {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ResetMetrics, _ctx: &mut Self::Context) -> Self::Result {
+        self.windows.clear();
+    }
+}
+
+impl Handler<ExportPrometheus> for MetricsStoreActor {
+    type Result = String;
+
+    fn handle(&mut self, _msg: ExportPrometheus, _ctx: &mut Self::Context) -> Self::Result {
+        let mut exposition = PrometheusExposition::default();
+        for ((key, outcome), histogram) in self.totals.iter() {
+            exposition.add_histogram(key, *outcome, histogram);
+        }
+        for (model, states) in self.total_bot_states() {
+            for (state_id, count) in states {
+                exposition.add_bots(&model, state_id, count);
+            }
+        }
+        for (model, count) in self.dropped_totals.iter() {
+            exposition.add_dropped_iterations(model, *count);
+        }
+        for (model, count) in self.skipped_totals.iter() {
+            exposition.add_skipped_ticks(model, *count);
+        }
+        for (key, value) in self.custom_totals.iter() {
+            exposition.add_custom_metric(key, value);
+        }
+        exposition.render()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::communication::protobuf::grpc::{custom_metric, fixture, CustomMetric};
+
+    fn agent_update(agent_id: u32, ts: SystemTime, buckets: Vec<u64>, bots: u32) -> AgentUpdate {
+        let histograms = vec![
+            fixture::histogram(200, buckets, 0),
+            fixture::histogram(500, vec![1], 0),
+        ];
+        fixture::agent_update(
+            agent_id,
+            ts,
+            vec![ModelStats {
+                states: vec![fixture::bot_states(ts, 2, bots)],
+                ..fixture::model_stats("Demo", ts, "get", histograms)
+            }],
+        )
+    }
+
+    #[actix::test]
+    async fn sums_dropped_iterations_deltas() {
+        let store = MetricsStoreActor::default().start();
+        let now = SystemTime::now();
+        let update = |agent_id, secs, dropped_iterations| {
+            let mut update = agent_update(agent_id, now + Duration::from_secs(secs), vec![1], 1);
+            update.stats[0].dropped_iterations = dropped_iterations;
+            update
+        };
+
+        store
+            .send(MultiAgentUpdateMessage(vec![
+                update(1, 0, 3),
+                update(2, 0, 1),
+            ]))
+            .await
+            .unwrap();
+        // agent 1 drops 2 more iterations, then loads a new simulation
+        store
+            .send(MultiAgentUpdateMessage(vec![update(1, 1, 5)]))
+            .await
+            .unwrap();
+        store
+            .send(MultiAgentUpdateMessage(vec![update(1, 2, 2)]))
+            .await
+            .unwrap();
+
+        let summary = store.send(QueryMetrics::default()).await.unwrap();
+        assert_eq!(summary.dropped_iterations["Demo"], 8);
+        let exposition = store.send(ExportPrometheus).await.unwrap();
+        assert!(exposition.contains("hailstorm_dropped_iterations_total{model=\"Demo\"} 8\n"));
+    }
+
+    #[actix::test]
+    async fn sums_skipped_ticks_apart_from_dropped_iterations() {
+        let store = MetricsStoreActor::default().start();
+        let now = SystemTime::now();
+        let update = |secs, dropped_iterations, skipped_ticks| {
+            let mut update = agent_update(1, now + Duration::from_secs(secs), vec![1], 1);
+            update.stats[0].dropped_iterations = dropped_iterations;
+            update.stats[0].skipped_ticks = skipped_ticks;
+            update
+        };
+
+        store
+            .send(MultiAgentUpdateMessage(vec![update(0, 0, 4)]))
+            .await
+            .unwrap();
+        store
+            .send(MultiAgentUpdateMessage(vec![update(1, 1, 10)]))
+            .await
+            .unwrap();
+
+        let summary = store.send(QueryMetrics::default()).await.unwrap();
+        assert_eq!(summary.skipped_ticks["Demo"], 10);
+        assert_eq!(summary.dropped_iterations["Demo"], 1);
+        let exposition = store.send(ExportPrometheus).await.unwrap();
+        assert!(exposition.contains("hailstorm_skipped_ticks_total{model=\"Demo\"} 10\n"));
+    }
+
+    #[actix::test]
+    async fn merges_script_metrics_from_agents() {
+        let store = MetricsStoreActor::default().start();
+        let now = SystemTime::now();
+        let metric = |name: &str, secs, value| CustomMetric {
+            name: name.to_string(),
+            labels: HashMap::from([(String::from("region"), String::from("eu"))]),
+            timestamp: Some((now + Duration::from_secs(secs)).into()),
+            value: Some(value),
+            ..Default::default()
+        };
+        let update = |agent_id, secs, orders, depth| {
+            let mut update = agent_update(agent_id, now + Duration::from_secs(secs), vec![1], 1);
+            update.stats[0].custom_metrics = vec![
+                metric("orders", secs, custom_metric::Value::Counter(orders)),
+                metric("depth", secs, custom_metric::Value::Gauge(depth)),
+            ];
+            update
+        };
+
+        store
+            .send(MultiAgentUpdateMessage(vec![
+                update(1, 0, 3.0, 7.0),
+                update(2, 0, 1.0, 4.0),
+            ]))
+            .await
+            .unwrap();
+        store
+            .send(MultiAgentUpdateMessage(vec![update(1, 1, 5.0, 9.0)]))
+            .await
+            .unwrap();
+
+        let summary = store.send(QueryMetrics::default()).await.unwrap();
+        let key = |name: &str| CustomMetricKey {
+            model: String::from("Demo"),
+            name: name.to_string(),
+            labels: BTreeMap::from([(String::from("region"), String::from("eu"))]),
+        };
+        assert_eq!(
+            summary.custom_metrics[&key("orders")],
+            CustomMetricValue::Counter(6.0)
+        );
+        assert_eq!(
+            summary.custom_metrics[&key("depth")],
+            CustomMetricValue::Gauge(13.0)
+        );
+        let exposition = store.send(ExportPrometheus).await.unwrap();
+        assert!(
+            exposition.contains("hailstorm_script_orders_total{model=\"Demo\",region=\"eu\"} 6\n")
+        );
+    }
+
+    #[actix::test]
+    async fn merges_cumulative_histograms_from_agents() {
+        let store = MetricsStoreActor::default().start();
+        let now = SystemTime::now();
+
+        store
+            .send(MultiAgentUpdateMessage(vec![
+                agent_update(1, now, vec![4, 4], 10),
+                agent_update(2, now, vec![2], 5),
+            ]))
+            .await
+            .unwrap();
+        store
+            .send(MultiAgentUpdateMessage(vec![agent_update(
+                1,
+                now + Duration::from_secs(1),
+                vec![5, 6],
+                12,
+            )]))
+            .await
+            .unwrap();
+
+        let summary = store.send(QueryMetrics::default()).await.unwrap();
+        assert_eq!(summary.actions.len(), 1);
+
+        let stats = &summary.actions[0];
+        // agent 1: 8 + 3 (delta) ok, agent 2: 2 ok, one error per agent
+        assert_eq!(stats.count, 15);
+        assert_eq!(stats.errors, 2);
+        assert_eq!(stats.outcomes.get(&200), Some(&13));
+        assert!(stats.throughput > 0.0);
+        assert_eq!(summary.bots["Demo"][&2], 17);
+    }
+
+    #[actix::test]
+    async fn restarts_baselines_on_new_simulation() {
+        let store = MetricsStoreActor::default().start();
+        let now = SystemTime::now();
+        let update = |simulation_id: &str, secs, buckets| {
+            let mut update = agent_update(1, now + Duration::from_secs(secs), buckets, 1);
+            update.simulation_id = simulation_id.to_string();
+            update.stats[0].performance[0].simulation_id = simulation_id.to_string();
+            update
+        };
+
+        store
+            .send(MultiAgentUpdateMessage(vec![update(
+                "previous",
+                0,
+                vec![8],
+            )]))
+            .await
+            .unwrap();
+        // the agent reset its histograms when loading the new simulation
+        store
+            .send(MultiAgentUpdateMessage(vec![update("current", 1, vec![3])]))
+            .await
+            .unwrap();
+        store
+            .send(MultiAgentUpdateMessage(vec![update("current", 2, vec![5])]))
+            .await
+            .unwrap();
+
+        let summary = store.send(QueryMetrics::default()).await.unwrap();
+        assert_eq!(summary.actions[0].outcomes.get(&200), Some(&13));
+        assert_eq!(summary.actions[0].errors, 2);
+    }
+
+    #[actix::test]
+    async fn query_filters_by_model() {
+        let store = MetricsStoreActor::default().start();
+        store
+            .send(MultiAgentUpdateMessage(vec![agent_update(
+                1,
+                SystemTime::now(),
+                vec![1],
+                1,
+            )]))
+            .await
+            .unwrap();
+
+        let summary = store
+            .send(QueryMetrics::default().model("Other"))
+            .await
+            .unwrap();
+        assert!(summary.actions.is_empty());
+        assert!(summary.bots.is_empty());
+    }
+
+    #[actix::test]
+    async fn export_totals_survive_reset() {
+        let store = MetricsStoreActor::default().start();
+        store
+            .send(MultiAgentUpdateMessage(vec![agent_update(
+                1,
+                SystemTime::now(),
+                vec![3],
+                2,
+            )]))
+            .await
+            .unwrap();
+        store.send(ResetMetrics).await.unwrap();
+
+        let text = store.send(ExportPrometheus).await.unwrap();
+        assert!(
+            text.contains(r#"hailstorm_actions_total{model="Demo",action="get",outcome="200"} 3"#)
+        );
+        assert!(text.contains(r#"hailstorm_bots{model="Demo",state="running"} 2"#));
+    }
+}
@@ -0,0 +1,358 @@
+use crate::agent::metrics::layout::HistogramLayout;
+use crate::communication::protobuf::grpc::PerformanceHistogram;
+use std::time::Duration;
+
+/// Number of logarithmic buckets of the default agents layout.
+pub const BUCKETS_COUNT: usize = 20;
+
+/// Latency histogram merged from agents [`PerformanceHistogram`] frames.
+///
+/// Buckets follow the [`HistogramLayout`] the agent recorded them with, 20 log2 buckets of 10ms
+/// by default: bucket `i` counts observations in `(2^(i-1), 2^i]` units of 10ms, bucket 0
+/// collects everything up to 10ms and the last bucket is unbounded.
+///
+/// Merging histograms with different layouts keeps the layout of the first non-empty one, the
+/// observations of the other are moved to the buckets containing the middle of their own.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    layout: HistogramLayout,
+    buckets: Vec<u64>,
+    sum: Duration,
+    min: Option<Duration>,
+    max: Option<Duration>,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::empty(HistogramLayout::default())
+    }
+}
+
+impl From<&PerformanceHistogram> for LatencyHistogram {
+    fn from(hist: &PerformanceHistogram) -> Self {
+        let layout = hist
+            .layout
+            .as_ref()
+            .map(HistogramLayout::from)
+            .unwrap_or_default();
+        let mut histogram = Self::empty(layout);
+        for (idx, count) in hist.buckets.iter().enumerate() {
+            histogram.add(idx, *count);
+        }
+        histogram.trim();
+        histogram.sum = layout.from_units(hist.sum);
+        // agents not reporting the count do not report min and max either
+        if hist.count > 0 {
+            histogram.min = Some(layout.from_units(hist.min));
+            histogram.max = Some(layout.from_units(hist.max));
+        }
+        histogram
+    }
+}
+
+impl LatencyHistogram {
+    fn empty(layout: HistogramLayout) -> Self {
+        Self {
+            layout,
+            buckets: vec![0; layout.fixed_len().unwrap_or_default()],
+            sum: Duration::ZERO,
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Add observations to a bucket, the ones beyond a fixed size layout go to the last bucket
+    fn add(&mut self, idx: usize, count: u64) {
+        let idx = self
+            .layout
+            .fixed_len()
+            .map_or(idx, |len| idx.min(len.saturating_sub(1)));
+        if self.buckets.len() <= idx {
+            self.buckets.resize(idx + 1, 0);
+        }
+        self.buckets[idx] += count;
+    }
+
+    /// Drop the trailing empty buckets of the layouts without a fixed size
+    fn trim(&mut self) {
+        if self.layout.fixed_len().is_none() {
+            let len = self
+                .buckets
+                .iter()
+                .rposition(|count| *count > 0)
+                .map_or(0, |idx| idx + 1);
+            self.buckets.truncate(len);
+        }
+    }
+
+    /// Bucket boundaries of the histogram
+    pub fn layout(&self) -> &HistogramLayout {
+        &self.layout
+    }
+
+    /// Observations count for each bucket
+    pub fn buckets(&self) -> &[u64] {
+        &self.buckets
+    }
+
+    /// Total number of observations
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Sum of all observed latencies
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// Average observed latency, `None` if the histogram is empty
+    pub fn mean(&self) -> Option<Duration> {
+        let count = self.count();
+        if count > 0 {
+            Some(self.sum.div_f64(count as f64))
+        } else {
+            None
+        }
+    }
+
+    /// Smallest observed latency, `None` if empty or not reported by the agents
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    /// Largest observed latency, `None` if empty or not reported by the agents
+    pub fn max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    /// Upper bound of the bucket with the given index
+    pub fn bucket_upper_bound(&self, idx: usize) -> Duration {
+        self.layout.bucket_bounds(idx).1
+    }
+
+    /// Add all the observations of another histogram
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        let (self_empty, other_empty) = (self.count() == 0, other.count() == 0);
+        if self_empty && self.layout != other.layout {
+            self.layout = other.layout;
+            self.buckets = vec![0; other.layout.fixed_len().unwrap_or_default()];
+        }
+        if self.layout == other.layout {
+            for (idx, count) in other.buckets.iter().enumerate() {
+                self.add(idx, *count);
+            }
+        } else {
+            for (idx, count) in other.buckets.iter().enumerate().filter(|(_, c)| **c > 0) {
+                let (lower, upper) = other.layout.bucket_bounds(idx);
+                let mut middle = lower + (upper - lower) / 2;
+                if let (Some(min), Some(max)) = (other.min, other.max) {
+                    middle = middle.clamp(min, max.max(min));
+                }
+                self.add(self.layout.bucket_index(middle), *count);
+            }
+        }
+        self.sum += other.sum;
+        // bounds are unknown as soon as a non-empty side does not report them
+        (self.min, self.max) = match (self_empty, other_empty) {
+            (true, _) => (other.min, other.max),
+            (_, true) => (self.min, self.max),
+            _ => (
+                self.min.zip(other.min).map(|(min, other)| min.min(other)),
+                self.max.zip(other.max).map(|(max, other)| max.max(other)),
+            ),
+        };
+    }
+
+    /// Observations added since a previous snapshot of the same cumulative histogram.
+    ///
+    /// If any counter decreased or the layout changed the source has been reset (e.g. agent
+    /// restart) so the whole histogram is considered new. Min and max of the delta are exact
+    /// when they changed, otherwise they are bounded by the non-empty delta buckets.
+    pub fn delta(&self, previous: &LatencyHistogram) -> LatencyHistogram {
+        let previous_count = |idx: usize| previous.buckets.get(idx).copied().unwrap_or_default();
+        let reset = self.layout != previous.layout
+            || self.sum < previous.sum
+            || (previous.buckets.len() > self.buckets.len()
+                && previous.buckets[self.buckets.len()..]
+                    .iter()
+                    .any(|count| *count > 0))
+            || self
+                .buckets
+                .iter()
+                .enumerate()
+                .any(|(idx, curr)| *curr < previous_count(idx));
+
+        if reset {
+            return self.clone();
+        }
+        let mut delta = Self::empty(self.layout);
+        delta.buckets = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(idx, curr)| curr - previous_count(idx))
+            .collect();
+        delta.trim();
+        delta.sum = self.sum - previous.sum;
+        let first = delta.buckets.iter().position(|count| *count > 0);
+        let last = delta.buckets.iter().rposition(|count| *count > 0);
+        delta.min = match (self.min, previous.min, first) {
+            (Some(min), Some(prev), _) if min < prev => Some(min),
+            (Some(min), _, Some(first)) => Some(self.layout.bucket_bounds(first).0.max(min)),
+            _ => None,
+        };
+        delta.max = match (self.max, previous.max, last) {
+            (Some(max), Some(prev), _) if max > prev => Some(max),
+            (Some(max), _, Some(last)) => Some(self.bucket_upper_bound(last).min(max)),
+            _ => None,
+        };
+        delta
+    }
+
+    /// Approximate the latency below which falls the given fraction (`0.0..=1.0`) of observations.
+    ///
+    /// The value is linearly interpolated inside the bucket containing the requested rank and
+    /// kept between the observed min and max when known, `None` is returned when the histogram is
+    /// empty.
+    pub fn percentile(&self, quantile: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = quantile.clamp(0.0, 1.0) * count as f64;
+        let mut cumulated = 0u64;
+        let mut value = None;
+        for (idx, bucket_count) in self.buckets.iter().enumerate() {
+            if *bucket_count == 0 {
+                continue;
+            }
+            if (cumulated + bucket_count) as f64 >= rank {
+                let (lower, upper) = self.layout.bucket_bounds(idx);
+                let fraction = (rank - cumulated as f64) / *bucket_count as f64;
+                value = Some(lower + (upper - lower).mul_f64(fraction.clamp(0.0, 1.0)));
+                break;
+            }
+            cumulated += bucket_count;
+        }
+        let value =
+            value.unwrap_or_else(|| self.bucket_upper_bound(self.buckets.len().saturating_sub(1)));
+        Some(match (self.min, self.max) {
+            (Some(min), Some(max)) => value.clamp(min, max.max(min)),
+            _ => value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::communication::protobuf::grpc::fixture;
+
+    fn histogram(buckets: &[u64], sum: u64) -> LatencyHistogram {
+        LatencyHistogram::from(&fixture::histogram(200, buckets.to_vec(), sum))
+    }
+
+    #[test]
+    fn delta_of_cumulative_histograms() {
+        let prev = histogram(&[1, 2, 3], 10);
+        let curr = histogram(&[2, 2, 5], 17);
+        assert_eq!(curr.delta(&prev), histogram(&[1, 0, 2], 7));
+    }
+
+    #[test]
+    fn delta_after_reset_keeps_whole_histogram() {
+        let prev = histogram(&[5, 5], 40);
+        let curr = histogram(&[1, 0], 1);
+        assert_eq!(curr.delta(&prev), curr);
+    }
+
+    #[test]
+    fn merge_sums_buckets() {
+        let mut acc = histogram(&[1, 2], 5);
+        acc.merge(&histogram(&[0, 3, 1], 12));
+        assert_eq!(acc, histogram(&[1, 5, 1], 17));
+        assert_eq!(acc.count(), 7);
+    }
+
+    #[test]
+    fn percentile_interpolates_inside_bucket() {
+        // 100 observations in (20ms, 40ms]
+        let hist = histogram(&[0, 0, 100], 300);
+        assert_eq!(hist.percentile(0.5), Some(Duration::from_millis(30)));
+        assert_eq!(hist.percentile(1.0), Some(Duration::from_millis(40)));
+        assert_eq!(LatencyHistogram::default().percentile(0.5), None);
+    }
+
+    #[test]
+    fn percentile_skips_empty_buckets() {
+        let hist = histogram(&[50, 0, 0, 50], 0);
+        assert!(hist.percentile(0.25).unwrap() <= Duration::from_millis(10));
+        assert!(hist.percentile(0.75).unwrap() > Duration::from_millis(40));
+    }
+
+    fn hdr_histogram(latencies_micros: &[u64]) -> LatencyHistogram {
+        let layout = HistogramLayout::hdr(Duration::from_micros(1), 2);
+        let mut buckets = Vec::new();
+        for micros in latencies_micros {
+            let idx = layout.bucket_index(Duration::from_micros(*micros));
+            if buckets.len() <= idx {
+                buckets.resize(idx + 1, 0);
+            }
+            buckets[idx] += 1;
+        }
+        LatencyHistogram::from(&PerformanceHistogram {
+            status: 200,
+            buckets,
+            sum: latencies_micros.iter().sum(),
+            layout: Some(layout.into()),
+            count: latencies_micros.len() as u64,
+            min: latencies_micros.iter().copied().min().unwrap_or_default(),
+            max: latencies_micros.iter().copied().max().unwrap_or_default(),
+        })
+    }
+
+    #[test]
+    fn sub_unit_latencies_keep_their_resolution() {
+        let hist = hdr_histogram(&[800, 1200, 2500, 4000]);
+        assert_eq!(hist.count(), 4);
+        assert_eq!(hist.min(), Some(Duration::from_micros(800)));
+        assert_eq!(hist.max(), Some(Duration::from_micros(4000)));
+        assert_eq!(hist.mean(), Some(Duration::from_micros(2125)));
+        let p50 = hist.percentile(0.5).unwrap();
+        assert!(
+            p50 >= Duration::from_micros(1190) && p50 <= Duration::from_micros(1210),
+            "p50 = {p50:?}"
+        );
+        assert_eq!(hist.percentile(1.0), Some(Duration::from_micros(4000)));
+    }
+
+    #[test]
+    fn merge_rebins_other_layouts() {
+        let mut acc = LatencyHistogram::default();
+        acc.merge(&hdr_histogram(&[800, 1200]));
+        assert!(matches!(acc.layout(), HistogramLayout::Hdr { .. }));
+
+        // the (20ms, 40ms] legacy bucket is moved to the HDR bucket of 30ms
+        acc.merge(&histogram(&[0, 0, 2], 6));
+        assert_eq!(acc.count(), 4);
+        assert_eq!(acc.sum(), Duration::from_micros(62_000));
+        assert_eq!((acc.min(), acc.max()), (None, None));
+        let p99 = acc.percentile(0.99).unwrap();
+        assert!(p99 > Duration::from_millis(29) && p99 < Duration::from_millis(31));
+
+        let mut legacy = histogram(&[1], 0);
+        legacy.merge(&hdr_histogram(&[35_000]));
+        assert_eq!(legacy.buckets()[..3], [1, 0, 1]);
+    }
+
+    #[test]
+    fn delta_bounds_min_and_max() {
+        let prev = hdr_histogram(&[1000, 5000]);
+        let curr = hdr_histogram(&[1000, 5000, 3000, 9000]);
+        let delta = curr.delta(&prev);
+        assert_eq!(delta.count(), 2);
+        assert_eq!(delta.max(), Some(Duration::from_micros(9000)));
+        let min = delta.min().unwrap();
+        assert!(min > Duration::from_micros(2900) && min <= Duration::from_micros(3000));
+    }
+}
@@ -0,0 +1,243 @@
+use crate::agent::metrics::custom::{CustomMetricKey, CustomMetricValue};
+use crate::controller::metrics::histogram::LatencyHistogram;
+use actix::{Message, MessageResponse};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime};
+
+/// Numeric outcome of an action as reported by agents (e.g. HTTP status code).
+pub type ActionOutcome = i64;
+
+/// Identifies a single action of a bot model.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ActionKey {
+    /// Name of the bot model.
+    pub model: String,
+    /// Name of the observed action.
+    pub action: String,
+}
+
+/// Whether an outcome has to be counted as an error.
+///
+/// Negative outcomes (failed or empty results) and HTTP-like error codes (`>= 400`) are errors.
+pub fn is_error_outcome(outcome: ActionOutcome) -> bool {
+    !(0..400).contains(&outcome)
+}
+
+/// Human readable name of a bot state id as reported by agents.
+pub fn bot_state_name(state_id: u32) -> String {
+    match state_id {
+        0 => String::from("idle"),
+        1 => String::from("initializing"),
+        2 => String::from("running"),
+        3 => String::from("stopping"),
+        4 => String::from("stopped"),
+        id if id >= 100 => format!("custom_{}", id - 100),
+        id => format!("unknown_{id}"),
+    }
+}
+
+/// Metrics merged from all agents over a time window.
+#[derive(Clone, Debug)]
+pub struct MetricsWindow {
+    /// Window start timestamp (aligned to the window duration).
+    pub start: SystemTime,
+    /// Window length.
+    pub duration: Duration,
+    /// Latency histograms observed during the window, grouped by action and outcome.
+    pub actions: HashMap<ActionKey, BTreeMap<ActionOutcome, LatencyHistogram>>,
+    /// Total bots count per model and state id at the end of the window.
+    pub bots: HashMap<String, BTreeMap<u32, u64>>,
+    /// Arrival-rate iterations dropped during the window per model.
+    pub dropped_iterations: HashMap<String, u64>,
+    /// Interval ticks skipped by the paced bots during the window per model.
+    pub skipped_ticks: HashMap<String, u64>,
+    /// Script metrics recorded during the window: counters and histograms increase, gauges summed
+    /// over the last value of each agent.
+    pub custom_metrics: HashMap<CustomMetricKey, CustomMetricValue>,
+}
+
+/// Aggregated statistics of a single action over the queried interval.
+#[derive(Clone, Debug)]
+pub struct ActionStats {
+    /// Model and action described by these stats.
+    pub key: ActionKey,
+    /// Number of executions.
+    pub count: u64,
+    /// Number of executions with an error outcome (see [`is_error_outcome`]).
+    pub errors: u64,
+    /// Executions per second over the queried interval.
+    pub throughput: f64,
+    /// Executions count grouped by outcome.
+    pub outcomes: BTreeMap<ActionOutcome, u64>,
+    /// Latency histogram merged over all the outcomes.
+    pub histogram: LatencyHistogram,
+}
+
+impl ActionStats {
+    /// Fraction (`0.0..=1.0`) of executions with an error outcome
+    pub fn error_rate(&self) -> f64 {
+        if self.count > 0 {
+            self.errors as f64 / self.count as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Approximate latency percentile (e.g. `0.95` for p95)
+    pub fn percentile(&self, quantile: f64) -> Option<Duration> {
+        self.histogram.percentile(quantile)
+    }
+}
+
+/// Passes and failures of a script check over the queried interval.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckStats {
+    /// Name of the bot model evaluating the check.
+    pub model: String,
+    /// Name of the check.
+    pub name: String,
+    /// Number of evaluations that passed.
+    pub passes: u64,
+    /// Number of evaluations that failed.
+    pub fails: u64,
+}
+
+impl CheckStats {
+    /// Fraction (`0.0..=1.0`) of passed evaluations, `None` if the check was never evaluated
+    pub fn pass_rate(&self) -> Option<f64> {
+        let total = self.passes + self.fails;
+        (total > 0).then(|| self.passes as f64 / total as f64)
+    }
+}
+
+/// Result of a [`QueryMetrics`] request.
+#[derive(MessageResponse, Clone, Debug, Default)]
+pub struct MetricsSummary {
+    /// Start of the first window included in the summary.
+    pub from: Option<SystemTime>,
+    /// End of the last window included in the summary.
+    pub to: Option<SystemTime>,
+    /// Stats for each matching action.
+    pub actions: Vec<ActionStats>,
+    /// Most recent bots count per model and state id.
+    pub bots: HashMap<String, BTreeMap<u32, u64>>,
+    /// Arrival-rate iterations dropped over the queried interval per model.
+    pub dropped_iterations: HashMap<String, u64>,
+    /// Interval ticks skipped by the paced bots over the queried interval per model.
+    pub skipped_ticks: HashMap<String, u64>,
+    /// Script metrics over the queried interval: counters and histograms summed, gauges at their
+    /// most recent value.
+    pub custom_metrics: BTreeMap<CustomMetricKey, CustomMetricValue>,
+}
+
+impl MetricsSummary {
+    /// Passes and failures of the script checks, collected from the `checks` counters of
+    /// [`custom_metrics`](Self::custom_metrics)
+    pub fn checks(&self) -> Vec<CheckStats> {
+        let mut checks = BTreeMap::<(&str, &str), CheckStats>::new();
+        for (key, value) in self.custom_metrics.iter() {
+            let (Some((name, passed)), CustomMetricValue::Counter(count)) = (key.as_check(), value)
+            else {
+                continue;
+            };
+            let stats = checks
+                .entry((&key.model, name))
+                .or_insert_with(|| CheckStats {
+                    model: key.model.clone(),
+                    name: name.to_string(),
+                    passes: 0,
+                    fails: 0,
+                });
+            if passed {
+                stats.passes += count.round() as u64;
+            } else {
+                stats.fails += count.round() as u64;
+            }
+        }
+        checks.into_values().collect()
+    }
+}
+
+#[derive(Message, Clone, Debug, Default)]
+#[rtype(result = "MetricsSummary")]
+/// Query aggregated statistics, optionally filtered by model, action and time range.
+pub struct QueryMetrics {
+    pub(super) model: Option<String>,
+    pub(super) action: Option<String>,
+    pub(super) since: Option<SystemTime>,
+    pub(super) until: Option<SystemTime>,
+}
+
+impl QueryMetrics {
+    /// Only include actions of the given model
+    pub fn model(self, model: &str) -> Self {
+        Self {
+            model: Some(String::from(model)),
+            ..self
+        }
+    }
+
+    /// Only include actions with the given name
+    pub fn action(self, action: &str) -> Self {
+        Self {
+            action: Some(String::from(action)),
+            ..self
+        }
+    }
+
+    /// Only include windows ending after the given time
+    pub fn since(self, since: SystemTime) -> Self {
+        Self {
+            since: Some(since),
+            ..self
+        }
+    }
+
+    /// Only include windows starting before the given time
+    pub fn until(self, until: SystemTime) -> Self {
+        Self {
+            until: Some(until),
+            ..self
+        }
+    }
+
+    pub(super) fn includes_window(&self, window: &MetricsWindow) -> bool {
+        self.since
+            .map(|since| window.start + window.duration > since)
+            .unwrap_or(true)
+            && self.until.map(|until| window.start < until).unwrap_or(true)
+    }
+
+    pub(super) fn includes_action(&self, key: &ActionKey) -> bool {
+        self.model.as_ref().map(|m| key.model.eq(m)).unwrap_or(true)
+            && self
+                .action
+                .as_ref()
+                .map(|a| key.action.eq(a))
+                .unwrap_or(true)
+    }
+}
+
+#[derive(Message, Clone, Debug, Default)]
+#[rtype(result = "Vec<MetricsWindow>")]
+/// Fetch the stored metrics windows, ordered by start time.
+pub struct FetchMetricsWindows {
+    pub(super) since: Option<SystemTime>,
+}
+
+impl FetchMetricsWindows {
+    /// Only return windows ending after the given time
+    pub fn since(since: SystemTime) -> Self {
+        Self { since: Some(since) }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+/// Drop all the stored windows, e.g. before starting a new simulation.
+pub struct ResetMetrics;
+
+#[derive(Message)]
+#[rtype(result = "String")]
+/// Render the metrics received since startup in Prometheus text format.
+pub struct ExportPrometheus;
@@ -0,0 +1,4 @@
+pub mod actor;
+pub mod histogram;
+pub mod message;
+pub mod prometheus;
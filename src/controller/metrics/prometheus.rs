@@ -0,0 +1,419 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::agent::metrics::custom::{CustomMetricKey, CustomMetricValue};
+use crate::communication::protobuf::grpc::ModelStats;
+use crate::controller::metrics::histogram::LatencyHistogram;
+use crate::controller::metrics::message::{bot_state_name, ActionKey, ActionOutcome};
+
+/// Builder of a Prometheus text format exposition of hailstorm metrics.
+///
+/// Renders the following metric families:
+/// - `hailstorm_action_duration_seconds` — latency histogram by `model`, `action` and `outcome`,
+///   bucket boundaries match the agents layout (by default log2 buckets with `le` from 10ms to
+///   ~43min, then `+Inf`), the empty buckets of HDR layouts are omitted;
+/// - `hailstorm_actions_total` — executed actions counter by `model`, `action` and `outcome`;
+/// - `hailstorm_bots` — bots count gauge by `model` and `state`;
+/// - `hailstorm_dropped_iterations_total` — arrival-rate iterations dropped by `model`;
+/// - `hailstorm_skipped_ticks_total` — interval ticks skipped by the paced bots by `model`;
+/// - `hailstorm_script_<name>` — metrics recorded by the scripts by `model` and script labels,
+///   counters are suffixed with `_total`. Names and label names are sanitized, script labels
+///   clashing with `model` or `le` are prefixed with `exported_`.
+#[derive(Default, Debug)]
+pub struct PrometheusExposition {
+    histograms: BTreeMap<(ActionKey, ActionOutcome), LatencyHistogram>,
+    bots: BTreeMap<(String, u32), u64>,
+    dropped_iterations: BTreeMap<String, u64>,
+    skipped_ticks: BTreeMap<String, u64>,
+    custom_metrics: BTreeMap<CustomMetricKey, CustomMetricValue>,
+}
+
+impl PrometheusExposition {
+    /// Add a cumulative histogram, merging it with the one already present for the same series
+    pub fn add_histogram(
+        &mut self,
+        key: &ActionKey,
+        outcome: ActionOutcome,
+        histogram: &LatencyHistogram,
+    ) {
+        self.histograms
+            .entry((key.clone(), outcome))
+            .or_default()
+            .merge(histogram);
+    }
+
+    /// Add a number of bots to the gauge of the given model and state
+    pub fn add_bots(&mut self, model: &str, state_id: u32, count: u64) {
+        *self.bots.entry((model.to_string(), state_id)).or_default() += count;
+    }
+
+    /// Add a number of dropped iterations to the counter of the given model
+    pub fn add_dropped_iterations(&mut self, model: &str, count: u64) {
+        *self
+            .dropped_iterations
+            .entry(model.to_string())
+            .or_default() += count;
+    }
+
+    /// Add a number of skipped interval ticks to the counter of the given model
+    pub fn add_skipped_ticks(&mut self, model: &str, count: u64) {
+        *self.skipped_ticks.entry(model.to_string()).or_default() += count;
+    }
+
+    /// Add a script metric, merging it with the one already present for the same series
+    pub fn add_custom_metric(&mut self, key: &CustomMetricKey, value: &CustomMetricValue) {
+        match self.custom_metrics.get_mut(key) {
+            Some(current) => current.merge(value),
+            None => {
+                self.custom_metrics.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    /// Add the most recent histograms, bot counts, dropped iterations, skipped ticks and script
+    /// metrics contained in a [`ModelStats`] frame
+    pub fn add_model_stats(&mut self, stats: &ModelStats) {
+        let mut latest_perf: BTreeMap<&str, _> = BTreeMap::new();
+        for snapshot in stats.performance.iter() {
+            let ts = snapshot.timestamp.map(|ts| (ts.seconds, ts.nanos));
+            match latest_perf.get(snapshot.action.as_str()) {
+                Some((latest_ts, _)) if *latest_ts >= ts => {}
+                _ => {
+                    latest_perf.insert(snapshot.action.as_str(), (ts, snapshot));
+                }
+            }
+        }
+        for (_, snapshot) in latest_perf.into_values() {
+            let key = ActionKey {
+                model: stats.model.clone(),
+                action: snapshot.action.clone(),
+            };
+            for histogram in snapshot.histograms.iter() {
+                self.add_histogram(&key, histogram.status, &LatencyHistogram::from(histogram));
+            }
+        }
+
+        if let Some(states) = stats
+            .states
+            .iter()
+            .max_by_key(|snapshot| snapshot.timestamp.map(|ts| (ts.seconds, ts.nanos)))
+        {
+            for group in states.states.iter() {
+                self.add_bots(&stats.model, group.state_id, group.count as u64);
+            }
+        }
+        self.add_dropped_iterations(&stats.model, stats.dropped_iterations);
+        self.add_skipped_ticks(&stats.model, stats.skipped_ticks);
+        for metric in stats.custom_metrics.iter() {
+            if let Some(value) = metric.metric_value() {
+                self.add_custom_metric(&metric.key(&stats.model), &value);
+            }
+        }
+    }
+
+    /// Render the exposition in Prometheus text format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP hailstorm_action_duration_seconds Latency of bot actions.\n");
+        out.push_str("# TYPE hailstorm_action_duration_seconds histogram\n");
+        for ((key, outcome), histogram) in self.histograms.iter() {
+            let labels = action_labels(key, *outcome);
+            let layout = histogram.layout();
+            let sparse = layout.fixed_len().is_none();
+            let mut cumulated = 0;
+            for (idx, count) in histogram.buckets().iter().enumerate() {
+                cumulated += count;
+                if sparse && *count == 0 {
+                    continue;
+                }
+                let le = if layout.is_unbounded(idx) {
+                    String::from("+Inf")
+                } else {
+                    histogram.bucket_upper_bound(idx).as_secs_f64().to_string()
+                };
+                let _ = writeln!(
+                    out,
+                    "hailstorm_action_duration_seconds_bucket{{{labels},le=\"{le}\"}} {cumulated}"
+                );
+            }
+            if sparse {
+                let _ = writeln!(
+                    out,
+                    "hailstorm_action_duration_seconds_bucket{{{labels},le=\"+Inf\"}} {cumulated}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "hailstorm_action_duration_seconds_sum{{{labels}}} {}",
+                histogram.sum().as_secs_f64()
+            );
+            let _ = writeln!(
+                out,
+                "hailstorm_action_duration_seconds_count{{{labels}}} {cumulated}"
+            );
+        }
+
+        out.push_str("# HELP hailstorm_actions_total Executed bot actions.\n");
+        out.push_str("# TYPE hailstorm_actions_total counter\n");
+        for ((key, outcome), histogram) in self.histograms.iter() {
+            let _ = writeln!(
+                out,
+                "hailstorm_actions_total{{{}}} {}",
+                action_labels(key, *outcome),
+                histogram.count()
+            );
+        }
+
+        out.push_str("# HELP hailstorm_bots Bots count by model and state.\n");
+        out.push_str("# TYPE hailstorm_bots gauge\n");
+        for ((model, state_id), count) in self.bots.iter() {
+            let _ = writeln!(
+                out,
+                "hailstorm_bots{{model=\"{}\",state=\"{}\"}} {count}",
+                escape_label(model),
+                bot_state_name(*state_id)
+            );
+        }
+
+        out.push_str(
+            "# HELP hailstorm_dropped_iterations_total Arrival-rate iterations dropped with the bots pool exhausted.\n",
+        );
+        out.push_str("# TYPE hailstorm_dropped_iterations_total counter\n");
+        for (model, count) in self.dropped_iterations.iter() {
+            let _ = writeln!(
+                out,
+                "hailstorm_dropped_iterations_total{{model=\"{}\"}} {count}",
+                escape_label(model)
+            );
+        }
+
+        out.push_str(
+            "# HELP hailstorm_skipped_ticks_total Interval ticks skipped by paced bots running a slow action.\n",
+        );
+        out.push_str("# TYPE hailstorm_skipped_ticks_total counter\n");
+        for (model, count) in self.skipped_ticks.iter() {
+            let _ = writeln!(
+                out,
+                "hailstorm_skipped_ticks_total{{model=\"{}\"}} {count}",
+                escape_label(model)
+            );
+        }
+
+        self.render_custom_metrics(&mut out);
+
+        out
+    }
+
+    fn render_custom_metrics(&self, out: &mut String) {
+        let mut families: BTreeMap<String, Vec<_>> = BTreeMap::new();
+        for (key, value) in self.custom_metrics.iter() {
+            let name = sanitize_name(&key.name);
+            let family = match value {
+                CustomMetricValue::Counter(_) if name.ends_with("_total") => {
+                    format!("hailstorm_script_{name}")
+                }
+                CustomMetricValue::Counter(_) => format!("hailstorm_script_{name}_total"),
+                _ => format!("hailstorm_script_{name}"),
+            };
+            families.entry(family).or_default().push((key, value));
+        }
+
+        for (family, metrics) in families.iter() {
+            let kind = metrics[0].1.kind();
+            let _ = writeln!(out, "# HELP {family} Script {kind} {}.", metrics[0].0.name);
+            let _ = writeln!(out, "# TYPE {family} {kind}");
+            for (key, value) in metrics.iter().filter(|(_, value)| value.kind() == kind) {
+                let labels = custom_labels(key);
+                match value {
+                    CustomMetricValue::Counter(value) | CustomMetricValue::Gauge(value) => {
+                        let _ = writeln!(out, "{family}{{{labels}}} {value}");
+                    }
+                    CustomMetricValue::Histogram(histogram) => {
+                        let mut cumulated = 0;
+                        for (idx, count) in histogram.buckets().iter().enumerate() {
+                            cumulated += count;
+                            let le = histogram
+                                .bounds()
+                                .get(idx)
+                                .map(|bound| bound.to_string())
+                                .unwrap_or_else(|| String::from("+Inf"));
+                            let _ = writeln!(
+                                out,
+                                "{family}_bucket{{{labels},le=\"{le}\"}} {cumulated}"
+                            );
+                        }
+                        let _ = writeln!(out, "{family}_sum{{{labels}}} {}", histogram.sum());
+                        let _ = writeln!(out, "{family}_count{{{labels}}} {cumulated}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn custom_labels(key: &CustomMetricKey) -> String {
+    let mut labels = format!("model=\"{}\"", escape_label(&key.model));
+    for (name, value) in key.labels.iter() {
+        let name = match sanitize_name(name) {
+            name if name == "model" || name == "le" => format!("exported_{name}"),
+            name => name,
+        };
+        let _ = write!(labels, ",{name}=\"{}\"", escape_label(value));
+    }
+    labels
+}
+
+/// Metric or label name with the characters not allowed by Prometheus replaced by `_`
+fn sanitize_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    match name.chars().next() {
+        Some(c) if !c.is_ascii_digit() => name,
+        _ => format!("_{name}"),
+    }
+}
+
+fn action_labels(key: &ActionKey, outcome: ActionOutcome) -> String {
+    format!(
+        "model=\"{}\",action=\"{}\",outcome=\"{outcome}\"",
+        escape_label(&key.model),
+        escape_label(&key.action)
+    )
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent::metrics::custom::ValueHistogram;
+    use crate::agent::metrics::layout::HistogramLayout;
+    use crate::communication::protobuf::grpc::{fixture, PerformanceHistogram};
+    use crate::controller::metrics::histogram::BUCKETS_COUNT;
+    use std::time::Duration;
+    use std::time::SystemTime;
+
+    #[test]
+    fn render_model_stats() {
+        let mut exposition = PrometheusExposition::default();
+        let now = SystemTime::now();
+        exposition.add_model_stats(&ModelStats {
+            dropped_iterations: 4,
+            skipped_ticks: 2,
+            states: vec![fixture::bot_states(now, 2, 7)],
+            ..fixture::model_stats(
+                "Demo",
+                now,
+                "get \"home\"",
+                vec![fixture::histogram(200, vec![1, 2, 3], 15)],
+            )
+        });
+
+        let text = exposition.render();
+        let labels = r#"model="Demo",action="get \"home\"",outcome="200""#;
+        assert!(text.contains(&format!(
+            "hailstorm_action_duration_seconds_bucket{{{labels},le=\"0.01\"}} 1\n"
+        )));
+        assert!(text.contains(&format!(
+            "hailstorm_action_duration_seconds_bucket{{{labels},le=\"0.04\"}} 6\n"
+        )));
+        assert!(text.contains(&format!(
+            "hailstorm_action_duration_seconds_bucket{{{labels},le=\"+Inf\"}} 6\n"
+        )));
+        assert!(text.contains(&format!(
+            "hailstorm_action_duration_seconds_sum{{{labels}}} 0.15\n"
+        )));
+        assert!(text.contains(&format!("hailstorm_actions_total{{{labels}}} 6\n")));
+        assert!(text.contains("hailstorm_bots{model=\"Demo\",state=\"running\"} 7\n"));
+        assert!(text.contains("hailstorm_dropped_iterations_total{model=\"Demo\"} 4\n"));
+        assert!(text.contains("hailstorm_skipped_ticks_total{model=\"Demo\"} 2\n"));
+    }
+
+    #[test]
+    fn buckets_match_agent_resolution() {
+        let mut exposition = PrometheusExposition::default();
+        exposition.add_histogram(
+            &ActionKey {
+                model: String::from("M"),
+                action: String::from("a"),
+            },
+            0,
+            &LatencyHistogram::default(),
+        );
+        let text = exposition.render();
+        assert_eq!(text.matches("_bucket{").count(), BUCKETS_COUNT);
+        assert!(text.contains("le=\"2621.44\""));
+    }
+
+    #[test]
+    fn hdr_buckets_skip_the_empty_ones() {
+        let layout = HistogramLayout::hdr(Duration::from_micros(1), 2);
+        let mut buckets = vec![0; layout.bucket_index(Duration::from_micros(1500)) + 1];
+        buckets[layout.bucket_index(Duration::from_micros(150))] = 2;
+        buckets[layout.bucket_index(Duration::from_micros(1500))] = 1;
+        let mut exposition = PrometheusExposition::default();
+        exposition.add_histogram(
+            &ActionKey {
+                model: String::from("M"),
+                action: String::from("a"),
+            },
+            0,
+            &LatencyHistogram::from(&PerformanceHistogram {
+                status: 0,
+                buckets,
+                sum: 1800,
+                layout: Some(layout.into()),
+                count: 3,
+                min: 150,
+                max: 1500,
+            }),
+        );
+        let text = exposition.render();
+        assert_eq!(text.matches("_bucket{").count(), 3);
+        assert!(text.contains("le=\"0.000151\"} 2\n"));
+        assert!(text.contains("le=\"+Inf\"} 3\n"));
+        assert!(text.contains("hailstorm_action_duration_seconds_sum{model=\"M\",action=\"a\",outcome=\"0\"} 0.0018\n"));
+    }
+
+    #[test]
+    fn script_metrics_are_sanitized() {
+        let key = |name: &str, labels: &[(&str, &str)]| CustomMetricKey {
+            model: String::from("M"),
+            name: name.to_string(),
+            labels: labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+        let mut cart = ValueHistogram::new(vec![1.0, 5.0]);
+        cart.record(3.0);
+        cart.record(8.0);
+        let mut exposition = PrometheusExposition::default();
+        exposition.add_custom_metric(
+            &key("orders.placed", &[("model", "x")]),
+            &CustomMetricValue::Counter(2.0),
+        );
+        exposition.add_custom_metric(
+            &key("orders.placed", &[("model", "x")]),
+            &CustomMetricValue::Counter(3.0),
+        );
+        exposition.add_custom_metric(&key("cart size", &[]), &CustomMetricValue::Histogram(cart));
+        let text = exposition.render();
+        assert!(text.contains("# TYPE hailstorm_script_orders_placed_total counter\n"));
+        assert!(text.contains(
+            "hailstorm_script_orders_placed_total{model=\"M\",exported_model=\"x\"} 5\n"
+        ));
+        assert!(text.contains("# TYPE hailstorm_script_cart_size histogram\n"));
+        assert!(text.contains("hailstorm_script_cart_size_bucket{model=\"M\",le=\"5\"} 1\n"));
+        assert!(text.contains("hailstorm_script_cart_size_bucket{model=\"M\",le=\"+Inf\"} 2\n"));
+        assert!(text.contains("hailstorm_script_cart_size_sum{model=\"M\"} 11\n"));
+    }
+}
@@ -7,11 +7,25 @@
 //!
 //! - [`builder::ControllerBuilder`] — Fluent builder for configuring and launching a controller.
 //! - [`actor::ControllerActor`] — The core actix actor managing simulation state and agent alignment.
-//! - [`message`] — Messages for loading and starting simulations.
+//! - [`message`] — Messages for loading, starting and stopping simulations.
+//! - [`metrics::actor::MetricsStoreActor`] — Built-in storage aggregating metrics from all the agents.
 //! - [`model::simulation`] — Simulation and bot definition types.
+//! - [`persistence::SimulationStateStore`] — Pluggable persistence of the simulation state, [`persistence::file::FileStateStore`] by default.
+//! - [`model::threshold`] — SLO thresholds evaluated against the aggregated metrics.
+//! - [`report::actor::RunReportActor`] — End-of-run reports in JSON, Markdown and HTML.
+//! - [`slo::actor::SloWatcherActor`] — Threshold verdicts and early abort on critical breaches.
+//! - [`validation::SimulationValidator`] — Dry-run of the script, models and shapes of a simulation.
+//! - `http::server::HttpApi` — HTTP/JSON admin API (requires the `http-api` feature).
 
 pub mod actor;
 pub mod builder;
 mod client;
+#[cfg(feature = "http-api")]
+pub mod http;
 pub mod message;
+pub mod metrics;
 pub mod model;
+pub mod persistence;
+pub mod report;
+pub mod slo;
+pub mod validation;
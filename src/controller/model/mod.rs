@@ -1 +1,2 @@
 pub mod simulation;
+pub mod threshold;
@@ -1,17 +1,33 @@
 use crate::communication::protobuf::grpc;
-use std::time::SystemTime;
+use crate::controller::model::threshold::Threshold;
+use crate::simulation::executor::ExecutionMode;
+use crate::simulation::series::TimeSeries;
+use crate::simulation::shape::{LoadStage, ModelShape};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 
 use crate::communication::protobuf::grpc::{
-    AgentSimulationState, ClientDistribution, LoadSimCommand,
+    AgentSimulationState, ClientDistribution, LoadSimCommand, SimulationPhase,
 };
 
-/// Definition of a bot type within a simulation, pairing a model name with its load shape expression.
-#[derive(Clone, Default)]
+/// Definition of a bot type within a simulation, pairing a model name with its load shape expression,
+/// its stage-based load profile or a recorded traffic curve.
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct BotDef {
     /// Name of the bot model (must match a struct in the Rune script).
     model: String,
     /// Mathematical expression defining the desired bot count over time (e.g., `"1000 * sin(t/10)"`).
     shape: String,
+    /// Stage-based load profile, used instead of the shape expression when not empty.
+    #[serde(default)]
+    stages: Vec<LoadStage>,
+    /// Recorded traffic curve, used instead of the stages and of the shape expression when set.
+    #[serde(default)]
+    series: Option<TimeSeries>,
+    /// Whether the shape is the number of concurrent bots or the rate of iterations per second.
+    #[serde(default)]
+    execution: ExecutionMode,
 }
 
 impl BotDef {
@@ -30,27 +46,175 @@ impl BotDef {
             ..self
         }
     }
+
+    /// immutable model name reference
+    pub fn model_ref(&self) -> &str {
+        &self.model
+    }
+
+    /// Set a stage-based load profile (e.g. ramp up, hold, ramp down) instead of the shape
+    /// expression
+    pub fn stages(self, stages: Vec<LoadStage>) -> Self {
+        Self { stages, ..self }
+    }
+
+    /// Replay a recorded traffic curve instead of the shape expression
+    pub fn series(self, series: TimeSeries) -> Self {
+        Self {
+            series: Some(series),
+            ..self
+        }
+    }
+
+    /// Set how the shape drives the load, concurrent bots by default
+    pub fn execution(self, execution: ExecutionMode) -> Self {
+        Self { execution, ..self }
+    }
+
+    /// immutable shape reference
+    pub fn shape_ref(&self) -> &str {
+        &self.shape
+    }
+
+    /// immutable stages reference
+    pub fn stages_ref(&self) -> &[LoadStage] {
+        &self.stages
+    }
+
+    /// Execution mode of the model
+    pub fn execution_ref(&self) -> ExecutionMode {
+        self.execution
+    }
+
+    /// immutable series reference
+    pub fn series_ref(&self) -> Option<&TimeSeries> {
+        self.series.as_ref()
+    }
+
+    /// Load shape of the model: the recorded series if set, otherwise the stages profile if
+    /// defined, otherwise the shape expression
+    pub fn model_shape(&self) -> ModelShape {
+        if let Some(series) = &self.series {
+            ModelShape::Series(series.clone())
+        } else if self.stages.is_empty() {
+            ModelShape::Expression(self.shape.clone())
+        } else {
+            ModelShape::Stages(self.stages.clone())
+        }
+    }
 }
 
 impl From<BotDef> for ClientDistribution {
     fn from(ud: BotDef) -> Self {
         Self {
+            stages: ud.stages.iter().map(Into::into).collect(),
+            series: ud.series.as_ref().map(Into::into),
+            arrival_rate: ud.execution.into(),
             model: ud.model,
             shape: ud.shape,
         }
     }
 }
 
+/// Phase of a multi-stage simulation, running its own load shapes for a fixed duration.
+///
+/// Within a phase the shape variable `t` is the time elapsed since the phase start, while
+/// `t_total` is the time elapsed since the simulation start and `duration` is the phase duration
+/// (all in seconds). Models without a shape in the phase are drained.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PhaseDef {
+    /// Name of the phase (e.g. `warm-up`).
+    name: String,
+    /// How long the phase lasts.
+    duration: Duration,
+    /// Bot types and their load shape expressions during the phase.
+    bots: Vec<BotDef>,
+}
+
+impl PhaseDef {
+    /// Set phase name
+    pub fn name(self, name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            ..self
+        }
+    }
+
+    /// Set phase duration
+    pub fn duration(self, duration: Duration) -> Self {
+        Self { duration, ..self }
+    }
+
+    /// Set bots for this phase
+    pub fn bots(self, bots: Vec<BotDef>) -> Self {
+        Self { bots, ..self }
+    }
+
+    /// immutable name reference
+    pub fn name_ref(&self) -> &str {
+        &self.name
+    }
+
+    /// phase duration
+    pub fn duration_ref(&self) -> Duration {
+        self.duration
+    }
+
+    /// immutable bots reference
+    pub fn bots_ref(&self) -> &[BotDef] {
+        &self.bots
+    }
+}
+
+impl From<PhaseDef> for SimulationPhase {
+    fn from(phase: PhaseDef) -> Self {
+        Self {
+            name: phase.name,
+            duration: phase.duration.try_into().ok(),
+            clients_evolution: phase.bots.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
 /// Complete definition of a simulation, including bot definitions and the Rune script source.
-#[derive(Clone, Default)]
+///
+/// A simulation either runs its `bots` shapes or, when `phases` are defined, goes through each
+/// phase in order. The run completes once its `duration` or the last phase has elapsed, otherwise
+/// it lasts until stopped.
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct SimulationDef {
+    /// Identifier generated by the controller when the simulation is loaded.
+    #[serde(default)]
+    pub(crate) id: String,
     /// Bot types and their load shape expressions.
     pub(crate) bots: Vec<BotDef>,
+    /// Sequence of phases, takes precedence over `bots` when not empty.
+    pub(crate) phases: Vec<PhaseDef>,
+    /// Maximum run duration, the simulation runs until stopped if missing.
+    pub(crate) duration: Option<Duration>,
     /// Rune script source code defining bot behaviors.
     pub(crate) script: String,
+    /// Service level objectives evaluated against the aggregated metrics.
+    pub(crate) thresholds: Vec<Threshold>,
+    /// Labels the agents running the simulation must have, all the agents run it when empty.
+    #[serde(default)]
+    pub(crate) agent_labels: HashMap<String, String>,
 }
 
 impl SimulationDef {
+    /// set the identifier of this simulation
+    pub fn id(self, id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            ..self
+        }
+    }
+
+    /// identifier of this simulation, empty until loaded by the controller
+    pub fn id_ref(&self) -> &str {
+        &self.id
+    }
+
     /// set bots for this simulation
     pub fn bots(self, bots: Vec<BotDef>) -> Self {
         Self { bots, ..self }
@@ -70,23 +234,117 @@ impl SimulationDef {
     pub fn script_ref(&self) -> &str {
         &self.script
     }
+
+    /// set thresholds for this simulation
+    pub fn thresholds(self, thresholds: Vec<Threshold>) -> Self {
+        Self { thresholds, ..self }
+    }
+
+    /// immutable thresholds reference
+    pub fn thresholds_ref(&self) -> &[Threshold] {
+        &self.thresholds
+    }
+
+    /// run this simulation only on the agents having all the given labels (e.g. `pool=mobile`)
+    pub fn agent_labels(self, agent_labels: HashMap<String, String>) -> Self {
+        Self {
+            agent_labels,
+            ..self
+        }
+    }
+
+    /// immutable agent labels reference
+    pub fn agent_labels_ref(&self) -> &HashMap<String, String> {
+        &self.agent_labels
+    }
+
+    /// Selector of the agents running this simulation, `None` when all of them run it
+    pub fn agent_selector(&self) -> Option<grpc::LabelSelector> {
+        (!self.agent_labels.is_empty()).then(|| grpc::LabelSelector::new(self.agent_labels.clone()))
+    }
+
+    /// set phases for this simulation
+    pub fn phases(self, phases: Vec<PhaseDef>) -> Self {
+        Self { phases, ..self }
+    }
+
+    /// immutable phases reference
+    pub fn phases_ref(&self) -> &[PhaseDef] {
+        &self.phases
+    }
+
+    /// Total duration of the phases, `None` if the simulation has no phases
+    pub fn phases_duration(&self) -> Option<Duration> {
+        if self.phases.is_empty() {
+            None
+        } else {
+            Some(self.phases.iter().map(|phase| phase.duration).sum())
+        }
+    }
+
+    /// set maximum run duration for this simulation
+    pub fn duration(self, duration: Duration) -> Self {
+        Self {
+            duration: Some(duration),
+            ..self
+        }
+    }
+
+    /// maximum run duration, if set
+    pub fn duration_ref(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// Duration after which a run completes, the shortest between `duration` and the phases
+    /// duration, `None` if the run lasts until stopped
+    pub fn run_duration(&self) -> Option<Duration> {
+        match (self.duration, self.phases_duration()) {
+            (Some(duration), Some(phases_duration)) => Some(duration.min(phases_duration)),
+            (duration, phases_duration) => duration.or(phases_duration),
+        }
+    }
+
+    /// Index and start offset of the phase running after `elapsed` since the simulation start.
+    ///
+    /// Returns `None` if the simulation has no phases or all of them are completed.
+    pub fn phase_at(&self, elapsed: Duration) -> Option<(usize, Duration)> {
+        let mut phase_start = Duration::ZERO;
+        for (idx, phase) in self.phases.iter().enumerate() {
+            let phase_end = phase_start + phase.duration;
+            if elapsed < phase_end {
+                return Some((idx, phase_start));
+            }
+            phase_start = phase_end;
+        }
+        None
+    }
 }
 
 impl From<SimulationDef> for LoadSimCommand {
     fn from(def: SimulationDef) -> Self {
         Self {
-            clients_evolution: def
-                .bots
-                .into_iter()
-                .map(|BotDef { model, shape }| ClientDistribution { model, shape })
-                .collect(),
+            clients_evolution: def.bots.into_iter().map(Into::into).collect(),
             script: def.script,
+            phases: def.phases.into_iter().map(Into::into).collect(),
+            simulation_id: def.id,
         }
     }
 }
 
+/// Why a simulation run ended.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompletionReason {
+    /// The run duration (or the last phase) elapsed.
+    Elapsed,
+    /// The run was stopped by a [`StopSimulation`](crate::controller::message::StopSimulation).
+    Stopped,
+    /// The run was stopped as the given critical threshold was breached, see
+    /// [`FailSimulation`](crate::controller::message::FailSimulation).
+    ThresholdBreached(String),
+}
+
 /// State machine representing the controller's current simulation lifecycle phase.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum SimulationState {
     /// No simulation is loaded.
     Idle,
@@ -102,9 +360,30 @@ pub enum SimulationState {
         /// The active simulation definition.
         simulation: SimulationDef,
     },
+    /// The last run ended, the simulation is still loaded and can be launched again.
+    Completed {
+        /// Start timestamp of the ended run.
+        start_ts: SystemTime,
+        /// When the run ended.
+        end_ts: SystemTime,
+        /// Why the run ended.
+        reason: CompletionReason,
+        /// The loaded simulation definition.
+        simulation: SimulationDef,
+    },
 }
 
 impl SimulationState {
+    /// Loaded simulation definition, if any
+    pub fn simulation_ref(&self) -> Option<&SimulationDef> {
+        match self {
+            SimulationState::Idle => None,
+            SimulationState::Ready { simulation }
+            | SimulationState::Launched { simulation, .. }
+            | SimulationState::Completed { simulation, .. } => Some(simulation),
+        }
+    }
+
     /// Check whether an agent's reported simulation state is aligned with the controller's desired state.
     pub fn is_aligned(&self, agent_sim_state: &grpc::AgentSimulationState) -> bool {
         match (self, agent_sim_state) {
@@ -116,13 +395,29 @@ impl SimulationState {
                 SimulationState::Idle,
                 AgentSimulationState::Ready
                 | AgentSimulationState::Waiting
-                | AgentSimulationState::Running,
+                | AgentSimulationState::Running
+                | AgentSimulationState::Finished,
             ) => false,
-            (SimulationState::Ready { .. }, AgentSimulationState::Ready) => true,
+            (
+                SimulationState::Ready { .. },
+                AgentSimulationState::Ready | AgentSimulationState::Stopping,
+            ) => true,
             (
                 SimulationState::Ready { .. },
                 AgentSimulationState::Idle
+                | AgentSimulationState::Waiting
+                | AgentSimulationState::Running
+                | AgentSimulationState::Finished,
+            ) => false,
+            (
+                SimulationState::Completed { .. },
+                AgentSimulationState::Ready
                 | AgentSimulationState::Stopping
+                | AgentSimulationState::Finished,
+            ) => true,
+            (
+                SimulationState::Completed { .. },
+                AgentSimulationState::Idle
                 | AgentSimulationState::Waiting
                 | AgentSimulationState::Running,
             ) => false,
@@ -132,6 +427,12 @@ impl SimulationState {
             {
                 true
             }
+            // agents may finish right before the controller completes the run
+            (SimulationState::Launched { start_ts, simulation }, AgentSimulationState::Finished) => {
+                simulation
+                    .run_duration()
+                    .is_some_and(|run_duration| *start_ts + run_duration <= SystemTime::now())
+            }
             (
                 SimulationState::Launched { .. },
                 AgentSimulationState::Idle
@@ -146,6 +447,8 @@ impl SimulationState {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::simulation::series::Interpolation;
+    use crate::simulation::shape::Easing;
     use std::ops::Add;
     use std::time::Duration;
 
@@ -199,6 +502,14 @@ mod tests {
         assert!(!state.is_aligned(&AgentSimulationState::Idle));
     }
 
+    #[test]
+    fn ready_aligned_with_stopping() {
+        let state = SimulationState::Ready {
+            simulation: SimulationDef::default(),
+        };
+        assert!(state.is_aligned(&AgentSimulationState::Stopping));
+    }
+
     #[test]
     fn launched_aligned_with_running() {
         assert!(make_launched_past().is_aligned(&AgentSimulationState::Running));
@@ -219,15 +530,119 @@ mod tests {
         assert!(!make_launched_past().is_aligned(&AgentSimulationState::Idle));
     }
 
+    #[test]
+    fn completed_aligned_with_finished() {
+        let state = SimulationState::Completed {
+            start_ts: SystemTime::now() - Duration::from_secs(60),
+            end_ts: SystemTime::now(),
+            reason: CompletionReason::Elapsed,
+            simulation: SimulationDef::default(),
+        };
+        assert!(state.is_aligned(&AgentSimulationState::Finished));
+        assert!(!state.is_aligned(&AgentSimulationState::Running));
+    }
+
+    #[test]
+    fn launched_aligned_with_finished_after_end() {
+        let state = SimulationState::Launched {
+            start_ts: SystemTime::now() - Duration::from_secs(60),
+            simulation: SimulationDef::default().duration(Duration::from_secs(30)),
+        };
+        assert!(state.is_aligned(&AgentSimulationState::Finished));
+        assert!(!make_launched_past().is_aligned(&AgentSimulationState::Finished));
+    }
+
+    #[test]
+    fn run_duration_is_the_shortest() {
+        let phases = vec![PhaseDef::default().duration(Duration::from_secs(60))];
+        assert_eq!(SimulationDef::default().run_duration(), None);
+        assert_eq!(
+            SimulationDef::default()
+                .phases(phases.clone())
+                .run_duration(),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            SimulationDef::default()
+                .phases(phases)
+                .duration(Duration::from_secs(30))
+                .run_duration(),
+            Some(Duration::from_secs(30))
+        );
+    }
+
     #[test]
     fn bot_def_builder() {
-        let def = BotDef::default()
-            .model("test_bot")
-            .shape("1000 * sin(t)");
+        let def = BotDef::default().model("test_bot").shape("1000 * sin(t)");
         assert_eq!(def.model, "test_bot");
         assert_eq!(def.shape, "1000 * sin(t)");
     }
 
+    #[test]
+    fn bot_def_series_travels_in_client_distribution() {
+        let series = TimeSeries::from_csv("t,value\n0,10\n60,30\n".as_bytes())
+            .unwrap()
+            .interpolation(Interpolation::Step)
+            .time_scale(0.5);
+        let def = BotDef::default()
+            .model("test_bot")
+            .stages(vec![LoadStage::new(100.0, Duration::from_secs(60))])
+            .series(series.clone());
+        assert_eq!(def.model_shape(), ModelShape::Series(series.clone()));
+
+        let distribution = ClientDistribution::from(def);
+        assert_eq!(
+            distribution
+                .series
+                .as_ref()
+                .map(|series| series.values.clone()),
+            Some(vec![10.0, 30.0])
+        );
+        assert_eq!(ModelShape::from(&distribution), ModelShape::Series(series));
+    }
+
+    #[test]
+    fn bot_def_execution_travels_in_client_distribution() {
+        let def = BotDef::default()
+            .model("test_bot")
+            .shape("50")
+            .execution(ExecutionMode::ArrivalRate { max_bots: 20 });
+        let distribution = ClientDistribution::from(def);
+        assert_eq!(
+            distribution.arrival_rate.map(|rate| rate.max_bots),
+            Some(20)
+        );
+        assert_eq!(
+            ExecutionMode::from(&distribution),
+            ExecutionMode::ArrivalRate { max_bots: 20 }
+        );
+
+        let closed = ClientDistribution::from(BotDef::default().model("a").shape("10"));
+        assert!(closed.arrival_rate.is_none());
+        assert_eq!(ExecutionMode::from(&closed), ExecutionMode::ConstantBots);
+    }
+
+    #[test]
+    fn bot_def_stages_travel_in_client_distribution() {
+        let stages = vec![
+            LoadStage::new(100.0, Duration::from_secs(60)).easing(Easing::Cosine),
+            LoadStage::new(100.0, Duration::from_secs(300)).easing(Easing::Step),
+            LoadStage::new(0.0, Duration::from_secs(60)),
+        ];
+        let def = BotDef::default().model("test_bot").stages(stages.clone());
+        assert_eq!(def.model_shape(), ModelShape::Stages(stages.clone()));
+
+        let distribution = ClientDistribution::from(def);
+        assert_eq!(distribution.stages.len(), 3);
+        assert_eq!(ModelShape::from(&distribution), ModelShape::Stages(stages));
+
+        let expression = ClientDistribution::from(BotDef::default().model("a").shape("10"));
+        assert_eq!(
+            ModelShape::from(&expression),
+            ModelShape::Expression(String::from("10"))
+        );
+    }
+
     #[test]
     fn simulation_def_builder() {
         let def = SimulationDef::default()
@@ -236,4 +651,20 @@ mod tests {
         assert_eq!(def.bots_ref().len(), 1);
         assert_eq!(def.script_ref(), "test script");
     }
+
+    #[test]
+    fn phase_at_elapsed_time() {
+        let def = SimulationDef::default().phases(vec![
+            PhaseDef::default().name("warm-up").duration(Duration::from_secs(60)),
+            PhaseDef::default().name("steady").duration(Duration::from_secs(300)),
+        ]);
+        assert_eq!(def.phases_duration(), Some(Duration::from_secs(360)));
+        assert_eq!(def.phase_at(Duration::ZERO), Some((0, Duration::ZERO)));
+        assert_eq!(
+            def.phase_at(Duration::from_secs(60)),
+            Some((1, Duration::from_secs(60)))
+        );
+        assert_eq!(def.phase_at(Duration::from_secs(360)), None);
+        assert_eq!(SimulationDef::default().phase_at(Duration::ZERO), None);
+    }
 }
@@ -0,0 +1,465 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::controller::metrics::histogram::LatencyHistogram;
+use crate::controller::metrics::message::MetricsSummary;
+
+/// Aggregated metric checked by a [`Threshold`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ThresholdMetric {
+    /// Latency percentile in milliseconds (e.g. `p95`, `p99.9`).
+    Percentile(f64),
+    /// Average latency in milliseconds (`mean`).
+    Mean,
+    /// Fraction of executions with an error outcome (`error_rate`).
+    ErrorRate,
+    /// Executions per second (`throughput`).
+    Throughput,
+    /// Number of executions (`count`).
+    Count,
+    /// Fraction of passed evaluations of the script checks (`checks`).
+    CheckRate,
+}
+
+/// Comparison operator of a [`Threshold`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Comparison {
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl Comparison {
+    fn holds(&self, value: f64, limit: f64) -> bool {
+        match self {
+            Comparison::Lt => value < limit,
+            Comparison::Le => value <= limit,
+            Comparison::Gt => value > limit,
+            Comparison::Ge => value >= limit,
+        }
+    }
+}
+
+/// Error parsing a threshold expression.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ThresholdParseError {
+    #[error("Invalid threshold expression '{0}', expected '<metric> <op> <value>'")]
+    InvalidExpression(String),
+    #[error("Unknown threshold metric '{0}'")]
+    UnknownMetric(String),
+    #[error("Invalid threshold value '{0}'")]
+    InvalidValue(String),
+}
+
+/// Service level objective checked against the aggregated metrics of a simulation.
+///
+/// Thresholds are parsed from expressions in the form `<metric> <op> <value>`, e.g.
+/// `p95 < 300ms`, `error_rate < 1%`, `mean <= 1.5s`, `throughput >= 100` or `checks > 99%`.
+/// Latencies accept `ms` (default) and `s` units, rates accept `%`.
+///
+/// By default the threshold applies to all the actions, use [`Threshold::model`] and
+/// [`Threshold::action`] to restrict it. `checks` thresholds measure the pass rate of the
+/// `hailstorm::check` evaluations, restricted to a single check by [`Threshold::check`].
+/// A [critical](Threshold::critical) threshold
+/// aborts the simulation as soon as it is breached over the given trailing window.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Threshold {
+    expression: String,
+    metric: ThresholdMetric,
+    comparison: Comparison,
+    limit: f64,
+    model: Option<String>,
+    action: Option<String>,
+    check: Option<String>,
+    critical_window: Option<Duration>,
+}
+
+/// Outcome of a threshold evaluation.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ThresholdVerdict {
+    /// Threshold expression.
+    pub expression: String,
+    /// Model the threshold is restricted to.
+    pub model: Option<String>,
+    /// Action the threshold is restricted to.
+    pub action: Option<String>,
+    /// Check the threshold is restricted to.
+    pub check: Option<String>,
+    /// Whether the threshold aborts the simulation when breached.
+    pub critical: bool,
+    /// Measured value, `None` if no matching action has been executed (or check evaluated).
+    pub value: Option<f64>,
+    /// Whether the threshold is satisfied, thresholds without data never pass.
+    pub passed: bool,
+}
+
+impl FromStr for Threshold {
+    type Err = ThresholdParseError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let invalid = || ThresholdParseError::InvalidExpression(expression.to_string());
+
+        let (op_idx, op) = ["<=", ">=", "<", ">"]
+            .iter()
+            .find_map(|op| expression.find(op).map(|idx| (idx, *op)))
+            .ok_or_else(invalid)?;
+        let comparison = match op {
+            "<=" => Comparison::Le,
+            ">=" => Comparison::Ge,
+            "<" => Comparison::Lt,
+            _ => Comparison::Gt,
+        };
+
+        let metric_str = expression[..op_idx].trim().to_lowercase();
+        let value_str = expression[op_idx + op.len()..].trim();
+        if metric_str.is_empty() || value_str.is_empty() {
+            return Err(invalid());
+        }
+
+        let metric = match metric_str.as_str() {
+            "mean" | "avg" => ThresholdMetric::Mean,
+            "error_rate" | "errors" => ThresholdMetric::ErrorRate,
+            "throughput" | "rps" => ThresholdMetric::Throughput,
+            "count" => ThresholdMetric::Count,
+            "checks" | "check_rate" => ThresholdMetric::CheckRate,
+            p if p.starts_with('p') => p[1..]
+                .parse::<f64>()
+                .ok()
+                .filter(|pct| *pct > 0.0 && *pct <= 100.0)
+                .map(|pct| ThresholdMetric::Percentile(pct / 100.0))
+                .ok_or_else(|| ThresholdParseError::UnknownMetric(metric_str.clone()))?,
+            other => return Err(ThresholdParseError::UnknownMetric(other.to_string())),
+        };
+
+        let limit = parse_value(metric, value_str)
+            .ok_or_else(|| ThresholdParseError::InvalidValue(value_str.to_string()))?;
+
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            metric,
+            comparison,
+            limit,
+            model: None,
+            action: None,
+            check: None,
+            critical_window: None,
+        })
+    }
+}
+
+fn parse_value(metric: ThresholdMetric, value: &str) -> Option<f64> {
+    let value = value.to_lowercase();
+    match metric {
+        ThresholdMetric::Percentile(_) | ThresholdMetric::Mean => {
+            if let Some(ms) = value.strip_suffix("ms") {
+                ms.trim().parse().ok()
+            } else if let Some(secs) = value.strip_suffix('s') {
+                secs.trim().parse::<f64>().ok().map(|secs| secs * 1000.0)
+            } else {
+                value.parse().ok()
+            }
+        }
+        ThresholdMetric::ErrorRate | ThresholdMetric::CheckRate => match value.strip_suffix('%') {
+            Some(pct) => pct.trim().parse::<f64>().ok().map(|pct| pct / 100.0),
+            None => value.parse().ok(),
+        },
+        ThresholdMetric::Throughput | ThresholdMetric::Count => value.parse().ok(),
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)?;
+        let scope = match (&self.action, &self.check) {
+            (_, Some(check)) => Some(format!("'{check}'")),
+            (Some(action), None) => Some(action.clone()),
+            (None, None) => None,
+        };
+        match (&self.model, scope) {
+            (Some(model), Some(scope)) => write!(f, " [{model}.{scope}]"),
+            (Some(model), None) => write!(f, " [{model}]"),
+            (None, Some(scope)) => write!(f, " [*.{scope}]"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl Threshold {
+    /// Parse a threshold expression (e.g. `p95 < 300ms`)
+    pub fn parse(expression: &str) -> Result<Self, ThresholdParseError> {
+        expression.parse()
+    }
+
+    /// Restrict the threshold to the actions of the given model
+    pub fn model(self, model: &str) -> Self {
+        Self {
+            model: Some(String::from(model)),
+            ..self
+        }
+    }
+
+    /// Restrict the threshold to the actions with the given name
+    pub fn action(self, action: &str) -> Self {
+        Self {
+            action: Some(String::from(action)),
+            ..self
+        }
+    }
+
+    /// Restrict a `checks` threshold to the evaluations of the check with the given name
+    pub fn check(self, check: &str) -> Self {
+        Self {
+            check: Some(String::from(check)),
+            ..self
+        }
+    }
+
+    /// Abort the simulation when the threshold is breached over the trailing `window`
+    pub fn critical(self, window: Duration) -> Self {
+        Self {
+            critical_window: Some(window),
+            ..self
+        }
+    }
+
+    /// Trailing window evaluated for critical thresholds
+    pub fn critical_window(&self) -> Option<Duration> {
+        self.critical_window
+    }
+
+    /// immutable expression reference
+    pub fn expression_ref(&self) -> &str {
+        &self.expression
+    }
+
+    /// Measure the threshold metric over the matching actions (or checks), `None` if there are no
+    /// executions
+    pub fn measure(&self, summary: &MetricsSummary) -> Option<f64> {
+        if self.metric == ThresholdMetric::CheckRate {
+            return self.measure_checks(summary);
+        }
+
+        let matching = summary
+            .actions
+            .iter()
+            .filter(|stats| {
+                self.model
+                    .as_ref()
+                    .map(|m| stats.key.model.eq(m))
+                    .unwrap_or(true)
+                    && self
+                        .action
+                        .as_ref()
+                        .map(|a| stats.key.action.eq(a))
+                        .unwrap_or(true)
+            })
+            .collect::<Vec<_>>();
+
+        let count = matching.iter().map(|stats| stats.count).sum::<u64>();
+        if count == 0 {
+            return match self.metric {
+                ThresholdMetric::Count | ThresholdMetric::Throughput => Some(0.0),
+                _ => None,
+            };
+        }
+
+        let histogram = || {
+            matching
+                .iter()
+                .fold(LatencyHistogram::default(), |mut acc, stats| {
+                    acc.merge(&stats.histogram);
+                    acc
+                })
+        };
+        let millis = |d: Duration| d.as_secs_f64() * 1000.0;
+
+        match self.metric {
+            ThresholdMetric::Percentile(quantile) => histogram().percentile(quantile).map(millis),
+            ThresholdMetric::Mean => histogram().mean().map(millis),
+            ThresholdMetric::ErrorRate => {
+                let errors = matching.iter().map(|stats| stats.errors).sum::<u64>();
+                Some(errors as f64 / count as f64)
+            }
+            ThresholdMetric::Throughput => {
+                Some(matching.iter().map(|stats| stats.throughput).sum())
+            }
+            ThresholdMetric::Count => Some(count as f64),
+            ThresholdMetric::CheckRate => None,
+        }
+    }
+
+    fn measure_checks(&self, summary: &MetricsSummary) -> Option<f64> {
+        let (passes, fails) = summary
+            .checks()
+            .into_iter()
+            .filter(|stats| {
+                self.model
+                    .as_ref()
+                    .map(|m| stats.model.eq(m))
+                    .unwrap_or(true)
+                    && self
+                        .check
+                        .as_ref()
+                        .map(|c| stats.name.eq(c))
+                        .unwrap_or(true)
+            })
+            .fold((0, 0), |(passes, fails), stats| {
+                (passes + stats.passes, fails + stats.fails)
+            });
+        (passes + fails > 0).then(|| passes as f64 / (passes + fails) as f64)
+    }
+
+    /// Evaluate the threshold over the given metrics summary
+    pub fn evaluate(&self, summary: &MetricsSummary) -> ThresholdVerdict {
+        let value = self.measure(summary);
+        ThresholdVerdict {
+            expression: self.expression.clone(),
+            model: self.model.clone(),
+            action: self.action.clone(),
+            check: self.check.clone(),
+            critical: self.critical_window.is_some(),
+            value,
+            passed: value
+                .map(|value| self.comparison.holds(value, self.limit))
+                .unwrap_or(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::metrics::custom::{CustomMetricKey, CustomMetricValue};
+    use crate::communication::protobuf::grpc::fixture;
+    use crate::controller::metrics::message::{ActionKey, ActionStats};
+
+    fn summary(actions: Vec<ActionStats>) -> MetricsSummary {
+        MetricsSummary {
+            actions,
+            ..Default::default()
+        }
+    }
+
+    fn stats(model: &str, action: &str, ok: u64, errors: u64) -> ActionStats {
+        ActionStats {
+            key: ActionKey {
+                model: model.to_string(),
+                action: action.to_string(),
+            },
+            count: ok + errors,
+            errors,
+            throughput: (ok + errors) as f64 / 10.0,
+            outcomes: Default::default(),
+            // all observations in (20ms, 40ms]
+            histogram: LatencyHistogram::from(&fixture::histogram(
+                200,
+                vec![0, 0, ok + errors],
+                3 * (ok + errors),
+            )),
+        }
+    }
+
+    #[test]
+    fn parse_expressions() {
+        let p95 = Threshold::parse("p95 < 300ms").unwrap();
+        assert_eq!(p95.metric, ThresholdMetric::Percentile(0.95));
+        assert_eq!(p95.comparison, Comparison::Lt);
+        assert_eq!(p95.limit, 300.0);
+
+        let err = Threshold::parse("error_rate <= 1%").unwrap();
+        assert_eq!(err.metric, ThresholdMetric::ErrorRate);
+        assert_eq!(err.limit, 0.01);
+
+        assert_eq!(Threshold::parse("mean < 1.5s").unwrap().limit, 1500.0);
+        assert_eq!(
+            Threshold::parse("latency < 1s"),
+            Err(ThresholdParseError::UnknownMetric(String::from("latency")))
+        );
+        assert!(Threshold::parse("p95 300ms").is_err());
+        assert!(Threshold::parse("p95 < fast").is_err());
+    }
+
+    #[test]
+    fn evaluate_scoped_threshold() {
+        let actions = summary(vec![stats("A", "get", 99, 1), stats("B", "get", 50, 50)]);
+
+        let verdict = Threshold::parse("error_rate < 2%")
+            .unwrap()
+            .model("A")
+            .evaluate(&actions);
+        assert!(verdict.passed);
+        assert_eq!(verdict.value, Some(0.01));
+
+        assert!(
+            !Threshold::parse("error_rate < 2%")
+                .unwrap()
+                .evaluate(&actions)
+                .passed
+        );
+        assert!(
+            Threshold::parse("p95 < 300ms")
+                .unwrap()
+                .evaluate(&actions)
+                .passed
+        );
+    }
+
+    #[test]
+    fn threshold_without_data_fails() {
+        let verdict = Threshold::parse("p99 < 1s")
+            .unwrap()
+            .model("Missing")
+            .evaluate(&summary(vec![stats("A", "get", 1, 0)]));
+        assert_eq!(verdict.value, None);
+        assert!(!verdict.passed);
+    }
+
+    #[test]
+    fn evaluate_check_pass_rate() {
+        let mut checks = summary(vec![stats("A", "get", 1, 0)]);
+        for (model, check, passed, count) in [
+            ("A", "status is 200", true, 995.0),
+            ("A", "status is 200", false, 5.0),
+            ("A", "has body", false, 100.0),
+            ("B", "status is 200", true, 10.0),
+        ] {
+            checks.custom_metrics.insert(
+                CustomMetricKey::check(model, check, passed),
+                CustomMetricValue::Counter(count),
+            );
+        }
+
+        let threshold = Threshold::parse("checks > 99%")
+            .unwrap()
+            .model("A")
+            .check("status is 200");
+        assert_eq!(threshold.to_string(), "checks > 99% [A.'status is 200']");
+        let verdict = threshold.evaluate(&checks);
+        assert_eq!(verdict.value, Some(0.995));
+        assert!(verdict.passed);
+
+        assert!(
+            !Threshold::parse("checks > 99%")
+                .unwrap()
+                .evaluate(&checks)
+                .passed
+        );
+        assert_eq!(
+            Threshold::parse("checks > 99%")
+                .unwrap()
+                .check("missing")
+                .evaluate(&checks)
+                .value,
+            None
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+
+use crate::controller::model::simulation::SimulationState;
+use crate::controller::persistence::{SimulationStateStore, StateStoreError};
+
+/// Persist the simulation state as a JSON file.
+///
+/// The file is written to a temporary sibling and then renamed, so that a crash during the write
+/// never leaves a truncated state behind.
+#[derive(Clone, Debug)]
+pub struct FileStateStore {
+    path: PathBuf,
+}
+
+impl Default for FileStateStore {
+    fn default() -> Self {
+        Self::new("hailstorm-state.json")
+    }
+}
+
+impl FileStateStore {
+    /// Store the state in the file at `path` (default `hailstorm-state.json`)
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl SimulationStateStore for FileStateStore {
+    fn load(&self) -> Result<Option<SimulationState>, StateStoreError> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&self.path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    fn save(&self, state: &SimulationState) -> Result<(), StateStoreError> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(state)?)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::controller::model::simulation::{BotDef, PhaseDef, SimulationDef};
+    use crate::controller::model::threshold::Threshold;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn save_and_load_launched_simulation() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStateStore::new(dir.path().join("state/controller.json"));
+        assert!(store.load().unwrap().is_none());
+
+        let start_ts = SystemTime::now();
+        store
+            .save(&SimulationState::Launched {
+                start_ts,
+                simulation: SimulationDef::default()
+                    .script(String::from("pub struct Demo;"))
+                    .phases(vec![PhaseDef::default()
+                        .name("steady")
+                        .duration(Duration::from_secs(3600))
+                        .bots(vec![BotDef::default().model("Demo").shape("10")])])
+                    .thresholds(vec![Threshold::parse("p95 < 300ms")
+                        .unwrap()
+                        .critical(Duration::from_secs(30))]),
+            })
+            .unwrap();
+
+        let Some(SimulationState::Launched {
+            start_ts: restored_ts,
+            simulation,
+        }) = store.load().unwrap()
+        else {
+            panic!("launched simulation not restored");
+        };
+        assert_eq!(restored_ts, start_ts);
+        assert_eq!(simulation.script_ref(), "pub struct Demo;");
+        assert_eq!(simulation.phases_ref()[0].bots_ref()[0].shape_ref(), "10");
+        assert_eq!(
+            simulation.thresholds_ref()[0],
+            Threshold::parse("p95 < 300ms")
+                .unwrap()
+                .critical(Duration::from_secs(30))
+        );
+    }
+}
@@ -0,0 +1,28 @@
+use thiserror::Error;
+
+use crate::controller::model::simulation::SimulationState;
+
+pub mod file;
+
+/// Error loading or saving the persisted simulation state.
+#[derive(Error, Debug)]
+pub enum StateStoreError {
+    #[error("I/O error - {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Serialization error - {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Backend persisting the controller [`SimulationState`].
+///
+/// The state is saved on every lifecycle transition (load, start, stop, abort and completion) and
+/// loaded when the controller is created, so that a restarted controller resumes the loaded or
+/// launched simulation without resetting the agents.
+/// A file-based implementation is provided by [`file::FileStateStore`].
+pub trait SimulationStateStore: Send {
+    /// Load the last saved state, `None` if nothing has been saved yet
+    fn load(&self) -> Result<Option<SimulationState>, StateStoreError>;
+
+    /// Save the current state, replacing the previous one
+    fn save(&self, state: &SimulationState) -> Result<(), StateStoreError>;
+}
@@ -0,0 +1,307 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use actix::{
+    Actor, ActorFutureExt, Addr, Context, Handler, MailboxError, Recipient, ResponseActFuture,
+    WrapFuture,
+};
+use thiserror::Error;
+
+use crate::controller::metrics::message::{FetchMetricsWindows, QueryMetrics};
+use crate::controller::report::message::GenerateRunReport;
+use crate::controller::report::model::RunReport;
+
+/// Output format of the run reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Machine-readable JSON (`.json`).
+    Json,
+    /// Human-readable Markdown (`.md`).
+    Markdown,
+    /// Standalone HTML page (`.html`).
+    Html,
+}
+
+impl ReportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Json => "json",
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+/// Configuration of the end-of-run reports.
+#[derive(Clone, Debug)]
+pub struct RunReportConfig {
+    output_dir: PathBuf,
+    formats: Vec<ReportFormat>,
+    grace_period: Duration,
+}
+
+impl Default for RunReportConfig {
+    fn default() -> Self {
+        Self {
+            output_dir: PathBuf::from("reports"),
+            formats: vec![
+                ReportFormat::Json,
+                ReportFormat::Markdown,
+                ReportFormat::Html,
+            ],
+            grace_period: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RunReportConfig {
+    /// Directory where reports are written (default `reports`)
+    pub fn output_dir(self, output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            output_dir: output_dir.into(),
+            ..self
+        }
+    }
+
+    /// Formats to be generated (default all)
+    pub fn formats(self, formats: Vec<ReportFormat>) -> Self {
+        Self { formats, ..self }
+    }
+
+    /// Time to wait for the last agents metrics before generating the report (default 5s)
+    pub fn grace_period(self, grace_period: Duration) -> Self {
+        Self {
+            grace_period,
+            ..self
+        }
+    }
+}
+
+/// Error during run report generation.
+#[derive(Error, Debug)]
+pub enum RunReportError {
+    #[error("Error fetching metrics - {0}")]
+    Metrics(#[from] MailboxError),
+    #[error("Error serializing report - {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Error writing report - {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Actor generating a report, from the metrics storage data, at the end of each run.
+pub struct RunReportActor {
+    config: RunReportConfig,
+    query_recipient: Recipient<QueryMetrics>,
+    windows_recipient: Recipient<FetchMetricsWindows>,
+}
+
+impl RunReportActor {
+    pub fn new<MetricsStorageAct>(
+        config: RunReportConfig,
+        metrics_storage_addr: Addr<MetricsStorageAct>,
+    ) -> Self
+    where
+        MetricsStorageAct: Actor<Context = Context<MetricsStorageAct>>
+            + Handler<QueryMetrics>
+            + Handler<FetchMetricsWindows>,
+    {
+        Self {
+            config,
+            query_recipient: metrics_storage_addr.clone().recipient(),
+            windows_recipient: metrics_storage_addr.recipient(),
+        }
+    }
+
+    fn write_report(&self, report: &RunReport) -> Result<(), RunReportError> {
+        std::fs::create_dir_all(&self.config.output_dir)?;
+        for format in self.config.formats.iter() {
+            let content = match format {
+                ReportFormat::Json => report.to_json()?,
+                ReportFormat::Markdown => report.to_markdown(),
+                ReportFormat::Html => report.to_html(),
+            };
+            let path = self.config.output_dir.join(format!(
+                "run-{}.{}",
+                report.started_at,
+                format.extension()
+            ));
+            std::fs::write(&path, content)?;
+            log::info!("Run report written to {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+impl Actor for RunReportActor {
+    type Context = Context<Self>;
+}
+
+impl Handler<GenerateRunReport> for RunReportActor {
+    type Result = ResponseActFuture<Self, Result<RunReport, RunReportError>>;
+
+    fn handle(&mut self, msg: GenerateRunReport, _ctx: &mut Self::Context) -> Self::Result {
+        let grace_period = self.config.grace_period;
+        let query_recipient = self.query_recipient.clone();
+        let windows_recipient = self.windows_recipient.clone();
+        let since = msg.start_ts;
+        let until = msg.end_ts + grace_period;
+
+        Box::pin(
+            async move {
+                actix::clock::sleep(grace_period).await;
+                let summary = query_recipient
+                    .send(QueryMetrics::default().since(since).until(until))
+                    .await?;
+                let windows = windows_recipient
+                    .send(FetchMetricsWindows::since(since))
+                    .await?;
+                Ok::<_, RunReportError>(RunReport::new(
+                    &msg.simulation,
+                    msg.start_ts,
+                    msg.end_ts,
+                    msg.agents,
+                    summary,
+                    &windows,
+                    msg.breached_threshold,
+                ))
+            }
+            .into_actor(self)
+            .map(|res, act, _ctx| {
+                let report = res?;
+                act.write_report(&report)?;
+                Ok(report)
+            })
+            .map(|res, _act, _ctx| {
+                if let Err(err) = &res {
+                    log::error!("Error generating run report - {err}");
+                }
+                res
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::communication::message::MultiAgentUpdateMessage;
+    use crate::communication::protobuf::grpc::{fixture, ModelStats};
+    use crate::controller::metrics::actor::MetricsStoreActor;
+    use crate::controller::model::simulation::{BotDef, SimulationDef};
+    use crate::controller::model::threshold::Threshold;
+    use crate::controller::report::message::AgentParticipation;
+    use std::time::SystemTime;
+
+    #[actix::test]
+    async fn generate_report_files() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let metrics = MetricsStoreActor::default().start();
+        let now = SystemTime::now();
+
+        let histograms = vec![
+            fixture::histogram(200, vec![0, 9], 18),
+            fixture::histogram(503, vec![1], 1),
+        ];
+        metrics
+            .send(MultiAgentUpdateMessage(vec![fixture::agent_update(
+                1,
+                now,
+                vec![ModelStats {
+                    states: vec![fixture::bot_states(now, 2, 3)],
+                    ..fixture::model_stats("Demo", now, "get", histograms)
+                }],
+            )]))
+            .await
+            .unwrap();
+
+        let reporter = RunReportActor::new(
+            RunReportConfig::default()
+                .output_dir(output_dir.path())
+                .grace_period(Duration::ZERO),
+            metrics,
+        )
+        .start();
+
+        let report = reporter
+            .send(GenerateRunReport::new(
+                SimulationDef::default()
+                    .script(String::from("pub struct Demo;"))
+                    .bots(vec![BotDef::default().model("Demo").shape("10")])
+                    .thresholds(vec![
+                        Threshold::parse("error_rate < 5%").unwrap(),
+                        Threshold::parse("count >= 10").unwrap().model("Demo"),
+                    ]),
+                now - Duration::from_secs(60),
+                now + Duration::from_secs(1),
+                vec![AgentParticipation {
+                    agent_id: 1,
+                    first_update: now,
+                    last_update: now,
+                    running: true,
+                }],
+            ))
+            .await
+            .unwrap()
+            .expect("report generation failed");
+
+        assert_eq!(report.actions.len(), 1);
+        assert_eq!(report.actions[0].count, 10);
+        assert_eq!(report.actions[0].errors, 1);
+        assert_eq!(report.actions[0].outcomes.get(&503), Some(&1));
+        assert_eq!(report.bots_timeline[0].states["running"], 3);
+        assert_eq!(report.script_hash.len(), 64);
+        assert!(!report.thresholds[0].passed);
+        assert!(report.thresholds[1].passed);
+        assert!(!report.passed);
+
+        for ext in ["json", "md", "html"] {
+            let path = output_dir
+                .path()
+                .join(format!("run-{}.{ext}", report.started_at));
+            let content = std::fs::read_to_string(path).expect("report file not written");
+            assert!(content.contains("Demo"));
+        }
+    }
+
+    #[actix::test]
+    async fn breached_threshold_fails_report() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let now = SystemTime::now();
+        let reporter = RunReportActor::new(
+            RunReportConfig::default()
+                .output_dir(output_dir.path())
+                .grace_period(Duration::ZERO),
+            MetricsStoreActor::default().start(),
+        )
+        .start();
+
+        let report = reporter
+            .send(
+                GenerateRunReport::new(
+                    SimulationDef::default(),
+                    now - Duration::from_secs(60),
+                    now,
+                    vec![],
+                )
+                .breached_threshold("error_rate < 1%"),
+            )
+            .await
+            .unwrap()
+            .expect("report generation failed");
+
+        assert_eq!(
+            report.breached_threshold.as_deref(),
+            Some("error_rate < 1%")
+        );
+        assert!(!report.passed);
+        for ext in ["json", "md", "html"] {
+            let path = output_dir
+                .path()
+                .join(format!("run-{}.{ext}", report.started_at));
+            let content = std::fs::read_to_string(path).expect("report file not written");
+            // the HTML report escapes the comparison operator
+            assert!(content.contains("error_rate"), "{ext} report: {content}");
+        }
+        assert!(report.to_markdown().contains("FAILED"));
+    }
+}
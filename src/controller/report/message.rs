@@ -0,0 +1,55 @@
+use std::time::SystemTime;
+
+use crate::controller::model::simulation::SimulationDef;
+use crate::controller::report::actor::RunReportError;
+use crate::controller::report::model::RunReport;
+
+/// Participation of an agent to a simulation run.
+#[derive(Clone, Debug)]
+pub struct AgentParticipation {
+    /// Agent identifier.
+    pub agent_id: u32,
+    /// First update received from the agent during the run.
+    pub first_update: SystemTime,
+    /// Last update received from the agent during the run.
+    pub last_update: SystemTime,
+    /// Whether the agent reported to be running the simulation.
+    pub running: bool,
+}
+
+#[derive(actix::Message)]
+#[rtype(result = "Result<RunReport, RunReportError>")]
+/// Build the report of a terminated simulation run and write it to the configured outputs
+pub struct GenerateRunReport {
+    pub(crate) simulation: SimulationDef,
+    pub(crate) start_ts: SystemTime,
+    pub(crate) end_ts: SystemTime,
+    pub(crate) agents: Vec<AgentParticipation>,
+    pub(crate) breached_threshold: Option<String>,
+}
+
+impl GenerateRunReport {
+    /// Create a new report request for the run of `simulation` between `start_ts` and `end_ts`
+    pub fn new(
+        simulation: SimulationDef,
+        start_ts: SystemTime,
+        end_ts: SystemTime,
+        agents: Vec<AgentParticipation>,
+    ) -> Self {
+        Self {
+            simulation,
+            start_ts,
+            end_ts,
+            agents,
+            breached_threshold: None,
+        }
+    }
+
+    /// Set the critical threshold whose breach stopped the run
+    pub fn breached_threshold(self, threshold: impl Into<String>) -> Self {
+        Self {
+            breached_threshold: Some(threshold.into()),
+            ..self
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod actor;
+pub mod message;
+pub mod model;
+mod render;
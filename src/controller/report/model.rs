@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::controller::metrics::message::{
+    bot_state_name, ActionStats, MetricsSummary, MetricsWindow,
+};
+use crate::controller::model::simulation::{BotDef, SimulationDef};
+use crate::controller::model::threshold::ThresholdVerdict;
+use crate::controller::report::message::AgentParticipation;
+
+/// Summary of a terminated simulation run.
+///
+/// Timestamps are expressed in milliseconds since unix epoch, latencies in milliseconds.
+#[derive(Serialize, Clone, Debug)]
+pub struct RunReport {
+    /// Simulation start timestamp.
+    pub started_at: u64,
+    /// Simulation end timestamp.
+    pub ended_at: u64,
+    /// Run duration in seconds.
+    pub duration_secs: f64,
+    /// SHA-256 of the Rune script.
+    pub script_hash: String,
+    /// Load shape of each bot model.
+    pub shapes: Vec<ShapeReport>,
+    /// Stats of each action executed during the run.
+    pub actions: Vec<ActionReport>,
+    /// Bots count per model and state over time.
+    pub bots_timeline: Vec<BotsTimelinePoint>,
+    /// Agents that took part to the run.
+    pub agents: Vec<AgentReport>,
+    /// Verdict of each threshold of the simulation.
+    pub thresholds: Vec<ThresholdVerdict>,
+    /// Critical threshold whose breach stopped the run, if any.
+    pub breached_threshold: Option<String>,
+    /// Whether all the thresholds passed and no critical one stopped the run.
+    pub passed: bool,
+}
+
+/// Load shape used by a bot model.
+#[derive(Serialize, Clone, Debug)]
+pub struct ShapeReport {
+    /// Name of the phase, `None` for simulations without phases.
+    pub phase: Option<String>,
+    /// Name of the bot model.
+    pub model: String,
+    /// Load shape expression.
+    pub shape: String,
+}
+
+/// Stats of an action over the whole run.
+#[derive(Serialize, Clone, Debug)]
+pub struct ActionReport {
+    /// Name of the bot model.
+    pub model: String,
+    /// Name of the action.
+    pub action: String,
+    /// Number of executions.
+    pub count: u64,
+    /// Number of executions with an error outcome.
+    pub errors: u64,
+    /// Fraction of executions with an error outcome.
+    pub error_rate: f64,
+    /// Executions per second.
+    pub throughput: f64,
+    /// Average latency.
+    pub mean: Option<f64>,
+    /// 50th percentile latency.
+    pub p50: Option<f64>,
+    /// 90th percentile latency.
+    pub p90: Option<f64>,
+    /// 95th percentile latency.
+    pub p95: Option<f64>,
+    /// 99th percentile latency.
+    pub p99: Option<f64>,
+    /// Executions count grouped by outcome.
+    pub outcomes: BTreeMap<i64, u64>,
+}
+
+/// Bots count of a model at a point in time.
+#[derive(Serialize, Clone, Debug)]
+pub struct BotsTimelinePoint {
+    /// Sample timestamp.
+    pub timestamp: u64,
+    /// Name of the bot model.
+    pub model: String,
+    /// Bots count by state name.
+    pub states: BTreeMap<String, u64>,
+}
+
+/// Participation of an agent to the run.
+#[derive(Serialize, Clone, Debug)]
+pub struct AgentReport {
+    /// Agent identifier.
+    pub agent_id: u32,
+    /// First update received during the run.
+    pub first_update: u64,
+    /// Last update received during the run.
+    pub last_update: u64,
+    /// Whether the agent reported to be running the simulation.
+    pub running: bool,
+}
+
+impl From<ActionStats> for ActionReport {
+    fn from(stats: ActionStats) -> Self {
+        let millis = |d: Duration| d.as_secs_f64() * 1000.0;
+        Self {
+            error_rate: stats.error_rate(),
+            mean: stats.histogram.mean().map(millis),
+            p50: stats.percentile(0.5).map(millis),
+            p90: stats.percentile(0.9).map(millis),
+            p95: stats.percentile(0.95).map(millis),
+            p99: stats.percentile(0.99).map(millis),
+            model: stats.key.model,
+            action: stats.key.action,
+            count: stats.count,
+            errors: stats.errors,
+            throughput: stats.throughput,
+            outcomes: stats.outcomes,
+        }
+    }
+}
+
+impl From<AgentParticipation> for AgentReport {
+    fn from(agent: AgentParticipation) -> Self {
+        Self {
+            agent_id: agent.agent_id,
+            first_update: epoch_millis(agent.first_update),
+            last_update: epoch_millis(agent.last_update),
+            running: agent.running,
+        }
+    }
+}
+
+impl RunReport {
+    /// Build the report of a run from the metrics collected between `start_ts` and `end_ts`,
+    /// `breached_threshold` is the critical threshold that stopped the run, if any
+    pub fn new(
+        simulation: &SimulationDef,
+        start_ts: SystemTime,
+        end_ts: SystemTime,
+        agents: Vec<AgentParticipation>,
+        summary: MetricsSummary,
+        windows: &[MetricsWindow],
+        breached_threshold: Option<String>,
+    ) -> Self {
+        let mut agents = agents
+            .into_iter()
+            .map(AgentReport::from)
+            .collect::<Vec<_>>();
+        agents.sort_by_key(|agent| agent.agent_id);
+
+        let bots_timeline = windows
+            .iter()
+            .filter(|window| window.start < end_ts)
+            .flat_map(|window| {
+                let mut models = window.bots.iter().collect::<Vec<_>>();
+                models.sort_by_key(|(model, _)| *model);
+                models.into_iter().map(|(model, states)| BotsTimelinePoint {
+                    timestamp: epoch_millis(window.start),
+                    model: model.clone(),
+                    states: states
+                        .iter()
+                        .map(|(state_id, count)| (bot_state_name(*state_id), *count))
+                        .collect(),
+                })
+            })
+            .collect();
+
+        let thresholds = simulation
+            .thresholds_ref()
+            .iter()
+            .map(|threshold| threshold.evaluate(&summary))
+            .collect::<Vec<_>>();
+
+        Self {
+            started_at: epoch_millis(start_ts),
+            ended_at: epoch_millis(end_ts),
+            duration_secs: end_ts
+                .duration_since(start_ts)
+                .unwrap_or_default()
+                .as_secs_f64(),
+            script_hash: format!("{:x}", Sha256::digest(simulation.script_ref().as_bytes())),
+            shapes: shapes(simulation),
+            actions: summary.actions.into_iter().map(Into::into).collect(),
+            bots_timeline,
+            agents,
+            passed: breached_threshold.is_none() && thresholds.iter().all(|verdict| verdict.passed),
+            thresholds,
+            breached_threshold,
+        }
+    }
+}
+
+fn shapes(simulation: &SimulationDef) -> Vec<ShapeReport> {
+    let shape_report = |phase: Option<&str>, bot: &BotDef| ShapeReport {
+        phase: phase.map(String::from),
+        model: bot.model_ref().to_string(),
+        shape: bot.model_shape().to_string(),
+    };
+    if simulation.phases_ref().is_empty() {
+        simulation
+            .bots_ref()
+            .iter()
+            .map(|bot| shape_report(None, bot))
+            .collect()
+    } else {
+        simulation
+            .phases_ref()
+            .iter()
+            .flat_map(|phase| {
+                phase
+                    .bots_ref()
+                    .iter()
+                    .map(|bot| shape_report(Some(phase.name_ref()), bot))
+            })
+            .collect()
+    }
+}
+
+pub(super) fn epoch_millis(ts: SystemTime) -> u64 {
+    ts.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
@@ -0,0 +1,259 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::controller::report::model::RunReport;
+
+/// Titled table, rendered either as Markdown or HTML.
+struct Section {
+    title: &'static str,
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl RunReport {
+    /// Render the report as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Render the report as a Markdown document
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Hailstorm run report\n\n");
+        for (label, value) in self.overview() {
+            out.push_str(&format!("- **{label}**: {value}\n"));
+        }
+        for section in self.sections() {
+            out.push_str(&format!("\n## {}\n\n", section.title));
+            if section.rows.is_empty() {
+                out.push_str("_No data_\n");
+                continue;
+            }
+            out.push_str(&format!("| {} |\n", section.headers.join(" | ")));
+            out.push_str(&format!(
+                "|{}\n",
+                section.headers.iter().map(|_| "---|").collect::<String>()
+            ));
+            for row in section.rows {
+                let cells = row
+                    .iter()
+                    .map(|cell| cell.replace('|', "\\|"))
+                    .collect::<Vec<_>>();
+                out.push_str(&format!("| {} |\n", cells.join(" | ")));
+            }
+        }
+        out
+    }
+
+    /// Render the report as a standalone HTML page
+    pub fn to_html(&self) -> String {
+        let mut out = String::from(concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+            "<title>Hailstorm run report</title>\n<style>\n",
+            "body { font-family: sans-serif; margin: 2em; }\n",
+            "table { border-collapse: collapse; margin-bottom: 1em; }\n",
+            "th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }\n",
+            "th { background: #eee; }\n",
+            "</style>\n</head>\n<body>\n<h1>Hailstorm run report</h1>\n<ul>\n"
+        ));
+        for (label, value) in self.overview() {
+            out.push_str(&format!(
+                "<li><b>{label}</b>: {}</li>\n",
+                escape_html(&value)
+            ));
+        }
+        out.push_str("</ul>\n");
+        for section in self.sections() {
+            out.push_str(&format!("<h2>{}</h2>\n", section.title));
+            if section.rows.is_empty() {
+                out.push_str("<p><i>No data</i></p>\n");
+                continue;
+            }
+            out.push_str("<table>\n<tr>");
+            for header in section.headers.iter() {
+                out.push_str(&format!("<th>{header}</th>"));
+            }
+            out.push_str("</tr>\n");
+            for row in section.rows {
+                out.push_str("<tr>");
+                for cell in row {
+                    out.push_str(&format!("<td>{}</td>", escape_html(&cell)));
+                }
+                out.push_str("</tr>\n");
+            }
+            out.push_str("</table>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    fn overview(&self) -> Vec<(&'static str, String)> {
+        let mut overview = vec![
+            ("Started", format_ts(self.started_at)),
+            ("Ended", format_ts(self.ended_at)),
+            ("Duration", format!("{:.1}s", self.duration_secs)),
+            ("Script SHA-256", self.script_hash.clone()),
+            ("Agents", self.agents.len().to_string()),
+        ];
+        if !self.thresholds.is_empty() || self.breached_threshold.is_some() {
+            let verdict = if self.passed { "PASSED" } else { "FAILED" };
+            overview.push(("Verdict", String::from(verdict)));
+        }
+        if let Some(threshold) = &self.breached_threshold {
+            overview.push(("Stopped by critical threshold", threshold.clone()));
+        }
+        overview
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        let opt_millis = |v: Option<f64>| v.map(|ms| format!("{ms:.1}")).unwrap_or_default();
+        vec![
+            Section {
+                title: "Shapes",
+                headers: vec!["Phase", "Model", "Shape"],
+                rows: self
+                    .shapes
+                    .iter()
+                    .map(|shape| {
+                        vec![
+                            shape.phase.clone().unwrap_or_default(),
+                            shape.model.clone(),
+                            shape.shape.clone(),
+                        ]
+                    })
+                    .collect(),
+            },
+            Section {
+                title: "Thresholds",
+                headers: vec![
+                    "Threshold",
+                    "Model",
+                    "Action",
+                    "Check",
+                    "Critical",
+                    "Value",
+                    "Result",
+                ],
+                rows: self
+                    .thresholds
+                    .iter()
+                    .map(|verdict| {
+                        vec![
+                            verdict.expression.clone(),
+                            verdict.model.clone().unwrap_or_default(),
+                            verdict.action.clone().unwrap_or_default(),
+                            verdict.check.clone().unwrap_or_default(),
+                            verdict.critical.to_string(),
+                            verdict.value.map(|v| format!("{v:.3}")).unwrap_or_default(),
+                            String::from(if verdict.passed { "pass" } else { "fail" }),
+                        ]
+                    })
+                    .collect(),
+            },
+            Section {
+                title: "Actions",
+                headers: vec![
+                    "Model",
+                    "Action",
+                    "Count",
+                    "Errors",
+                    "Error rate",
+                    "Throughput (/s)",
+                    "Mean (ms)",
+                    "p50 (ms)",
+                    "p90 (ms)",
+                    "p95 (ms)",
+                    "p99 (ms)",
+                ],
+                rows: self
+                    .actions
+                    .iter()
+                    .map(|action| {
+                        vec![
+                            action.model.clone(),
+                            action.action.clone(),
+                            action.count.to_string(),
+                            action.errors.to_string(),
+                            format!("{:.2}%", action.error_rate * 100.0),
+                            format!("{:.2}", action.throughput),
+                            opt_millis(action.mean),
+                            opt_millis(action.p50),
+                            opt_millis(action.p90),
+                            opt_millis(action.p95),
+                            opt_millis(action.p99),
+                        ]
+                    })
+                    .collect(),
+            },
+            Section {
+                title: "Outcomes",
+                headers: vec!["Model", "Action", "Outcome", "Count"],
+                rows: self
+                    .actions
+                    .iter()
+                    .flat_map(|action| {
+                        action.outcomes.iter().map(|(outcome, count)| {
+                            vec![
+                                action.model.clone(),
+                                action.action.clone(),
+                                outcome.to_string(),
+                                count.to_string(),
+                            ]
+                        })
+                    })
+                    .collect(),
+            },
+            Section {
+                title: "Bots timeline",
+                headers: vec!["Time", "Model", "Bots by state"],
+                rows: self
+                    .bots_timeline
+                    .iter()
+                    .map(|point| {
+                        vec![
+                            format_ts(point.timestamp),
+                            point.model.clone(),
+                            point
+                                .states
+                                .iter()
+                                .map(|(state, count)| format!("{state}: {count}"))
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        ]
+                    })
+                    .collect(),
+            },
+            Section {
+                title: "Agents",
+                headers: vec!["Agent", "First update", "Last update", "Running"],
+                rows: self
+                    .agents
+                    .iter()
+                    .map(|agent| {
+                        vec![
+                            agent.agent_id.to_string(),
+                            format_ts(agent.first_update),
+                            format_ts(agent.last_update),
+                            agent.running.to_string(),
+                        ]
+                    })
+                    .collect(),
+            },
+        ]
+    }
+}
+
+fn format_ts(epoch_millis: u64) -> String {
+    OffsetDateTime::from(UNIX_EPOCH + Duration::from_millis(epoch_millis))
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| epoch_millis.to_string())
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,352 @@
+use std::time::{Duration, SystemTime};
+
+use actix::{
+    Actor, ActorFutureExt, AsyncContext, Context, Handler, MessageResult, Recipient,
+    ResponseActFuture, SpawnHandle, WrapFuture,
+};
+use futures::future::join_all;
+
+use crate::controller::message::FailSimulation;
+use crate::controller::metrics::message::QueryMetrics;
+use crate::controller::model::threshold::Threshold;
+use crate::controller::slo::message::{
+    FetchVerdict, SloVerdict, UnwatchThresholds, WatchThresholds,
+};
+
+/// Configuration of the thresholds watcher.
+#[derive(Clone, Debug)]
+pub struct SloWatcherConfig {
+    check_interval: Duration,
+    grace_period: Duration,
+}
+
+impl Default for SloWatcherConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: Duration::from_secs(5),
+            grace_period: Duration::from_secs(5),
+        }
+    }
+}
+
+impl SloWatcherConfig {
+    /// Interval between two checks of the critical thresholds (default 5s)
+    pub fn check_interval(self, check_interval: Duration) -> Self {
+        Self {
+            check_interval,
+            ..self
+        }
+    }
+
+    /// Time to wait for the last agents metrics before evaluating the final verdict (default 5s)
+    pub fn grace_period(self, grace_period: Duration) -> Self {
+        Self {
+            grace_period,
+            ..self
+        }
+    }
+}
+
+struct WatchedRun {
+    thresholds: Vec<Threshold>,
+    start_ts: SystemTime,
+    check_handle: SpawnHandle,
+    aborted_by: Option<String>,
+}
+
+/// Actor evaluating the thresholds of the launched simulation.
+///
+/// Critical thresholds are periodically checked over their trailing window and the simulation
+/// is stopped as failed, see [`FailSimulation`], as soon as one of them is breached. When the run terminates all the thresholds
+/// are evaluated over the whole run and the verdict is logged and kept for [`FetchVerdict`].
+pub struct SloWatcherActor {
+    config: SloWatcherConfig,
+    metrics: Recipient<QueryMetrics>,
+    controller: Recipient<FailSimulation>,
+    run: Option<WatchedRun>,
+    last_verdict: Option<SloVerdict>,
+}
+
+impl SloWatcherActor {
+    pub fn new(
+        config: SloWatcherConfig,
+        metrics: Recipient<QueryMetrics>,
+        controller: Recipient<FailSimulation>,
+    ) -> Self {
+        Self {
+            config,
+            metrics,
+            controller,
+            run: None,
+            last_verdict: None,
+        }
+    }
+
+    fn check_critical_thresholds(&mut self, ctx: &mut Context<Self>) {
+        let Some(run) = &self.run else {
+            return;
+        };
+        if run.aborted_by.is_some() {
+            return;
+        }
+        let now = SystemTime::now();
+        let checks = run
+            .thresholds
+            .iter()
+            .filter_map(|threshold| {
+                let window = threshold.critical_window()?;
+                let since = now.checked_sub(window)?;
+                (since >= run.start_ts).then(|| (threshold.clone(), since))
+            })
+            .map(|(threshold, since)| {
+                let query_fut = self.metrics.send(QueryMetrics::default().since(since));
+                async move { (threshold, query_fut.await) }
+            })
+            .collect::<Vec<_>>();
+
+        if checks.is_empty() {
+            return;
+        }
+
+        ctx.spawn(join_all(checks).into_actor(self).map(|results, act, _ctx| {
+            let breached = results.into_iter().find_map(|(threshold, res)| match res {
+                Ok(summary) => {
+                    let verdict = threshold.evaluate(&summary);
+                    (verdict.value.is_some() && !verdict.passed).then(|| threshold.to_string())
+                }
+                Err(err) => {
+                    log::error!("Error querying metrics for threshold {threshold} - {err}");
+                    None
+                }
+            });
+            if let (Some(threshold), Some(run)) = (breached, act.run.as_mut()) {
+                if run.aborted_by.is_none() {
+                    log::warn!("Critical threshold {threshold} breached, stopping simulation");
+                    run.aborted_by = Some(threshold.clone());
+                    act.controller.do_send(FailSimulation { threshold });
+                }
+            }
+        }));
+    }
+}
+
+impl Actor for SloWatcherActor {
+    type Context = Context<Self>;
+}
+
+impl Handler<WatchThresholds> for SloWatcherActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: WatchThresholds, ctx: &mut Self::Context) -> Self::Result {
+        if let Some(run) = self.run.take() {
+            ctx.cancel_future(run.check_handle);
+        }
+        let check_handle = ctx.run_interval(self.config.check_interval, |act, ctx| {
+            act.check_critical_thresholds(ctx)
+        });
+        self.run = Some(WatchedRun {
+            thresholds: msg.thresholds,
+            start_ts: msg.start_ts,
+            check_handle,
+            aborted_by: None,
+        });
+    }
+}
+
+impl Handler<UnwatchThresholds> for SloWatcherActor {
+    type Result = ResponseActFuture<Self, Option<SloVerdict>>;
+
+    fn handle(&mut self, msg: UnwatchThresholds, ctx: &mut Self::Context) -> Self::Result {
+        let Some(run) = self.run.take() else {
+            return Box::pin(actix::fut::ready(None));
+        };
+        ctx.cancel_future(run.check_handle);
+
+        let grace_period = self.config.grace_period;
+        let metrics = self.metrics.clone();
+        let since = run.start_ts;
+        let until = msg.end_ts + grace_period;
+        let query_fut = async move {
+            actix::clock::sleep(grace_period).await;
+            metrics
+                .send(QueryMetrics::default().since(since).until(until))
+                .await
+        };
+        Box::pin(query_fut.into_actor(self).map(move |res, act, _ctx| {
+            let summary = match res {
+                Ok(summary) => summary,
+                Err(err) => {
+                    log::error!("Error querying metrics for thresholds verdict - {err}");
+                    return None;
+                }
+            };
+            let thresholds = run
+                .thresholds
+                .iter()
+                .map(|threshold| {
+                    let verdict = threshold.evaluate(&summary);
+                    if verdict.passed {
+                        log::info!("Threshold {threshold} passed ({:?})", verdict.value);
+                    } else {
+                        log::warn!("Threshold {threshold} failed ({:?})", verdict.value);
+                    }
+                    verdict
+                })
+                .collect::<Vec<_>>();
+            let verdict = SloVerdict {
+                passed: run.aborted_by.is_none() && thresholds.iter().all(|t| t.passed),
+                thresholds,
+                aborted_by: run.aborted_by,
+            };
+            act.last_verdict = Some(verdict.clone());
+            Some(verdict)
+        }))
+    }
+}
+
+impl Handler<FetchVerdict> for SloWatcherActor {
+    type Result = MessageResult<FetchVerdict>;
+
+    fn handle(&mut self, _msg: FetchVerdict, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.last_verdict.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::communication::message::MultiAgentUpdateMessage;
+    use crate::communication::protobuf::grpc::fixture;
+    use crate::controller::metrics::actor::MetricsStoreActor;
+    use actix::Addr;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default, Clone)]
+    struct FailureRecorder(Arc<Mutex<Vec<String>>>);
+    impl Actor for FailureRecorder {
+        type Context = Context<Self>;
+    }
+    impl Handler<FailSimulation> for FailureRecorder {
+        type Result = ();
+        fn handle(&mut self, msg: FailSimulation, _ctx: &mut Self::Context) {
+            self.0.lock().unwrap().push(msg.threshold);
+        }
+    }
+
+    async fn metrics_with_errors(ok: u64, errors: u64) -> Addr<MetricsStoreActor> {
+        let metrics = MetricsStoreActor::default().start();
+        metrics
+            .send(update_with_errors(1, ok, errors))
+            .await
+            .unwrap();
+        metrics
+    }
+
+    fn update_with_errors(agent_id: u32, ok: u64, errors: u64) -> MultiAgentUpdateMessage {
+        let now = SystemTime::now();
+        let histograms = vec![
+            fixture::histogram(200, vec![ok], ok),
+            fixture::histogram(500, vec![errors], errors),
+        ];
+        MultiAgentUpdateMessage(vec![fixture::agent_update(
+            agent_id,
+            now,
+            vec![fixture::model_stats("Demo", now, "get", histograms)],
+        )])
+    }
+
+    #[actix::test]
+    async fn critical_threshold_fails_simulation() {
+        let metrics = metrics_with_errors(50, 50).await;
+        let failures = FailureRecorder::default();
+        let watcher = SloWatcherActor::new(
+            SloWatcherConfig::default()
+                .check_interval(Duration::from_millis(20))
+                .grace_period(Duration::ZERO),
+            metrics.recipient(),
+            failures.clone().start().recipient(),
+        )
+        .start();
+
+        watcher
+            .send(WatchThresholds::new(
+                vec![Threshold::parse("error_rate < 1%")
+                    .unwrap()
+                    .critical(Duration::from_secs(1))],
+                SystemTime::now() - Duration::from_secs(10),
+            ))
+            .await
+            .unwrap();
+        actix::clock::sleep(Duration::from_millis(200)).await;
+        assert_eq!(
+            *failures.0.lock().unwrap(),
+            vec![String::from("error_rate < 1%")]
+        );
+
+        let verdict = watcher
+            .send(UnwatchThresholds::at(SystemTime::now()))
+            .await
+            .unwrap()
+            .expect("missing verdict");
+        assert!(!verdict.passed);
+        assert_eq!(verdict.aborted_by.as_deref(), Some("error_rate < 1%"));
+    }
+
+    #[actix::test]
+    async fn verdict_of_passed_run() {
+        let metrics = metrics_with_errors(100, 0).await;
+        let watcher = SloWatcherActor::new(
+            SloWatcherConfig::default().grace_period(Duration::ZERO),
+            metrics.recipient(),
+            FailureRecorder::default().start().recipient(),
+        )
+        .start();
+
+        watcher
+            .send(WatchThresholds::new(
+                vec![
+                    Threshold::parse("error_rate < 1%").unwrap(),
+                    Threshold::parse("p95 < 300ms").unwrap().model("Demo"),
+                ],
+                SystemTime::now() - Duration::from_secs(10),
+            ))
+            .await
+            .unwrap();
+        watcher
+            .send(UnwatchThresholds::at(SystemTime::now()))
+            .await
+            .unwrap();
+
+        let verdict = watcher
+            .send(FetchVerdict)
+            .await
+            .unwrap()
+            .expect("missing verdict");
+        assert!(verdict.passed);
+        assert_eq!(verdict.thresholds.len(), 2);
+    }
+
+    #[actix::test]
+    async fn verdict_waits_for_late_updates() {
+        let metrics = metrics_with_errors(100, 0).await;
+        let watcher = SloWatcherActor::new(
+            SloWatcherConfig::default().grace_period(Duration::from_millis(200)),
+            metrics.clone().recipient(),
+            FailureRecorder::default().start().recipient(),
+        )
+        .start();
+
+        watcher
+            .send(WatchThresholds::new(
+                vec![Threshold::parse("error_rate < 1%").unwrap()],
+                SystemTime::now() - Duration::from_secs(10),
+            ))
+            .await
+            .unwrap();
+        let verdict_fut = watcher.send(UnwatchThresholds::at(SystemTime::now()));
+        metrics.send(update_with_errors(2, 0, 50)).await.unwrap();
+
+        let verdict = verdict_fut.await.unwrap().expect("missing verdict");
+        assert!(!verdict.passed);
+    }
+}
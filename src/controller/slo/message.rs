@@ -0,0 +1,54 @@
+use std::time::SystemTime;
+
+use actix::MessageResponse;
+use serde::Serialize;
+
+use crate::controller::model::threshold::{Threshold, ThresholdVerdict};
+
+/// Verdict of the thresholds of a simulation run.
+#[derive(MessageResponse, Serialize, Clone, Debug)]
+pub struct SloVerdict {
+    /// Whether all the thresholds passed.
+    pub passed: bool,
+    /// Verdict of each threshold over the whole run.
+    pub thresholds: Vec<ThresholdVerdict>,
+    /// Critical threshold that stopped the run, if any.
+    pub aborted_by: Option<String>,
+}
+
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+/// Start watching the thresholds of a launched simulation
+pub struct WatchThresholds {
+    pub(crate) thresholds: Vec<Threshold>,
+    pub(crate) start_ts: SystemTime,
+}
+
+impl WatchThresholds {
+    /// Watch `thresholds` of a simulation started at `start_ts`
+    pub fn new(thresholds: Vec<Threshold>, start_ts: SystemTime) -> Self {
+        Self {
+            thresholds,
+            start_ts,
+        }
+    }
+}
+
+#[derive(actix::Message)]
+#[rtype(result = "Option<SloVerdict>")]
+/// Stop watching the current run and evaluate its final verdict
+pub struct UnwatchThresholds {
+    pub(crate) end_ts: SystemTime,
+}
+
+impl UnwatchThresholds {
+    /// Terminate the run at `end_ts`
+    pub fn at(end_ts: SystemTime) -> Self {
+        Self { end_ts }
+    }
+}
+
+#[derive(actix::Message)]
+#[rtype(result = "Option<SloVerdict>")]
+/// Fetch the verdict of the last terminated run
+pub struct FetchVerdict;
@@ -0,0 +1,262 @@
+use std::collections::BTreeSet;
+
+use actix::{Actor, Addr};
+use serde::Serialize;
+
+use crate::agent::metrics::manager::actor::MetricsManagerActor;
+use crate::controller::model::simulation::{BotDef, SimulationDef};
+use crate::simulation::bot::registry::BotRegistry;
+
+/// Problem found validating a simulation definition.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ValidationIssue {
+    /// The script does not compile.
+    InvalidScript {
+        /// Compilation error.
+        message: String,
+    },
+    /// A bot definition refers to a model the script does not define.
+    UnknownModel {
+        /// Phase of the bot definition, `None` for the simulation bots.
+        phase: Option<String>,
+        /// Name of the model.
+        model: String,
+    },
+    /// A shape expression cannot be parsed.
+    BadShape {
+        /// Phase of the bot definition, `None` for the simulation bots.
+        phase: Option<String>,
+        /// Name of the model.
+        model: String,
+        /// Parsing error.
+        message: String,
+    },
+}
+
+/// Outcome of a simulation dry-run.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    /// Bot models discovered in the script, sorted by name.
+    pub models: Vec<String>,
+    /// Problems found, the simulation is valid when empty.
+    pub issues: Vec<ValidationIssue>,
+    /// Problems not preventing the simulation from being loaded, e.g. a script that does not
+    /// compile with the default rune modules only.
+    pub warnings: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Whether the simulation can be sent to the agents
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Function building the rune context the scripts are compiled with
+pub type ValidationContextBuilder = Box<dyn Fn() -> rune::Context + Send>;
+
+/// Dry-run of a simulation definition on the controller.
+///
+/// The script is compiled discovering the bot models as agents do, then every bot definition is
+/// checked to refer to a discovered model and to have a parsable shape. Scripts relying on custom
+/// rune modules need a context builder installing them, see [`SimulationValidator::new`].
+///
+/// The default validator compiles the scripts with the default rune modules only: as scripts may
+/// rely on modules installed by the agents (e.g. `env` or `storage`), compilation errors are only
+/// reported as warnings and the bot models are not checked.
+pub struct SimulationValidator {
+    context_builder: ValidationContextBuilder,
+    strict_script: bool,
+    metrics_addr: Option<Addr<MetricsManagerActor>>,
+}
+
+impl Default for SimulationValidator {
+    fn default() -> Self {
+        Self {
+            context_builder: Box::new(|| {
+                rune::Context::with_default_modules().expect("error loading default rune modules")
+            }),
+            strict_script: false,
+            metrics_addr: None,
+        }
+    }
+}
+
+impl SimulationValidator {
+    /// Create a validator compiling the scripts with the context returned by the given builder,
+    /// scripts that do not compile are rejected
+    pub fn new(context_builder: impl Fn() -> rune::Context + Send + 'static) -> Self {
+        Self {
+            context_builder: Box::new(context_builder),
+            strict_script: true,
+            metrics_addr: None,
+        }
+    }
+
+    /// Validate the given simulation definition
+    pub fn validate(&mut self, simulation: &SimulationDef) -> ValidationReport {
+        let mut issues = Vec::new();
+        let mut warnings = Vec::new();
+        let models = match self.discover_models(simulation.script_ref()) {
+            Ok(models) => Some(models),
+            Err(message) if self.strict_script => {
+                issues.push(ValidationIssue::InvalidScript { message });
+                None
+            }
+            Err(message) => {
+                warnings.push(ValidationIssue::InvalidScript { message });
+                None
+            }
+        };
+
+        // agents ignore the simulation bots when phases are defined
+        let bot_defs: Vec<(Option<&str>, &BotDef)> = if simulation.phases_ref().is_empty() {
+            simulation
+                .bots_ref()
+                .iter()
+                .map(|bot| (None, bot))
+                .collect()
+        } else {
+            simulation
+                .phases_ref()
+                .iter()
+                .flat_map(|phase| {
+                    phase
+                        .bots_ref()
+                        .iter()
+                        .map(move |bot| (Some(phase.name_ref()), bot))
+                })
+                .collect()
+        };
+
+        for (phase, bot) in bot_defs {
+            let phase = phase.map(String::from);
+            if let Some(models) = &models {
+                if !models.contains(bot.model_ref()) {
+                    issues.push(ValidationIssue::UnknownModel {
+                        phase: phase.clone(),
+                        model: bot.model_ref().to_string(),
+                    });
+                }
+            }
+            if let Err(err) = bot.model_shape().parse() {
+                issues.push(ValidationIssue::BadShape {
+                    phase,
+                    model: bot.model_ref().to_string(),
+                    message: err.to_string(),
+                });
+            }
+        }
+
+        ValidationReport {
+            models: models.map(Vec::from_iter).unwrap_or_default(),
+            issues,
+            warnings,
+        }
+    }
+
+    fn discover_models(&mut self, script: &str) -> Result<BTreeSet<String>, String> {
+        let metrics_addr = self
+            .metrics_addr
+            .get_or_insert_with(MetricsManagerActor::start_default)
+            .clone();
+        let mut registry = BotRegistry::new((self.context_builder)(), metrics_addr)
+            .map_err(|err| err.to_string())?;
+        registry
+            .load_script(script)
+            .map_err(|err| err.to_string())?;
+        Ok(registry.model_names().into_iter().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::controller::model::simulation::PhaseDef;
+    use std::time::Duration;
+
+    const DEMO_SCRIPT: &str = r#"
+        struct Demo { id }
+        impl Demo {
+            pub fn new(params) { Self { id: params.bot_id } }
+            pub fn register_bot(bot) {}
+        }
+    "#;
+
+    #[actix::test]
+    async fn valid_simulation() {
+        let report = SimulationValidator::default().validate(
+            &SimulationDef::default()
+                .script(DEMO_SCRIPT.to_string())
+                .bots(vec![BotDef::default().model("Demo").shape("10 * step(t)")]),
+        );
+        assert!(report.is_valid(), "unexpected issues {:?}", report.issues);
+        assert_eq!(report.models, vec![String::from("Demo")]);
+    }
+
+    #[actix::test]
+    async fn unknown_models_and_bad_shapes_are_reported() {
+        let report = SimulationValidator::default().validate(
+            &SimulationDef::default()
+                .script(DEMO_SCRIPT.to_string())
+                .phases(vec![PhaseDef::default()
+                    .name("steady")
+                    .duration(Duration::from_secs(60))
+                    .bots(vec![
+                        BotDef::default().model("Demo").shape("10 *"),
+                        BotDef::default().model("Demoo").shape("10"),
+                    ])]),
+        );
+        assert_eq!(report.models, vec![String::from("Demo")]);
+        assert_eq!(report.issues.len(), 2);
+        assert!(matches!(
+            &report.issues[0],
+            ValidationIssue::BadShape { phase: Some(phase), model, .. } if phase == "steady" && model == "Demo"
+        ));
+        assert_eq!(
+            report.issues[1],
+            ValidationIssue::UnknownModel {
+                phase: Some(String::from("steady")),
+                model: String::from("Demoo"),
+            }
+        );
+    }
+
+    #[actix::test]
+    async fn invalid_script_is_reported() {
+        let report = SimulationValidator::new(|| {
+            rune::Context::with_default_modules().expect("error loading default rune modules")
+        })
+        .validate(
+            &SimulationDef::default()
+                .script(String::from("struct Demo {"))
+                .bots(vec![BotDef::default().model("Demo").shape("10")]),
+        );
+        assert!(report.models.is_empty());
+        assert!(matches!(
+            report.issues.as_slice(),
+            [ValidationIssue::InvalidScript { .. }]
+        ));
+    }
+
+    #[actix::test]
+    async fn compile_errors_are_advisory_without_context_builder() {
+        let report = SimulationValidator::default().validate(
+            &SimulationDef::default()
+                .script(String::from(
+                    "use storage::Storage;\nstruct Demo {}\nimpl Demo { pub fn new(params) { Demo {} } }",
+                ))
+                .bots(vec![BotDef::default().model("Demo").shape("10 *")]),
+        );
+        assert!(report.models.is_empty());
+        assert!(matches!(
+            report.warnings.as_slice(),
+            [ValidationIssue::InvalidScript { .. }]
+        ));
+        assert!(matches!(
+            report.issues.as_slice(),
+            [ValidationIssue::BadShape { .. }]
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::simulation::actor::simulation::BotStateChange;
+use crate::simulation::actor::simulation::{BotStateChange, BotTicksSkipped};
 use crate::simulation::bot::scripted::ScriptedBot;
 use crate::simulation::rune::types::value::OwnedValue;
 use crate::utils::actix::weak_context::WeakContext;
@@ -8,7 +8,7 @@ use actix::{
 };
 use rand::Rng;
 use rune::Hash;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 /// Lifecycle state of a bot instance.
@@ -48,18 +48,30 @@ impl From<BotState> for u32 {
 pub struct BotActor {
     bot_id: u64,
     state_change_recipient: Recipient<BotStateChange>,
+    ticks_skipped_recipient: Recipient<BotTicksSkipped>,
     bot: Option<ScriptedBot>,
+    paced: bool,
 }
 
 impl BotActor {
     pub fn new<A>(bot_id: u64, simulation_addr: Addr<A>, bot: ScriptedBot) -> Self
     where
-        A: Actor<Context = Context<A>> + Handler<BotStateChange>,
+        A: Actor<Context = Context<A>> + Handler<BotStateChange> + Handler<BotTicksSkipped>,
     {
         Self {
             bot_id,
-            state_change_recipient: simulation_addr.recipient(),
+            state_change_recipient: simulation_addr.clone().recipient(),
+            ticks_skipped_recipient: simulation_addr.recipient(),
             bot: Some(bot),
+            paced: true,
+        }
+    }
+
+    /// Run actions only when asked by the simulation, instead of every bot interval
+    pub(crate) fn driven(self) -> Self {
+        Self {
+            paced: false,
+            ..self
         }
     }
 }
@@ -69,15 +81,40 @@ impl Actor for BotActor {
 
     fn started(&mut self, ctx: &mut Self::Context) {
         log::debug!("Bot actor started");
+        if !self.paced {
+            return;
+        }
         let interval = self.bot.as_ref().expect("bot not defined").get_interval();
         let random_delay =
             Duration::from_millis(rand::rng().random_range(0..interval.as_millis() as u64));
+        let bot_id = self.bot_id;
+        let ticks_skipped_recipient = self.ticks_skipped_recipient.clone();
         ctx.run_later(random_delay, move |_a, ctx| {
-            ctx.run_interval_weak(interval, |addr| async move {
-                match addr.send(DoAction).await {
-                    Ok(Ok(())) => {}
-                    Ok(Err(err)) => log::error!("Error executing DoAction - {err}"),
-                    Err(err) => log::error!("Error sending DoAction - {err}"),
+            ctx.run_interval_weak(interval, move |addr| {
+                let ticks_skipped_recipient = ticks_skipped_recipient.clone();
+                async move {
+                    let started_at = Instant::now();
+                    let mut skipped = match addr.send(DoAction).await {
+                        Ok(Ok(())) => 0,
+                        Ok(Err(ActionExecutionError::OccupiedBot)) => 1,
+                        Ok(Err(err)) => {
+                            log::error!("Error executing DoAction - {err}");
+                            0
+                        }
+                        Err(err) => {
+                            log::error!("Error sending DoAction - {err}");
+                            0
+                        }
+                    };
+                    skipped += skipped_ticks(started_at.elapsed(), interval);
+                    if skipped > 0 {
+                        ticks_skipped_recipient
+                            .try_send(BotTicksSkipped {
+                                bot_id,
+                                count: skipped,
+                            })
+                            .unwrap_or_else(|e| log::error!("Error sending skipped ticks - {e}"));
+                    }
                 }
             });
         });
@@ -94,6 +131,15 @@ impl Actor for BotActor {
     }
 }
 
+/// Ticks of the bot interval elapsed while an action was running, the next action starts one
+/// interval after the end of the previous one so these ticks are never executed
+fn skipped_ticks(elapsed: Duration, interval: Duration) -> u64 {
+    elapsed
+        .as_nanos()
+        .checked_div(interval.as_nanos())
+        .map_or(0, |ticks| u64::try_from(ticks).unwrap_or(u64::MAX))
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 pub(crate) struct StopBot;
@@ -120,9 +166,21 @@ impl Handler<StopBot> for BotActor {
     }
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub(crate) struct KillBot;
+
+impl Handler<KillBot> for BotActor {
+    type Result = ();
+
+    fn handle(&mut self, _msg: KillBot, ctx: &mut Self::Context) -> Self::Result {
+        ctx.stop();
+    }
+}
+
 #[derive(Message)]
 #[rtype(result = "Result<(), ActionExecutionError>")]
-struct DoAction;
+pub(crate) struct DoAction;
 
 /// Error during bot action execution.
 #[derive(Error, Debug)]
@@ -233,3 +291,17 @@ impl Handler<ExecuteHandler> for BotActor {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ticks_elapsed_during_an_action_are_skipped() {
+        let interval = Duration::from_millis(100);
+        assert_eq!(skipped_ticks(Duration::from_millis(40), interval), 0);
+        assert_eq!(skipped_ticks(Duration::from_millis(100), interval), 1);
+        assert_eq!(skipped_ticks(Duration::from_millis(350), interval), 3);
+        assert_eq!(skipped_ticks(Duration::from_secs(1), Duration::ZERO), 0);
+    }
+}
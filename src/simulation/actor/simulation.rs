@@ -1,10 +1,12 @@
-use std::cmp::{min, Ordering};
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
 use std::time::{Duration, SystemTime};
 
 use crate::agent::builder::SimulationParams;
+use crate::agent::metrics::manager::message::{ResetSimulationMetrics, UpdateExpectedIntervals};
 use actix::{
-    Actor, AsyncContext, Context, Handler, Message, MessageResponse, ResponseFuture, WrapFuture,
+    Actor, ActorFutureExt, AsyncContext, Context, Handler, Message, MessageResponse, MessageResult,
+    Recipient, ResponseFuture, WrapFuture,
 };
 use futures::FutureExt;
 
@@ -12,26 +14,92 @@ use crate::simulation::actor::bot::{ActionExecutionError, BotState, ExecuteHandl
 use crate::simulation::bot::registry::BotRegistry;
 use crate::simulation::bot_model::BotModel;
 use crate::simulation::error::SimulationError;
+use crate::simulation::executor::ExecutionMode;
 use crate::simulation::rune::types::value::OwnedValue;
-use crate::simulation::shape::parse_shape_fun;
+use crate::simulation::shape::{time_of_day, ModelShape, ShapeFn};
 use crate::utils::actix::synchro_context::WeakContext;
 
+/// Phase of the loaded simulation with its parsed shapes
+struct LoadedPhase {
+    name: String,
+    /// phase duration in seconds, infinite for simulations without phases
+    duration: f64,
+    model_shapes: HashMap<String, ShapeFn>,
+    model_executions: HashMap<String, ExecutionMode>,
+}
+
+/// Interval between two evaluations of the shapes, bots are spawned and stopped on each tick
+pub(crate) const TICK_INTERVAL: Duration = Duration::from_millis(1500);
+
+/// Contiguous slice `[offset, offset + share)` of the global load assigned to the agent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct LoadShare {
+    offset: f64,
+    share: f64,
+}
+
+impl LoadShare {
+    /// Slice of the agent given the weights of all the agents, taken in ordinal order
+    pub(crate) fn from_weights(
+        agent_id: u32,
+        ordinals: &HashMap<u32, u32>,
+        weights: &HashMap<u32, f64>,
+    ) -> Option<Self> {
+        let ordinal = ordinals.get(&agent_id)?;
+        let weight = weights.get(&agent_id)?;
+        let total: f64 = weights.values().sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let preceding: f64 = weights
+            .iter()
+            .filter(|(id, _)| ordinals.get(id).is_some_and(|other| other < ordinal))
+            .map(|(_, weight)| weight)
+            .sum();
+        Some(Self {
+            offset: preceding / total,
+            share: weight / total,
+        })
+    }
+
+    /// Bots assigned to the agent, the counts of all the agents sum up to the global one
+    pub(crate) fn count(&self, global_count: f64) -> usize {
+        // tolerance for the rounding errors accumulated summing the shares
+        let bound = |fraction: f64| (global_count * fraction + 1e-9).floor();
+        (bound(self.offset + self.share) - bound(self.offset)).max(0.0) as usize
+    }
+
+    /// Iterations per second assigned to the agent, the rates of all the agents sum up to the
+    /// global one
+    pub(crate) fn rate(&self, global_rate: f64) -> f64 {
+        global_rate * self.share
+    }
+}
+
 /// Actor representing a hailstorm simulation
 pub struct SimulationActor {
     agent_id: u32,
     simulation_params: SimulationParams,
+    simulation_id: String,
     start_ts: Option<SystemTime>,
+    end_ts: Option<SystemTime>,
     bot_registry: BotRegistry,
     agents_count: u32,
-    model_shapes: HashMap<String, Box<dyn Fn(f64) -> f64>>,
+    ordinal: Option<u32>,
+    load_share: Option<LoadShare>,
+    phases: Vec<LoadedPhase>,
+    phase_idx: usize,
+    phase_start_ts: Option<SystemTime>,
     bots: HashMap<String, BotModel>,
+    expected_intervals_recipient: Option<Recipient<UpdateExpectedIntervals>>,
+    metrics_reset_recipient: Option<Recipient<ResetSimulationMetrics>>,
 }
 
 impl Actor for SimulationActor {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        ctx.run_interval_synchro(Duration::from_millis(1500), |act, ctx| act.tick(ctx));
+        ctx.run_interval_synchro(TICK_INTERVAL, |act, ctx| act.tick(ctx));
     }
 }
 
@@ -44,55 +112,197 @@ impl SimulationActor {
         Self {
             agent_id,
             simulation_params,
+            simulation_id: String::new(),
             start_ts: None,
+            end_ts: None,
             bot_registry,
             agents_count: 1,
-            model_shapes: Default::default(),
+            ordinal: None,
+            load_share: None,
+            phases: Default::default(),
+            phase_idx: 0,
+            phase_start_ts: None,
             bots: Default::default(),
+            expected_intervals_recipient: None,
+            metrics_reset_recipient: None,
+        }
+    }
+
+    /// Notify the interval expected between two actions of each model when a simulation is loaded
+    pub(crate) fn expected_intervals(self, recipient: Recipient<UpdateExpectedIntervals>) -> Self {
+        Self {
+            expected_intervals_recipient: Some(recipient),
+            ..self
+        }
+    }
+
+    /// Notify the id of each loaded simulation, the metrics of the previous one are discarded
+    pub(crate) fn metrics_reset(self, recipient: Recipient<ResetSimulationMetrics>) -> Self {
+        Self {
+            metrics_reset_recipient: Some(recipient),
+            ..self
+        }
+    }
+
+    /// Interval between two actions of the paced models, the arrival-rate ones are excluded as
+    /// their iterations are scheduled regardless of the response times
+    fn paced_intervals(&self) -> HashMap<String, Duration> {
+        let open_models = self
+            .phases
+            .iter()
+            .flat_map(|phase| phase.model_executions.iter())
+            .filter(|(_model, execution)| matches!(execution, ExecutionMode::ArrivalRate { .. }))
+            .map(|(model, _execution)| model)
+            .collect::<BTreeSet<_>>();
+        self.bot_registry
+            .model_names()
+            .into_iter()
+            .filter(|model| !open_models.contains(model))
+            .filter_map(|model| {
+                self.bot_registry
+                    .build_factory(model)
+                    .map(|factory| (model.clone(), factory.behaviour.get_interval()))
+            })
+            .collect()
+    }
+
+    fn load_phase(phase: PhaseShapes) -> Result<LoadedPhase, SimulationError> {
+        let mut model_shapes: HashMap<String, ShapeFn> = HashMap::new();
+        for (model, shape) in phase.model_shapes {
+            match shape.parse() {
+                Ok(shape_fn) => model_shapes.insert(model, shape_fn),
+                Err(err) => {
+                    return Err(SimulationError::BadModelShape {
+                        model,
+                        message: err.to_string(),
+                    })
+                }
+            };
         }
+
+        Ok(LoadedPhase {
+            name: phase.name,
+            duration: phase
+                .duration
+                .map(|duration| duration.as_secs_f64())
+                .unwrap_or(f64::INFINITY),
+            model_shapes,
+            model_executions: phase.model_executions,
+        })
     }
 
-    fn register_model(&mut self, model: String, shape: String) -> Result<(), SimulationError> {
-        let shape_fun = parse_shape_fun(shape)?;
+    fn is_finished(&self, now: SystemTime) -> bool {
+        self.end_ts.is_some_and(|end_ts| end_ts <= now)
+    }
 
-        self.model_shapes.insert(model, Box::new(shape_fun));
+    fn is_running(&self, now: SystemTime) -> bool {
+        self.start_ts.is_some_and(|start_ts| start_ts < now) && !self.is_finished(now)
+    }
 
-        Ok(())
+    /// Run an arrival-rate iteration of the model, scheduled by a tick of the given simulation
+    fn start_iteration(
+        &mut self,
+        simulation_id: &str,
+        model_name: &str,
+        max_bots: usize,
+        ctx: &mut Context<Self>,
+    ) {
+        if simulation_id != self.simulation_id || !self.is_running(SystemTime::now()) {
+            return;
+        }
+        let addr = ctx.address();
+        let Some(model) = self.bots.get_mut(model_name) else {
+            return;
+        };
+        let Some((bot_id, action)) = model.start_iteration(addr, max_bots) else {
+            log::debug!("Dropped iteration of {model_name}, bots pool exhausted");
+            return;
+        };
+        let model_name = model_name.to_string();
+        ctx.spawn(action.into_actor(self).map(move |res, act, _ctx| {
+            if let Some(model) = act.bots.get_mut(&model_name) {
+                model.finish_iteration(bot_id);
+            }
+            match res {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => log::error!("Error executing DoAction - {err}"),
+                Err(err) => log::error!("Error sending DoAction - {err}"),
+            }
+        }));
     }
 
-    fn normalize_count(global_count: f64, agent_id: u32, agents_count: u32) -> usize {
-        let shift = (agent_id % agents_count) as f64 / agents_count as f64;
+    fn normalize_count(global_count: f64, agent_ordinal: u32, agents_count: u32) -> usize {
+        let shift = (agent_ordinal % agents_count) as f64 / agents_count as f64;
         ((global_count / agents_count as f64) + shift).floor() as usize
     }
 
     fn tick(&mut self, ctx: &mut Context<Self>) {
+        let now = SystemTime::now();
         let maybe_elapsed = self
             .start_ts
             .as_ref()
-            .filter(|start_ts| **start_ts < SystemTime::now())
+            .filter(|start_ts| **start_ts < now && !self.is_finished(now))
             .map(SystemTime::elapsed)
             .transpose()
             .ok()
             .flatten() // ignore errors
             .map(|dur| dur.as_secs_f64());
 
-        if let Some(elapsed) = maybe_elapsed {
-            for (model_name, shape) in self.model_shapes.iter() {
-                let shape_val = shape(elapsed);
-                let count = match (
-                    Self::normalize_count(shape_val, self.agent_id, self.agents_count),
-                    self.simulation_params.max_running,
-                ) {
-                    (normalized_count, Some(max_running)) => min(normalized_count, max_running),
-                    (normalized_count, None) => normalized_count,
-                };
+        if let (Some(elapsed), Some(phase)) = (maybe_elapsed, self.phases.get(self.phase_idx)) {
+            let phase_elapsed = self
+                .phase_start_ts
+                .and_then(|phase_start_ts| now.duration_since(phase_start_ts).ok())
+                .map(|dur| dur.as_secs_f64())
+                .unwrap_or(0.0);
+            let tod = time_of_day(now);
 
-                let model = if let Some(mu) = self.bots.get_mut(model_name) {
-                    mu
-                } else {
+            for model_name in phase.model_shapes.keys() {
+                if !self.bots.contains_key(model_name) {
                     log::warn!("No bot-model defined with name {model_name}");
-                    break;
+                }
+            }
+
+            // models without a shape in the current phase are drained
+            for (model_name, model) in self.bots.iter_mut() {
+                let shape_val = phase
+                    .model_shapes
+                    .get(model_name)
+                    .map(|shape| shape(phase_elapsed, elapsed, phase.duration, tod))
+                    .unwrap_or(0.0);
+
+                // the shape is the global rate of iterations per second
+                if let Some(ExecutionMode::ArrivalRate { max_bots }) =
+                    phase.model_executions.get(model_name)
+                {
+                    model.retain(|_id, bot| bot.is_connected());
+                    model.stop_mismatched_bots(false);
+                    let rate = match self.load_share {
+                        Some(load_share) => load_share.rate(shape_val),
+                        None => shape_val / self.agents_count as f64,
+                    };
+                    if rate <= 0.0 {
+                        model.stop_idle_bots();
+                    }
+                    let max_bots = self.simulation_params.cap_running(*max_bots);
+                    for offset in model.schedule_iterations(rate, TICK_INTERVAL) {
+                        let simulation_id = self.simulation_id.clone();
+                        let model_name = model_name.clone();
+                        ctx.run_later(offset, move |act, ctx| {
+                            act.start_iteration(&simulation_id, &model_name, max_bots, ctx)
+                        });
+                    }
+                    continue;
+                }
+                model.stop_mismatched_bots(true);
+                let normalized_count = match self.load_share {
+                    Some(load_share) => load_share.count(shape_val),
+                    None => Self::normalize_count(
+                        shape_val,
+                        self.ordinal.unwrap_or(self.agent_id),
+                        self.agents_count,
+                    ),
                 };
+                let count = self.simulation_params.cap_running(normalized_count);
 
                 let running_count = model.count_active();
 
@@ -110,11 +320,7 @@ impl SimulationActor {
                         // running number is as expected
                     }
                     Ordering::Greater => {
-                        let spawn_count =
-                            match (count - running_count, self.simulation_params.max_rate) {
-                                (running_diff, Some(max_rate)) => min(running_diff, max_rate),
-                                (running_diff, None) => running_diff,
-                            };
+                        let spawn_count = self.simulation_params.cap_spawn(count - running_count);
 
                         for _idx in 0..spawn_count {
                             model.spawn_bot(ctx.address());
@@ -173,59 +379,149 @@ impl Handler<BotStateChange> for SimulationActor {
     }
 }
 
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+/// Notify that a specific bot skipped some ticks of its interval while running an action
+pub struct BotTicksSkipped {
+    pub bot_id: u64,
+    pub count: u64,
+}
+
+impl Handler<BotTicksSkipped> for SimulationActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: BotTicksSkipped, _ctx: &mut Self::Context) -> Self::Result {
+        if let Some(model) = self
+            .bots
+            .values_mut()
+            .find(|model| model.contains_id(msg.bot_id))
+        {
+            model.add_skipped_ticks(msg.count);
+        }
+    }
+}
+
+/// Load shapes of a simulation phase.
+pub(crate) struct PhaseShapes {
+    pub name: String,
+    /// Phase duration, `None` for simulations without phases.
+    pub duration: Option<Duration>,
+    pub model_shapes: HashMap<String, ModelShape>,
+    /// Execution mode of the models, closed for the missing ones.
+    pub model_executions: HashMap<String, ExecutionMode>,
+}
+
 /// Commands that can be sent to the simulation actor to control its lifecycle.
 pub(crate) enum SimulationCommand {
-    /// Load a simulation with given model shapes and Rune script.
+    /// Load a simulation with given phases and Rune script.
     LoadSimulation {
-        model_shapes: HashMap<String, String>,
+        simulation_id: String,
+        phases: Vec<PhaseShapes>,
         script: String,
     },
-    /// Launch the loaded simulation at the specified start time.
+    /// Launch the loaded simulation at the specified start time, bots are drained at the end time.
     LaunchSimulation {
+        simulation_id: String,
+        start_ts: SystemTime,
+        end_ts: Option<SystemTime>,
+    },
+    /// Switch to the phase with the given index, started at the specified time.
+    EnterPhase {
+        index: usize,
         start_ts: SystemTime,
     },
     /// Update the total number of agents participating in the simulation.
     UpdateAgentsCount {
         count: u32,
     },
+    /// Update the ordinals assigned by the controller to the live agents, used to split the
+    /// load evenly. Until its ordinal is known the agent falls back to its id.
+    ///
+    /// When the controller knows the agents capacity, `weights` holds the share of the load of
+    /// each agent.
+    UpdateAgentOrdinals {
+        ordinals: HashMap<u32, u32>,
+        weights: HashMap<u32, f64>,
+    },
     /// Stop the simulation, optionally resetting all state.
+    ///
+    /// When `abort` is set running bots are torn down immediately and their `Stopping`
+    /// hook is skipped, otherwise they are drained gracefully on the next ticks.
     StopSimulation {
         reset: bool,
+        abort: bool,
     },
 }
 
 #[derive(Message, Default)]
-#[rtype(result = "()")]
-/// Control the simulation sending a list of command
+#[rtype(result = "Vec<SimulationError>")]
+/// Control the simulation sending a list of command, returns the errors of the rejected commands
 pub(crate) struct SimulationCommandLst {
     pub commands: Vec<SimulationCommand>,
 }
 
 impl Handler<SimulationCommandLst> for SimulationActor {
-    type Result = ();
+    type Result = MessageResult<SimulationCommandLst>;
 
     fn handle(&mut self, msg: SimulationCommandLst, _ctx: &mut Self::Context) -> Self::Result {
+        let mut errors = Vec::new();
         for cmd in msg.commands {
             match cmd {
                 SimulationCommand::LoadSimulation {
-                    model_shapes,
+                    simulation_id,
+                    phases,
                     script,
                 } => {
-                    let model_registration_out = model_shapes
-                        .into_iter()
-                        .map(|(model, shape)| self.register_model(model, shape))
-                        .collect::<Result<Vec<_>, _>>();
-
-                    if let Err(err) = model_registration_out {
-                        log::error!("Error registering simulation clients - {err}")
+                    if let Some(recipient) = &self.metrics_reset_recipient {
+                        let reset = ResetSimulationMetrics {
+                            simulation_id: simulation_id.clone(),
+                        };
+                        if let Err(err) = recipient.try_send(reset) {
+                            log::error!("Error sending metrics reset - {err}");
+                        }
                     }
-
+                    self.simulation_id = simulation_id;
                     let load_script_out = self.bot_registry.load_script(&script);
                     if let Err(err) = load_script_out {
                         log::error!("Error loading script - {err}");
+                        // the bots of the previous script must not run in the new simulation
+                        self.bot_registry.reset_script();
+                        errors.push(SimulationError::from(err));
+                    } else {
+                        let model_names = self.bot_registry.model_names();
+                        let unknown_models = phases
+                            .iter()
+                            .flat_map(|phase| phase.model_shapes.keys())
+                            .filter(|model| !model_names.contains(model))
+                            .collect::<BTreeSet<_>>();
+                        for model in unknown_models {
+                            log::error!("No bot-model defined with name {model}");
+                            errors.push(SimulationError::UnknownModel(model.clone()));
+                        }
+                    }
+
+                    let phases_out = phases
+                        .into_iter()
+                        .map(Self::load_phase)
+                        .collect::<Result<Vec<_>, _>>();
+
+                    match phases_out {
+                        Ok(phases) => self.phases = phases,
+                        Err(err) => {
+                            log::error!("Error registering simulation clients - {err}");
+                            errors.push(err);
+                        }
                     }
+                    self.phase_idx = 0;
+                    self.phase_start_ts = None;
 
-                    self.bots.drain();
+                    // bots of the previous simulation are no longer tracked, let them run their stopping hook
+                    for (_m, mut model) in self.bots.drain() {
+                        model
+                            .bots_mut()
+                            .filter(|bot| bot.state() != BotState::Stopping)
+                            .for_each(|bot| bot.stop_bot());
+                    }
                     for (idx, model) in self.bot_registry.model_names().into_iter().enumerate() {
                         match self.bot_registry.build_factory(model) {
                             Some(factory) => {
@@ -239,9 +535,43 @@ impl Handler<SimulationCommandLst> for SimulationActor {
                             }
                         }
                     }
+                    if let Some(recipient) = &self.expected_intervals_recipient {
+                        let update = UpdateExpectedIntervals {
+                            intervals: self.paced_intervals(),
+                        };
+                        if let Err(err) = recipient.try_send(update) {
+                            log::error!("Error sending expected intervals - {err}");
+                        }
+                    }
                 }
-                SimulationCommand::LaunchSimulation { start_ts } => {
+                SimulationCommand::LaunchSimulation {
+                    simulation_id,
+                    start_ts,
+                    end_ts,
+                } => {
+                    // the controller reloads agents reporting another simulation, bots of the
+                    // loaded one must not run meanwhile
+                    if !simulation_id.is_empty() && simulation_id != self.simulation_id {
+                        log::warn!(
+                            "Ignoring launch of simulation {simulation_id} while {} is loaded",
+                            self.simulation_id
+                        );
+                        continue;
+                    }
                     self.start_ts = Some(start_ts);
+                    self.end_ts = end_ts;
+                    self.phase_idx = 0;
+                    self.phase_start_ts = Some(start_ts);
+                }
+                SimulationCommand::EnterPhase { index, start_ts } => {
+                    match self.phases.get(index) {
+                        Some(phase) => {
+                            log::info!("Entering simulation phase {index} '{}'", phase.name);
+                            self.phase_idx = index;
+                            self.phase_start_ts = Some(start_ts);
+                        }
+                        None => log::warn!("Ignoring unknown simulation phase {index}"),
+                    }
                 }
                 SimulationCommand::UpdateAgentsCount { count } => {
                     if count > 0 {
@@ -251,15 +581,31 @@ impl Handler<SimulationCommandLst> for SimulationActor {
                         self.agents_count = 1;
                     }
                 }
-                SimulationCommand::StopSimulation { reset } => {
+                SimulationCommand::UpdateAgentOrdinals { ordinals, weights } => {
+                    self.ordinal = ordinals.get(&self.agent_id).copied();
+                    if self.ordinal.is_none() {
+                        log::warn!("No ordinal assigned to agent {}", self.agent_id);
+                    }
+                    self.load_share = LoadShare::from_weights(self.agent_id, &ordinals, &weights);
+                }
+                SimulationCommand::StopSimulation { reset, abort } => {
                     self.start_ts = None;
+                    self.end_ts = None;
+                    if abort {
+                        self.bots
+                            .values_mut()
+                            .flat_map(|model| model.bots_mut())
+                            .for_each(|bot| bot.kill_bot());
+                    }
                     if reset {
                         self.bot_registry.reset_script();
-                        self.model_shapes.clear();
+                        self.phases.clear();
+                        self.simulation_id.clear();
                     }
                 }
             }
         }
+        MessageResult(errors)
     }
 }
 
@@ -269,16 +615,21 @@ pub(crate) enum SimulationState {
     Ready,
     Waiting,
     Running,
+    Stopping,
+    Finished,
 }
 
 pub(crate) struct ClientStats {
     pub model: String,
     pub timestamp: SystemTime,
     pub count_by_state: HashMap<BotState, usize>,
+    pub dropped_iterations: u64,
+    pub skipped_ticks: u64,
 }
 
 #[derive(MessageResponse)]
 pub(crate) struct SimulationStats {
+    pub simulation_id: String,
     pub stats: Vec<ClientStats>,
     pub timestamp: SystemTime,
     pub state: SimulationState,
@@ -292,12 +643,17 @@ impl Handler<FetchSimulationStats> for SimulationActor {
     type Result = SimulationStats;
 
     fn handle(&mut self, _msg: FetchSimulationStats, _ctx: &mut Self::Context) -> Self::Result {
+        let draining = self.bots.values().any(|model| !model.is_empty());
+        let finished = self.is_finished(SystemTime::now());
         let state = match (
             self.start_ts.as_ref(),
             self.bot_registry.has_registered_models(),
         ) {
+            (None, _) if draining => SimulationState::Stopping,
             (_, false) => SimulationState::Idle,
             (None, true) => SimulationState::Ready,
+            (Some(_), true) if finished && draining => SimulationState::Stopping,
+            (Some(_), true) if finished => SimulationState::Finished,
             (Some(ts), true) if *ts < SystemTime::now() => SimulationState::Running,
             (Some(_), true) => SimulationState::Waiting,
         };
@@ -309,10 +665,13 @@ impl Handler<FetchSimulationStats> for SimulationActor {
                 timestamp: SystemTime::now(),
                 model: model.clone(),
                 count_by_state: usr.count_by_state(),
+                dropped_iterations: usr.dropped_iterations(),
+                skipped_ticks: usr.skipped_ticks(),
             })
             .collect();
 
         SimulationStats {
+            simulation_id: self.simulation_id.clone(),
             stats,
             timestamp: SystemTime::now(),
             state,
@@ -365,7 +724,86 @@ impl Handler<InvokeHandler> for SimulationActor {
 
 #[cfg(test)]
 mod test {
-    use crate::simulation::actor::simulation::SimulationActor;
+    use crate::agent::metrics::manager::actor::MetricsManagerActor;
+    use crate::simulation::actor::simulation::{
+        FetchSimulationStats, LoadShare, SimulationActor, SimulationCommand, SimulationCommandLst,
+        SimulationState,
+    };
+    use crate::simulation::bot::registry::BotRegistry;
+    use crate::simulation::error::SimulationError;
+    use actix::Actor;
+    use std::collections::HashMap;
+    use std::time::SystemTime;
+
+    const VALID_SCRIPT: &str = r#"
+            struct Demo {}
+            impl Demo {
+              pub fn register_bot(bot) {}
+              pub fn new(par) { Self {} }
+            }
+    "#;
+
+    fn load(simulation_id: &str, script: &str) -> SimulationCommandLst {
+        SimulationCommandLst {
+            commands: vec![SimulationCommand::LoadSimulation {
+                simulation_id: simulation_id.to_string(),
+                phases: vec![],
+                script: script.to_string(),
+            }],
+        }
+    }
+
+    #[actix::test]
+    async fn launch_of_another_simulation_is_ignored() {
+        let registry = BotRegistry::new(
+            rune::Context::with_default_modules().unwrap(),
+            MetricsManagerActor::start_default(),
+        )
+        .unwrap();
+        let simulation = SimulationActor::new(1, Default::default(), registry).start();
+        simulation.send(load("first", VALID_SCRIPT)).await.unwrap();
+
+        let launch = |simulation_id: &str| SimulationCommandLst {
+            commands: vec![SimulationCommand::LaunchSimulation {
+                simulation_id: simulation_id.to_string(),
+                start_ts: SystemTime::now(),
+                end_ts: None,
+            }],
+        };
+        simulation.send(launch("second")).await.unwrap();
+        let stats = simulation.send(FetchSimulationStats).await.unwrap();
+        assert!(matches!(stats.state, SimulationState::Ready));
+
+        simulation.send(launch("first")).await.unwrap();
+        let stats = simulation.send(FetchSimulationStats).await.unwrap();
+        assert!(matches!(stats.state, SimulationState::Running));
+    }
+
+    #[actix::test]
+    async fn invalid_script_discards_previous_bots() {
+        let registry = BotRegistry::new(
+            rune::Context::with_default_modules().unwrap(),
+            MetricsManagerActor::start_default(),
+        )
+        .unwrap();
+        let simulation = SimulationActor::new(1, Default::default(), registry).start();
+
+        let errors = simulation.send(load("first", VALID_SCRIPT)).await.unwrap();
+        assert!(errors.is_empty());
+        let stats = simulation.send(FetchSimulationStats).await.unwrap();
+        assert!(matches!(stats.state, SimulationState::Ready));
+        assert_eq!(stats.stats.len(), 1);
+
+        let errors = simulation
+            .send(load("second", "struct Demo {} impl X {}"))
+            .await
+            .unwrap();
+        assert!(matches!(errors[..], [SimulationError::Script(_)]));
+        let stats = simulation.send(FetchSimulationStats).await.unwrap();
+        assert!(matches!(stats.state, SimulationState::Idle));
+        assert_eq!(stats.simulation_id, "second");
+        assert!(stats.stats.is_empty());
+    }
 
     #[test]
     fn normalize_count_distributes_evenly() {
@@ -382,6 +820,27 @@ mod test {
         }
     }
 
+    #[test]
+    fn load_share_follows_weights() {
+        let ordinals: HashMap<u32, u32> = [(10, 0), (20, 1), (30, 2)].into();
+        let weights: HashMap<u32, f64> = [(10, 0.125), (20, 0.5), (30, 0.375)].into();
+        let shares = [10, 20, 30]
+            .map(|agent_id| LoadShare::from_weights(agent_id, &ordinals, &weights).unwrap());
+
+        for n in 0..200 {
+            let counts = shares.map(|share| share.count(n as f64));
+            assert_eq!(counts.iter().sum::<usize>(), n, "n={n}: {counts:?}");
+            for (count, weight) in counts.iter().zip([0.125, 0.5, 0.375]) {
+                assert!(
+                    (*count as f64 - n as f64 * weight).abs() < 1.0,
+                    "n={n}: {counts:?}"
+                );
+            }
+        }
+        assert_eq!(shares.map(|share| share.count(8.0)), [1, 4, 3]);
+        assert!(LoadShare::from_weights(40, &ordinals, &weights).is_none());
+    }
+
     #[test]
     fn normalize_count_single_agent() {
         // With a single agent, it should get the full count
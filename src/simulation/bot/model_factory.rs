@@ -2,6 +2,7 @@ use crate::simulation::bot::params::BotParams;
 use crate::simulation::bot::scripted::ScriptedBot;
 use crate::simulation::compound_id::CompoundId;
 use crate::simulation::rune::extension::bot::BotBehaviour;
+use crate::simulation::rune::extension::metrics::with_model_sync;
 use rune::runtime::RuntimeContext;
 use rune::Unit;
 use std::sync::Arc;
@@ -28,8 +29,13 @@ impl BotModelFactory {
             internal_id: compound_id.internal_id(),
             global_id: compound_id.global_id(),
         };
-        match vm.call([&self.model, "new"], (params,)) {
-            Ok(instance) => Some(ScriptedBot::new(self.behaviour.clone(), instance, vm)),
+        match with_model_sync(&self.model, || vm.call([&self.model, "new"], (params,))) {
+            Ok(instance) => Some(ScriptedBot::new(
+                &self.model,
+                self.behaviour.clone(),
+                instance,
+                vm,
+            )),
             Err(err) => {
                 log::error!("Error constructing bot '{}': {err}", self.model);
                 None
@@ -1,4 +1,6 @@
-use crate::agent::metrics::manager::message::{StartActionTimer, StopActionTimer};
+use crate::agent::metrics::manager::message::{
+    RecordCustomMetric, StartActionTimer, StopActionTimer,
+};
 use crate::simulation::bot::error::{BotError, LoadScriptError};
 use crate::simulation::bot::model_factory::BotModelFactory;
 use crate::simulation::bot::params::BotParams;
@@ -43,9 +45,11 @@ impl BotRegistry {
     where
         A: Actor<Context = actix::Context<A>>
             + Handler<StartActionTimer>
-            + Handler<StopActionTimer>,
+            + Handler<StopActionTimer>
+            + Handler<RecordCustomMetric>,
     {
         context.install(bot::module()?)?;
+        context.install(metrics::check_module(metrics_mgr_addr.clone())?)?;
         context.install(metrics::module(metrics_mgr_addr)?)?;
         let runtime = Arc::new(context.runtime()?);
 
@@ -178,9 +182,10 @@ impl BotRegistry {
                 internal_id: compound_id.internal_id(),
                 global_id: compound_id.global_id(),
             };
-            let bot_creation_result = vm.call([model, "new"], (params,));
+            let bot_creation_result =
+                metrics::with_model_sync(model, || vm.call([model, "new"], (params,)));
             match bot_creation_result {
-                Ok(instance) => Some(ScriptedBot::new(b.clone(), instance, vm)),
+                Ok(instance) => Some(ScriptedBot::new(model, b.clone(), instance, vm)),
                 Err(e) => {
                     log::error!("Error during '{model}' instantiation - {e}");
                     None
@@ -194,6 +199,7 @@ impl BotRegistry {
     /// # Returns
     ///
     /// Returns the count of registered bot models.
+    #[cfg(test)]
     pub(crate) fn count_bot_models(&self) -> usize {
         self.bot_types.len()
     }
@@ -310,7 +316,7 @@ mod test {
         let bot = registry.bot_types.get("Demo").unwrap();
 
         let mut vm = Vm::new(registry.runtime, registry.unit);
-        let instance = vm.call(&["Demo", "new"], ()).unwrap();
+        let instance = vm.call(["Demo", "new"], ()).unwrap();
         vm.call(bot.random_action(), (&instance,))
             .expect("Error running action");
     }
@@ -1,7 +1,10 @@
-use crate::simulation::actor::bot::{BotActor, BotState, ExecuteHandler, StopBot, TriggerHook};
-use crate::simulation::actor::simulation::BotStateChange;
+use crate::simulation::actor::bot::{
+    BotActor, BotState, DoAction, ExecuteHandler, KillBot, StopBot, TriggerHook,
+};
+use crate::simulation::actor::simulation::{BotStateChange, BotTicksSkipped};
 use crate::simulation::bot::model_factory::BotModelFactory;
 use crate::simulation::compound_id::CompoundId;
+use crate::simulation::executor::IterationSchedule;
 use crate::simulation::rune::types::value::OwnedValue;
 use crate::simulation::sequential_id_generator::SequentialIdGenerator;
 use crate::utils::varint::VarintDecode;
@@ -9,9 +12,13 @@ use actix::dev::Request;
 use actix::{Actor, Addr, Context, Handler};
 use rune::Hash;
 use std::collections::HashMap;
+use std::time::Duration;
 pub struct SimulationBot {
     state: BotState,
     addr: Addr<BotActor>,
+    /// runs actions every interval, otherwise when driven by the arrival-rate executor
+    paced: bool,
+    busy: bool,
 }
 
 impl SimulationBot {
@@ -24,6 +31,16 @@ impl SimulationBot {
         }
     }
 
+    /// Stop the bot immediately, without running its `Stopping` hook.
+    pub fn kill_bot(&mut self) {
+        let send_outcome = self.addr.try_send(KillBot);
+        if let Err(err) = send_outcome {
+            log::error!("Error killing bot - {}", err);
+        } else {
+            self.state = BotState::Stopping;
+        }
+    }
+
     pub fn execute_handler(&self, id: Hash, args: OwnedValue) -> Request<BotActor, ExecuteHandler> {
         self.addr.send(ExecuteHandler { id, args })
     }
@@ -40,6 +57,10 @@ impl SimulationBot {
     pub fn is_connected(&self) -> bool {
         self.addr.connected()
     }
+
+    fn is_idle(&self) -> bool {
+        !self.paced && !self.busy && self.state != BotState::Stopping && self.is_connected()
+    }
 }
 
 pub struct BotModel {
@@ -48,6 +69,9 @@ pub struct BotModel {
     id_generator: SequentialIdGenerator,
     bot_factory: BotModelFactory,
     bots: HashMap<u64, SimulationBot>,
+    schedule: IterationSchedule,
+    dropped_iterations: u64,
+    skipped_ticks: u64,
 }
 
 impl BotModel {
@@ -58,12 +82,22 @@ impl BotModel {
             bot_factory: factory,
             id_generator: Default::default(),
             bots: Default::default(),
+            schedule: Default::default(),
+            dropped_iterations: 0,
+            skipped_ticks: 0,
         }
     }
 
     pub fn spawn_bot<A>(&mut self, addr: Addr<A>)
     where
-        A: Actor<Context = Context<A>> + Handler<BotStateChange>,
+        A: Actor<Context = Context<A>> + Handler<BotStateChange> + Handler<BotTicksSkipped>,
+    {
+        self.spawn(addr, true);
+    }
+
+    fn spawn<A>(&mut self, addr: Addr<A>, paced: bool) -> Option<u64>
+    where
+        A: Actor<Context = Context<A>> + Handler<BotStateChange> + Handler<BotTicksSkipped>,
     {
         let usr_id = self.id_generator.next();
         let compound_id = CompoundId::new(self.agent_id, self.model_id, usr_id);
@@ -71,21 +105,102 @@ impl BotModel {
 
         match self.bot_factory.new_bot(compound_id) {
             Some(bot_behaviour) => {
+                let actor = BotActor::new(internal_id, addr, bot_behaviour);
+                let actor = if paced { actor } else { actor.driven() };
                 self.bots.insert(
                     internal_id,
                     SimulationBot {
                         state: BotState::Running,
-                        addr: BotActor::create(|_| BotActor::new(internal_id, addr, bot_behaviour)),
+                        addr: BotActor::create(|_| actor),
+                        paced,
+                        busy: false,
                     },
                 );
+                Some(internal_id)
             }
             None => {
                 self.id_generator.release_id(usr_id);
                 log::error!("Failed to create bot, releasing ID {usr_id}");
+                None
+            }
+        }
+    }
+
+    /// Offsets of the arrival-rate iterations due in the next window
+    pub(crate) fn schedule_iterations(&mut self, rate: f64, window: Duration) -> Vec<Duration> {
+        self.schedule.next_window(rate, window)
+    }
+
+    /// Run an iteration on an idle bot of the arrival-rate pool, growing the pool up to
+    /// `max_bots`. The iteration is dropped when the pool is exhausted.
+    pub(crate) fn start_iteration<A>(
+        &mut self,
+        addr: Addr<A>,
+        max_bots: usize,
+    ) -> Option<(u64, Request<BotActor, DoAction>)>
+    where
+        A: Actor<Context = Context<A>> + Handler<BotStateChange> + Handler<BotTicksSkipped>,
+    {
+        let idle_id = self
+            .bots
+            .iter()
+            .find(|(_id, bot)| bot.is_idle())
+            .map(|(id, _bot)| *id);
+        let bot_id = match idle_id {
+            Some(bot_id) => Some(bot_id),
+            None if self.count_active() < max_bots => self.spawn(addr, false),
+            None => None,
+        };
+        match bot_id.and_then(|bot_id| self.bots.get_mut(&bot_id).map(|bot| (bot_id, bot))) {
+            Some((bot_id, bot)) => {
+                bot.busy = true;
+                Some((bot_id, bot.addr.send(DoAction)))
+            }
+            None => {
+                self.dropped_iterations += 1;
+                None
             }
         }
     }
 
+    /// Make the bot available to the next arrival-rate iterations
+    pub(crate) fn finish_iteration(&mut self, bot_id: u64) {
+        if let Some(bot) = self.bots.get_mut(&bot_id) {
+            bot.busy = false;
+        }
+    }
+
+    /// Iterations dropped since the model was loaded because the bots pool was exhausted
+    pub fn dropped_iterations(&self) -> u64 {
+        self.dropped_iterations
+    }
+
+    /// Count the interval ticks a bot of the model skipped while running a slow action
+    pub(crate) fn add_skipped_ticks(&mut self, count: u64) {
+        self.skipped_ticks = self.skipped_ticks.saturating_add(count);
+    }
+
+    /// Interval ticks skipped by the bots of the model since it was loaded
+    pub fn skipped_ticks(&self) -> u64 {
+        self.skipped_ticks
+    }
+
+    /// Gracefully stop the running bots not matching the given pacing
+    pub(crate) fn stop_mismatched_bots(&mut self, paced: bool) {
+        self.bots
+            .values_mut()
+            .filter(|bot| bot.paced != paced && bot.state != BotState::Stopping)
+            .for_each(|bot| bot.stop_bot());
+    }
+
+    /// Gracefully stop the arrival-rate bots not running an iteration
+    pub(crate) fn stop_idle_bots(&mut self) {
+        self.bots
+            .values_mut()
+            .filter(|bot| bot.is_idle())
+            .for_each(|bot| bot.stop_bot());
+    }
+
     pub fn count_by_state(&self) -> HashMap<BotState, usize> {
         let mut group_by_state = HashMap::new();
 
@@ -119,6 +234,10 @@ impl BotModel {
         })
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.bots.is_empty()
+    }
+
     pub fn bots_mut(&mut self) -> impl Iterator<Item = &mut SimulationBot> {
         self.bots.values_mut()
     }
@@ -10,7 +10,7 @@ use thiserror::Error;
 /// # Generics
 ///
 /// - `AgentId`: The type of the agent ID. This allows for flexibility in specifying what
-/// type the agent ID should be, accommodating different use cases or identifier schemes.
+///   type the agent ID should be, accommodating different use cases or identifier schemes.
 ///
 /// # Fields
 ///
@@ -21,7 +21,7 @@ use thiserror::Error;
 /// # Errors
 ///
 /// - `CompoundIdParseError::BadFormat`: Indicates that the internal ID could not be parsed
-/// into a valid `CompoundId` due to incorrect format or content.
+///   into a valid `CompoundId` due to incorrect format or content.
 ///
 /// # Examples
 ///
@@ -64,7 +64,7 @@ pub struct CompoundId<AgentId> {
 /// # Variants
 ///
 /// - `BadFormat(String)`: Indicates that the internal ID could not be parsed into a valid `CompoundId`
-/// due to incorrect format or content. The contained `String` provides a descriptive error message.
+///   due to incorrect format or content. The contained `String` provides a descriptive error message.
 ///
 /// # Usage
 ///
@@ -199,7 +199,7 @@ impl<AgentId> CompoundId<AgentId> {
     /// # Parameters
     ///
     /// - `agent_id`: The new agent ID to be used in the returned `CompoundId`. The type of this parameter
-    /// can differ from the original `CompoundId`'s agent ID type.
+    ///   can differ from the original `CompoundId`'s agent ID type.
     ///
     /// # Returns
     ///
@@ -1,3 +1,4 @@
+use crate::simulation::bot::error::LoadScriptError;
 use meval::Error;
 use thiserror::Error;
 
@@ -7,6 +8,32 @@ pub enum SimulationError {
     /// The mathematical expression for a load shape function could not be parsed.
     #[error("Bad Shape function - {0}")]
     BadShape(String),
+    /// The load shape expression of a model could not be parsed.
+    #[error("Bad shape of model '{model}' - {message}")]
+    BadModelShape { model: String, message: String },
+    /// The simulation script could not be loaded.
+    #[error("Script error - {0}")]
+    Script(#[from] LoadScriptError),
+    /// A load shape refers to a model not defined by the script.
+    #[error("Unknown model '{0}'")]
+    UnknownModel(String),
+}
+
+/// Errors that can occur loading a recorded time series.
+#[derive(Debug, Error)]
+pub enum TimeSeriesError {
+    /// The series has no points.
+    #[error("Empty time series")]
+    Empty,
+    /// A point has a non finite timestamp or value.
+    #[error("Invalid time series point ({0}, {1})")]
+    InvalidPoint(f64, f64),
+    /// The CSV series could not be parsed.
+    #[error("Invalid CSV time series - {0}")]
+    Csv(#[from] csv::Error),
+    /// The JSON series could not be parsed.
+    #[error("Invalid JSON time series - {0}")]
+    Json(#[from] serde_json::Error),
 }
 
 impl From<meval::Error> for SimulationError {
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// How the shape of a bot model drives the load.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExecutionMode {
+    /// Closed model: the shape is the number of concurrent bots, each one running an action every
+    /// `interval` after the previous one.
+    #[default]
+    ConstantBots,
+    /// Open model: the shape is the number of iterations started per second.
+    ///
+    /// Iterations are scheduled at that rate regardless of the response time, each one running an
+    /// action on an idle bot of a pool that grows up to `max_bots` on each agent. Iterations
+    /// finding the pool exhausted are dropped and reported as `dropped_iterations`.
+    ArrivalRate {
+        /// Bots pool cap on each agent, further capped by the agent `max_running`.
+        max_bots: usize,
+    },
+}
+
+/// Spreads the iterations of an arrival-rate model over the simulation ticks.
+///
+/// Fractional iterations are carried to the next window, so that a rate below one iteration per
+/// tick is still honoured on average.
+#[derive(Debug, Default)]
+pub(crate) struct IterationSchedule {
+    carry: f64,
+}
+
+impl IterationSchedule {
+    /// Offsets, from the window start, of the iterations due in the next `window` at `rate`
+    /// iterations per second
+    pub(crate) fn next_window(&mut self, rate: f64, window: Duration) -> Vec<Duration> {
+        if !(rate > 0.0 && rate.is_finite()) {
+            self.carry = 0.0;
+            return Vec::new();
+        }
+        let due = self.carry + rate * window.as_secs_f64();
+        let count = due.floor();
+        let offsets = (0..count as usize)
+            .map(|idx| Duration::from_secs_f64((idx as f64 + 1.0 - self.carry) / rate))
+            .collect();
+        self.carry = due - count;
+        offsets
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn iterations_are_evenly_spaced_across_windows() {
+        let window = Duration::from_millis(1500);
+        let mut schedule = IterationSchedule::default();
+
+        let first = schedule.next_window(1.0, window);
+        assert_eq!(first, vec![Duration::from_secs(1)]);
+        // the half iteration carried from the first window is due after 0.5s
+        let second = schedule.next_window(1.0, window);
+        assert_eq!(
+            second,
+            vec![Duration::from_millis(500), Duration::from_millis(1500)]
+        );
+
+        let total: usize = (0..100)
+            .map(|_| schedule.next_window(0.5, window).len())
+            .sum();
+        assert_eq!(total, 75);
+    }
+
+    #[test]
+    fn no_iterations_without_rate() {
+        let mut schedule = IterationSchedule::default();
+        schedule.next_window(0.5, Duration::from_secs(1));
+        assert!(schedule.next_window(0.0, Duration::from_secs(1)).is_empty());
+        assert!(schedule
+            .next_window(f64::NAN, Duration::from_secs(1))
+            .is_empty());
+        assert_eq!(schedule.next_window(1.0, Duration::from_secs(1)).len(), 1);
+    }
+}
@@ -14,6 +14,7 @@ pub struct SimulationFacade {
     user_handler_tx: Recipient<InvokeHandler>,
 }
 
+#[allow(dead_code)]
 impl SimulationFacade {
     pub fn new<S>(actor: Addr<S>) -> Self
     where
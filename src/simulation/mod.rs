@@ -10,6 +10,9 @@
 //! - [`bot::registry::BotRegistry`] — Rune script loader and bot type registry.
 //! - [`compound_id::CompoundId`] — Multi-level hierarchical identifier (agent/model/bot).
 //! - [`shape::parse_shape_fun`] — Mathematical expression parser for load shapes.
+//! - [`executor::ExecutionMode`] — Closed (concurrent bots) or open (arrival rate) model execution.
+//! - [`series::TimeSeries`] — Recorded traffic curve replayed as a load shape.
+//! - [`preview::ShapePreview`] — Expected global and per-agent bot counts of a shape over time.
 
 mod bot_model;
 pub(crate) mod facade;
@@ -19,5 +22,8 @@ pub mod actor;
 pub mod bot;
 pub mod compound_id;
 pub mod error;
+pub mod executor;
+pub mod preview;
 pub mod rune;
+pub mod series;
 pub mod shape;
@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::agent::builder::SimulationParams;
+use crate::communication::protobuf::grpc::AgentOrdinals;
+use crate::simulation::actor::simulation::{LoadShare, TICK_INTERVAL};
+use crate::simulation::series::TimeSeries;
+use crate::simulation::shape::{LoadStage, ModelShape, DAY_SECS};
+
+/// Expected load of a shape over time, split amongst a set of agents.
+///
+/// The shape is sampled every `step`, while bots are spawned and stopped on each agent tick as the
+/// simulation does: the global count is split proportionally to the agents capacity, capped by
+/// `max_running` and approached by at most `max_rate` new bots per tick.
+///
+/// # Examples
+///
+/// ```
+/// use hailstorm::agent::builder::SimulationParams;
+/// use hailstorm::simulation::preview::ShapePreview;
+/// use std::time::Duration;
+///
+/// let report = ShapePreview::new("100")
+///     .duration(Duration::from_secs(30))
+///     .step(Duration::from_secs(15))
+///     .agents(vec![
+///         SimulationParams::default().max_running(30),
+///         SimulationParams::default().max_running(30).max_rate(10),
+///     ])
+///     .sample()
+///     .unwrap();
+///
+/// let last = report.samples.last().unwrap();
+/// assert_eq!(last.expected, 100.0);
+/// assert_eq!(last.running, 60);
+/// ```
+pub struct ShapePreview {
+    shape: ModelShape,
+    duration: Duration,
+    step: Duration,
+    phase_duration: Option<Duration>,
+    time_of_day: Duration,
+    agents: Vec<SimulationParams>,
+}
+
+impl ShapePreview {
+    /// Preview of the given shape expression, by default sampled every 10 seconds over 10 minutes
+    /// on a single agent without limits
+    pub fn new(shape: &str) -> Self {
+        Self::model_shape(ModelShape::Expression(shape.to_string()))
+    }
+
+    /// Preview of the given stage-based load profile
+    pub fn stages(stages: Vec<LoadStage>) -> Self {
+        Self::model_shape(ModelShape::Stages(stages))
+    }
+
+    /// Preview of the given recorded traffic curve
+    pub fn series(series: TimeSeries) -> Self {
+        Self::model_shape(ModelShape::Series(series))
+    }
+
+    fn model_shape(shape: ModelShape) -> Self {
+        Self {
+            shape,
+            duration: Duration::from_secs(600),
+            step: Duration::from_secs(10),
+            phase_duration: None,
+            time_of_day: Duration::ZERO,
+            agents: vec![SimulationParams::default()],
+        }
+    }
+
+    /// Sample the shape from `t = 0` up to the given duration
+    pub fn duration(self, duration: Duration) -> Self {
+        Self { duration, ..self }
+    }
+
+    /// Interval between two samples
+    pub fn step(self, step: Duration) -> Self {
+        Self { step, ..self }
+    }
+
+    /// Value of the `duration` shape variable, infinite if not set as for simulations without phases
+    pub fn phase_duration(self, phase_duration: Duration) -> Self {
+        Self {
+            phase_duration: Some(phase_duration),
+            ..self
+        }
+    }
+
+    /// Wall-clock time of day (UTC) at `t = 0`, from which the `tod` shape variable advances,
+    /// midnight if not set
+    pub fn time_of_day(self, time_of_day: Duration) -> Self {
+        Self {
+            time_of_day,
+            ..self
+        }
+    }
+
+    /// Agents the load is split amongst, in ordinal order
+    pub fn agents(self, agents: Vec<SimulationParams>) -> Self {
+        Self { agents, ..self }
+    }
+
+    /// Sample the expected global and per-agent bot counts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the shape expression cannot be parsed.
+    pub fn sample(&self) -> Result<PreviewReport, meval::Error> {
+        let shape = self.shape.clone().parse()?;
+        let phase_duration = self
+            .phase_duration
+            .map_or(f64::INFINITY, |duration| duration.as_secs_f64());
+        let start_tod = self.time_of_day.as_secs_f64();
+        let shape_at = |t: f64| shape(t, t, phase_duration, (start_tod + t) % DAY_SECS);
+
+        let ordinals = AgentOrdinals::from_capacities(
+            &self
+                .agents
+                .iter()
+                .enumerate()
+                .map(|(agent_id, params)| (agent_id as u32, params.capacity()))
+                .collect::<HashMap<_, _>>(),
+        );
+        let shares = (0..self.agents.len() as u32)
+            .map(|agent_id| {
+                LoadShare::from_weights(agent_id, &ordinals.ordinals, &ordinals.weights)
+            })
+            .collect::<Vec<_>>();
+        let share_counts = |global_count: f64| {
+            shares
+                .iter()
+                .map(|share| share.map_or(0, |share| share.count(global_count)))
+                .collect::<Vec<_>>()
+        };
+
+        let step = self.step.max(Duration::from_millis(1));
+        let mut running = vec![0; self.agents.len()];
+        let mut next_tick = Duration::ZERO;
+        let mut samples = Vec::new();
+        let mut t = Duration::ZERO;
+        while t <= self.duration {
+            while next_tick <= t {
+                let targets = share_counts(shape_at(next_tick.as_secs_f64()));
+                for ((params, running), target) in
+                    self.agents.iter().zip(running.iter_mut()).zip(targets)
+                {
+                    let target = params.cap_running(target);
+                    *running = if target < *running {
+                        target
+                    } else {
+                        *running + params.cap_spawn(target - *running)
+                    };
+                }
+                next_tick += TICK_INTERVAL;
+            }
+
+            let expected = shape_at(t.as_secs_f64());
+            let agents = self
+                .agents
+                .iter()
+                .zip(share_counts(expected))
+                .zip(running.iter())
+                .map(|((params, share), running)| AgentSample {
+                    share,
+                    target: params.cap_running(share),
+                    running: *running,
+                })
+                .collect::<Vec<_>>();
+            samples.push(PreviewSample {
+                t: t.as_secs_f64(),
+                expected,
+                running: running.iter().sum(),
+                agents,
+            });
+            t += step;
+        }
+
+        Ok(PreviewReport { samples })
+    }
+}
+
+/// Bot counts of an agent at a sample time.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AgentSample {
+    /// Share of the global count assigned to the agent.
+    pub share: usize,
+    /// Share capped by the agent `max_running`.
+    pub target: usize,
+    /// Bots running on the agent, limited by the agent `max_rate`.
+    pub running: usize,
+}
+
+/// Expected bot counts at a sample time.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PreviewSample {
+    /// Seconds since the simulation start.
+    pub t: f64,
+    /// Global count given by the shape.
+    pub expected: f64,
+    /// Bots running on all the agents.
+    pub running: usize,
+    /// Counts of each agent, in ordinal order.
+    pub agents: Vec<AgentSample>,
+}
+
+/// Samples of a [`ShapePreview`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PreviewReport {
+    /// Samples in time order.
+    pub samples: Vec<PreviewSample>,
+}
+
+impl PreviewReport {
+    /// CSV with a row per sample and the `share`, `target` and `running` columns of each agent
+    pub fn to_csv(&self) -> String {
+        let agents_count = self.samples.first().map_or(0, |sample| sample.agents.len());
+        let mut csv = String::from("t,expected,running");
+        for idx in 0..agents_count {
+            let _ = write!(
+                csv,
+                ",agent_{idx}_share,agent_{idx}_target,agent_{idx}_running"
+            );
+        }
+        csv.push('\n');
+        for sample in self.samples.iter() {
+            let _ = write!(csv, "{},{},{}", sample.t, sample.expected, sample.running);
+            for agent in sample.agents.iter() {
+                let _ = write!(csv, ",{},{},{}", agent.share, agent.target, agent.running);
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Pretty-printed JSON of the samples
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// ASCII chart of the running bots (`#`) and of the count expected by the shape (`o`), the
+    /// gap between them shows where the agents limits flatten the curve
+    pub fn to_ascii_chart(&self, width: usize, height: usize) -> String {
+        let (width, height) = (width.max(1), height.max(1));
+        if self.samples.is_empty() {
+            return String::new();
+        }
+        let columns = (0..width)
+            .map(|col| &self.samples[col * self.samples.len() / width])
+            .collect::<Vec<_>>();
+        let max_value = columns
+            .iter()
+            .map(|sample| sample.expected.max(sample.running as f64))
+            .fold(0.0, f64::max)
+            .max(1.0);
+        let row_of = |value: f64| ((value.max(0.0) / max_value) * height as f64).round() as usize;
+
+        let label_width = format!("{max_value:.0}").len();
+        let mut chart = String::new();
+        for row in (1..=height).rev() {
+            let label = match row {
+                row if row == height => format!("{max_value:.0}"),
+                _ => String::new(),
+            };
+            let _ = write!(chart, "{label:>label_width$} |");
+            for sample in columns.iter() {
+                let symbol = if row_of(sample.expected) == row {
+                    'o'
+                } else if row_of(sample.running as f64) >= row {
+                    '#'
+                } else {
+                    ' '
+                };
+                chart.push(symbol);
+            }
+            chart.push('\n');
+        }
+        let _ = writeln!(chart, "{:>label_width$} +{}", 0, "-".repeat(width));
+        let end = columns.last().map_or(0.0, |sample| sample.t);
+        let _ = writeln!(
+            chart,
+            "{:>label_width$}  0s{:>pad$}",
+            "",
+            format!("{end}s"),
+            pad = width.saturating_sub(2)
+        );
+        chart
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rate_and_caps_flatten_the_curve() {
+        let report = ShapePreview::new("100")
+            .duration(Duration::from_secs(6))
+            .step(Duration::from_secs(3))
+            .agents(vec![
+                SimulationParams::default().max_running(20),
+                SimulationParams::default().max_running(80).max_rate(20),
+            ])
+            .sample()
+            .unwrap();
+
+        let counts = report
+            .samples
+            .iter()
+            .map(|sample| {
+                sample
+                    .agents
+                    .iter()
+                    .map(|agent| (agent.share, agent.target, agent.running))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        // ticks at 0s, 1.5s and 3s before the second sample, 4.5s and 6s before the last one
+        assert_eq!(
+            counts,
+            vec![
+                vec![(20, 20, 20), (80, 80, 20)],
+                vec![(20, 20, 20), (80, 80, 60)],
+                vec![(20, 20, 20), (80, 80, 80)],
+            ]
+        );
+        assert_eq!(report.samples[1].running, 80);
+    }
+
+    #[test]
+    fn shares_sum_up_to_the_shape() {
+        let report = ShapePreview::new("10 * t")
+            .duration(Duration::from_secs(10))
+            .step(Duration::from_secs(1))
+            .agents((0..3).map(|_| SimulationParams::default()).collect())
+            .sample()
+            .unwrap();
+
+        for sample in report.samples.iter() {
+            let shares: usize = sample.agents.iter().map(|agent| agent.share).sum();
+            assert_eq!(shares, sample.expected.floor() as usize);
+        }
+        assert_eq!(report.samples.len(), 11);
+    }
+
+    #[test]
+    fn report_formats() {
+        let report = ShapePreview::new("50 * step(t - 5)")
+            .duration(Duration::from_secs(10))
+            .step(Duration::from_secs(5))
+            .sample()
+            .unwrap();
+
+        let csv = report.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("t,expected,running,agent_0_share,agent_0_target,agent_0_running")
+        );
+        assert_eq!(lines.last(), Some("10,50,50,50,50,50"));
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["samples"][2]["running"], 50);
+
+        let chart = report.to_ascii_chart(3, 4);
+        assert_eq!(chart.lines().count(), 6);
+        assert!(chart.starts_with("50 |  o\n"));
+    }
+
+    #[test]
+    fn time_of_day_advances_from_the_start() {
+        let report = ShapePreview::new("tod")
+            .duration(Duration::from_secs(7200))
+            .step(Duration::from_secs(3600))
+            .time_of_day(Duration::from_secs(23 * 3600))
+            .sample()
+            .unwrap();
+        let expected = report
+            .samples
+            .iter()
+            .map(|sample| sample.expected)
+            .collect::<Vec<_>>();
+        assert_eq!(expected, vec![82_800.0, 0.0, 3_600.0]);
+    }
+
+    #[test]
+    fn invalid_shape() {
+        assert!(ShapePreview::new("10 *").sample().is_err());
+    }
+}
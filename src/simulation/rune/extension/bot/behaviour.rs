@@ -73,7 +73,7 @@ impl BotBehaviour {
                 return act.hash;
             }
         }
-        return self.actions.last().expect("No actions found").hash;
+        self.actions.last().expect("No actions found").hash
     }
 
     pub fn hook_action(&self, state: BotState) -> Option<Hash> {
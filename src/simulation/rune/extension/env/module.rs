@@ -53,7 +53,6 @@ impl EnvModuleConf {
     pub fn with_prefix(self, prefix: &str) -> Self {
         Self {
             prefix: Some(String::from(prefix)),
-            ..self
         }
     }
 }
@@ -74,7 +73,7 @@ fn read_env(maybe_prefix: Option<&String>, name: &str) -> Option<String> {
 /// # Parameters
 ///
 /// - `cfg`: An `EnvModuleConf` struct specifying the module's configuration, including any
-/// optional prefix to use when reading environment variables.
+///   optional prefix to use when reading environment variables.
 ///
 /// # Returns
 ///
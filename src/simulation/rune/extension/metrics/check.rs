@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use actix::Recipient;
+use rune::runtime::{RuntimeError, Value, VmResult};
+use rune::FromValue;
+
+use crate::agent::metrics::custom::{CustomMetricKey, CustomMetricUpdate};
+use crate::agent::metrics::manager::message::RecordCustomMetric;
+use crate::simulation::rune::types::value::OwnedValue;
+
+tokio::task_local! {
+    /// Model of the bot whose script is running, checks are recorded under it
+    static BOT_MODEL: String;
+}
+
+/// Run a bot script call, attributing the checks it evaluates to `model`
+pub(crate) async fn with_model<F: Future>(model: &str, call: F) -> F::Output {
+    BOT_MODEL.scope(model.to_string(), call).await
+}
+
+/// Run a synchronous bot script call (e.g. the bot constructor), attributing the checks it
+/// evaluates to `model`
+pub(crate) fn with_model_sync<R>(model: &str, call: impl FnOnce() -> R) -> R {
+    BOT_MODEL.sync_scope(model.to_string(), call)
+}
+
+/// Evaluates the checks of the running bot, counting their passes and failures.
+pub(super) struct CheckRecorder {
+    recipient: Recipient<RecordCustomMetric>,
+}
+
+impl CheckRecorder {
+    pub(super) fn new(recipient: Recipient<RecordCustomMetric>) -> Self {
+        Self { recipient }
+    }
+
+    fn model() -> Result<String, RuntimeError> {
+        BOT_MODEL
+            .try_with(Clone::clone)
+            .map_err(|_| RuntimeError::panic("Checks can only be evaluated by a bot"))
+    }
+
+    fn record(&self, model: &str, name: &str, passed: bool) {
+        self.recipient.do_send(RecordCustomMetric::new(
+            CustomMetricKey::check(model, name, passed),
+            CustomMetricUpdate::Increment(1.0),
+        ));
+    }
+
+    /// Record the result of a check, returning it
+    pub(super) fn check(&self, name: &str, condition: bool) -> VmResult<bool> {
+        let model = rune::vm_try!(Self::model());
+        self.record(&model, name, condition);
+        VmResult::Ok(condition)
+    }
+
+    /// Record the results of an object of checks, returning whether all of them passed
+    pub(super) fn check_all(&self, checks: HashMap<String, Value>) -> VmResult<bool> {
+        let model = rune::vm_try!(Self::model());
+        let results = rune::vm_try!(checks
+            .into_iter()
+            .map(|(name, value)| match OwnedValue::from_value(value)? {
+                OwnedValue::Bool(passed) => Ok((name, passed)),
+                _ => Err(RuntimeError::panic(format!(
+                    "Expected a boolean condition for check '{name}'"
+                ))),
+            })
+            .collect::<Result<Vec<_>, RuntimeError>>());
+        for (name, passed) in results.iter() {
+            self.record(&model, name, *passed);
+        }
+        VmResult::Ok(results.iter().all(|(_, passed)| *passed))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use actix::Actor;
+    use rune::{Diagnostics, Source, Sources, Vm};
+
+    use super::*;
+    use crate::agent::metrics::custom::CustomMetricValue;
+    use crate::agent::metrics::manager::actor::MetricsManagerActor;
+    use crate::agent::metrics::manager::message::FetchCustomMetrics;
+    use crate::simulation::rune::extension::metrics;
+
+    #[actix::test]
+    async fn checks_are_counted_per_model() {
+        let metrics_addr = MetricsManagerActor::default().start();
+        let mut context = rune::Context::with_default_modules().unwrap();
+        context
+            .install(metrics::check_module(metrics_addr.clone()).unwrap())
+            .unwrap();
+        let mut sources = Sources::new();
+        sources
+            .insert(
+                Source::new(
+                    "script",
+                    r#"
+                    pub fn main(status) {
+                        let ok = hailstorm::check("status is 200", status == 200);
+                        let all = hailstorm::check_all(#{"status is 200": status == 200, "is even": status % 2 == 0});
+                        [ok, all]
+                    }
+                    "#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let unit = rune::prepare(&mut sources)
+            .with_context(&context)
+            .with_diagnostics(&mut Diagnostics::new())
+            .build()
+            .unwrap();
+        let mut vm = Vm::new(Arc::new(context.runtime().unwrap()), Arc::new(unit));
+
+        for status in [200, 404] {
+            let out = with_model("Demo", vm.async_call(["main"], (status,)))
+                .await
+                .unwrap();
+            let out: Vec<bool> = rune::from_value(out).unwrap();
+            assert_eq!(out, vec![status == 200, status == 200]);
+        }
+        assert!(vm.call(["main"], (200,)).is_err());
+
+        let counts = metrics_addr
+            .send(FetchCustomMetrics)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|snapshot| {
+                assert_eq!(snapshot.key.model, "Demo");
+                let (check, passed) = snapshot.key.as_check().unwrap();
+                ((check.to_string(), passed), snapshot.value)
+            })
+            .collect::<HashMap<_, _>>();
+        let count = |check: &str, passed| counts.get(&(check.to_string(), passed)).cloned();
+        assert_eq!(
+            count("status is 200", true),
+            Some(CustomMetricValue::Counter(2.0))
+        );
+        assert_eq!(
+            count("status is 200", false),
+            Some(CustomMetricValue::Counter(2.0))
+        );
+        assert_eq!(
+            count("is even", true),
+            Some(CustomMetricValue::Counter(2.0))
+        );
+        assert_eq!(count("is even", false), None);
+    }
+}
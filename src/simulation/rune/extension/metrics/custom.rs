@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use actix::Recipient;
+use rune::runtime::{RuntimeError, Value, VmResult};
+use rune::{Any, FromValue};
+
+use crate::agent::metrics::custom::{CustomMetricKey, CustomMetricUpdate};
+use crate::agent::metrics::manager::message::RecordCustomMetric;
+use crate::simulation::rune::types::value::OwnedValue;
+
+/// Key of a custom metric, labels values can be strings, numbers, booleans or chars
+pub(super) fn metric_key(
+    model: &str,
+    name: &str,
+    labels: HashMap<String, Value>,
+) -> Result<CustomMetricKey, RuntimeError> {
+    let labels = labels
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match OwnedValue::from_value(value)? {
+                OwnedValue::String(v) => v,
+                OwnedValue::Integer(v) => v.to_string(),
+                OwnedValue::Unsigned(v) => v.to_string(),
+                OwnedValue::Float(v) => v.to_string(),
+                OwnedValue::Bool(v) => v.to_string(),
+                OwnedValue::Char(v) => v.to_string(),
+                _ => {
+                    return Err(RuntimeError::panic(format!(
+                        "Unsupported value of label '{key}' of metric '{name}'"
+                    )))
+                }
+            };
+            Ok((key, value))
+        })
+        .collect::<Result<BTreeMap<_, _>, _>>()?;
+    Ok(CustomMetricKey {
+        model: model.to_string(),
+        name: name.to_string(),
+        labels,
+    })
+}
+
+/// Numeric value of an integer or float
+pub(super) fn number(value: Value) -> Result<f64, RuntimeError> {
+    match OwnedValue::from_value(value)? {
+        OwnedValue::Integer(v) => Ok(v as f64),
+        OwnedValue::Unsigned(v) => Ok(v as f64),
+        OwnedValue::Float(v) => Ok(v),
+        _ => Err(RuntimeError::panic("Expected a number")),
+    }
+}
+
+fn send_update(
+    recipient: &Recipient<RecordCustomMetric>,
+    key: &CustomMetricKey,
+    update: CustomMetricUpdate,
+) {
+    recipient.do_send(RecordCustomMetric::new(key.clone(), update));
+}
+
+/// Monotonic counter recorded by a script (e.g. orders placed).
+#[derive(Any)]
+pub struct Counter {
+    key: CustomMetricKey,
+    recipient: Recipient<RecordCustomMetric>,
+}
+
+impl Counter {
+    pub(super) fn new(key: CustomMetricKey, recipient: Recipient<RecordCustomMetric>) -> Self {
+        Self { key, recipient }
+    }
+
+    /// Increment the counter by one
+    #[rune::function]
+    pub fn inc(&self) {
+        send_update(
+            &self.recipient,
+            &self.key,
+            CustomMetricUpdate::Increment(1.0),
+        );
+    }
+
+    /// Increment the counter by a non negative amount
+    #[rune::function]
+    pub fn add(&self, amount: Value) -> VmResult<()> {
+        let amount = rune::vm_try!(number(amount));
+        if !(amount >= 0.0 && amount.is_finite()) {
+            return VmResult::err(RuntimeError::panic(format!(
+                "Invalid increment {amount} of counter '{}'",
+                self.key.name
+            )));
+        }
+        send_update(
+            &self.recipient,
+            &self.key,
+            CustomMetricUpdate::Increment(amount),
+        );
+        VmResult::Ok(())
+    }
+}
+
+/// Gauge recorded by a script (e.g. queue depth seen), keeping the last value set.
+#[derive(Any)]
+pub struct Gauge {
+    key: CustomMetricKey,
+    recipient: Recipient<RecordCustomMetric>,
+}
+
+impl Gauge {
+    pub(super) fn new(key: CustomMetricKey, recipient: Recipient<RecordCustomMetric>) -> Self {
+        Self { key, recipient }
+    }
+
+    /// Set the gauge value
+    #[rune::function]
+    pub fn set(&self, value: Value) -> VmResult<()> {
+        let value = rune::vm_try!(number(value));
+        send_update(&self.recipient, &self.key, CustomMetricUpdate::Set(value));
+        VmResult::Ok(())
+    }
+
+    /// Add a (possibly negative) amount to the gauge value
+    #[rune::function]
+    pub fn add(&self, amount: Value) -> VmResult<()> {
+        let amount = rune::vm_try!(number(amount));
+        send_update(
+            &self.recipient,
+            &self.key,
+            CustomMetricUpdate::Adjust(amount),
+        );
+        VmResult::Ok(())
+    }
+
+    /// Increment the gauge value by one
+    #[rune::function]
+    pub fn inc(&self) {
+        send_update(&self.recipient, &self.key, CustomMetricUpdate::Adjust(1.0));
+    }
+
+    /// Decrement the gauge value by one
+    #[rune::function]
+    pub fn dec(&self) {
+        send_update(&self.recipient, &self.key, CustomMetricUpdate::Adjust(-1.0));
+    }
+}
+
+/// Histogram of the values recorded by a script (e.g. cart size).
+#[derive(Any)]
+pub struct Histogram {
+    key: CustomMetricKey,
+    bounds: Arc<[f64]>,
+    recipient: Recipient<RecordCustomMetric>,
+}
+
+impl Histogram {
+    pub(super) fn new(
+        key: CustomMetricKey,
+        bounds: Arc<[f64]>,
+        recipient: Recipient<RecordCustomMetric>,
+    ) -> Self {
+        Self {
+            key,
+            bounds,
+            recipient,
+        }
+    }
+
+    /// Record a value
+    #[rune::function]
+    pub fn record(&self, value: Value) -> VmResult<()> {
+        let value = rune::vm_try!(number(value));
+        send_update(
+            &self.recipient,
+            &self.key,
+            CustomMetricUpdate::Record {
+                value,
+                bounds: self.bounds.clone(),
+            },
+        );
+        VmResult::Ok(())
+    }
+}
@@ -1,4 +1,7 @@
+mod check;
+mod custom;
 mod module;
 mod performance;
 
-pub use module::module;
+pub(crate) use check::{with_model, with_model_sync};
+pub use module::{check_module, module};
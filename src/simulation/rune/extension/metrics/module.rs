@@ -1,11 +1,21 @@
-use crate::agent::metrics::manager::message::{StartActionTimer, StopActionTimer};
+use crate::agent::metrics::manager::message::{
+    RecordCustomMetric, StartActionTimer, StopActionTimer,
+};
+use crate::simulation::rune::extension::metrics::check::CheckRecorder;
+use crate::simulation::rune::extension::metrics::custom::{Counter, Gauge, Histogram};
 use crate::simulation::rune::extension::metrics::performance::PerformanceRegistry;
 use actix::{Actor, Addr, Context, Handler};
+use rune::runtime::Value;
 use rune::{ContextError, Module};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 pub fn module<A>(metrics_mgr_addr: Addr<A>) -> Result<Module, ContextError>
 where
-    A: Actor<Context = Context<A>> + Handler<StartActionTimer> + Handler<StopActionTimer>,
+    A: Actor<Context = Context<A>>
+        + Handler<StartActionTimer>
+        + Handler<StopActionTimer>
+        + Handler<RecordCustomMetric>,
 {
     let mut module = Module::with_crate_item("hailstorm", ["metrics"])?;
 
@@ -14,6 +24,42 @@ where
         PerformanceRegistry::new(model, metrics_mgr_addr.clone())
     }).build()?;
     module.function_meta(PerformanceRegistry::observe)?;
+    module.function_meta(PerformanceRegistry::observe_async)?;
+    module.function_meta(PerformanceRegistry::counter)?;
+    module.function_meta(PerformanceRegistry::gauge)?;
+    module.function_meta(PerformanceRegistry::histogram)?;
+    module.function_meta(PerformanceRegistry::histogram_with_bounds)?;
+
+    module.ty::<Counter>()?;
+    module.function_meta(Counter::inc)?;
+    module.function_meta(Counter::add)?;
+    module.ty::<Gauge>()?;
+    module.function_meta(Gauge::set)?;
+    module.function_meta(Gauge::add)?;
+    module.function_meta(Gauge::inc)?;
+    module.function_meta(Gauge::dec)?;
+    module.ty::<Histogram>()?;
+    module.function_meta(Histogram::record)?;
+
+    Ok(module)
+}
+
+/// `hailstorm::check(name, condition)` and `hailstorm::check_all(#{name: condition})`, counting
+/// the passes and failures of the checks evaluated by the bots
+pub fn check_module<A>(metrics_mgr_addr: Addr<A>) -> Result<Module, ContextError>
+where
+    A: Actor<Context = Context<A>> + Handler<RecordCustomMetric>,
+{
+    let mut module = Module::with_crate("hailstorm")?;
+
+    let recorder = Arc::new(CheckRecorder::new(metrics_mgr_addr.recipient()));
+    let check_recorder = recorder.clone();
+    module.function("check", move |name: String, condition: bool| {
+        check_recorder.check(&name, condition)
+    }).build()?;
+    module.function("check_all", move |checks: HashMap<String, Value>| {
+        recorder.check_all(checks)
+    }).build()?;
 
     Ok(module)
 }
@@ -1,12 +1,20 @@
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use actix::{Actor, Addr, Context, Handler, Recipient};
-use rune::runtime::{Function, Ref, RuntimeError, Value, VmResult};
-use rune::{Any, FromValue};
+use rune::runtime::{Function, Future, Ref, RuntimeError, Value, VmError, VmResult};
+use rune::{vm_try, Any, FromValue, TypeHash};
 
+use crate::agent::metrics::custom::DEFAULT_VALUE_BOUNDS;
 use crate::agent::metrics::manager::message::StartedActionTimer;
-use crate::agent::metrics::manager::message::{StartActionTimer, StopActionTimer};
+use crate::agent::metrics::manager::message::{
+    RecordCustomMetric, StartActionTimer, StopActionTimer,
+};
 use crate::agent::metrics::timer::{ActionOutcome, ExecutionInfo};
+use crate::simulation::rune::extension::metrics::custom::{
+    metric_key, number, Counter, Gauge, Histogram,
+};
 use crate::simulation::rune::types::value::OwnedValue;
 
 #[derive(Any)]
@@ -14,17 +22,22 @@ pub struct PerformanceRegistry {
     model: String,
     start_timer_recipient: Recipient<StartActionTimer>,
     stop_timer_recipient: Recipient<StopActionTimer>,
+    custom_metric_recipient: Recipient<RecordCustomMetric>,
 }
 
 impl PerformanceRegistry {
     pub fn new<A>(model: String, metrics_addr: Addr<A>) -> Self
     where
-        A: Actor<Context = Context<A>> + Handler<StartActionTimer> + Handler<StopActionTimer>,
+        A: Actor<Context = Context<A>>
+            + Handler<StartActionTimer>
+            + Handler<StopActionTimer>
+            + Handler<RecordCustomMetric>,
     {
         Self {
             model,
             start_timer_recipient: metrics_addr.clone().recipient(),
-            stop_timer_recipient: metrics_addr.recipient(),
+            stop_timer_recipient: metrics_addr.clone().recipient(),
+            custom_metric_recipient: metrics_addr.recipient(),
         }
     }
 
@@ -52,29 +65,320 @@ impl PerformanceRegistry {
             .map_err(|e| RuntimeError::panic(e.to_string()))
     }
 
-    /// Observe the execution of an action, timing it and recording metrics.
+    /// Counter of the model with the given name and labels
+    #[rune::function]
+    pub fn counter(&self, name: Ref<str>, labels: HashMap<String, Value>) -> VmResult<Counter> {
+        let key = vm_try!(metric_key(&self.model, &name, labels));
+        VmResult::Ok(Counter::new(key, self.custom_metric_recipient.clone()))
+    }
+
+    /// Gauge of the model with the given name and labels
+    #[rune::function]
+    pub fn gauge(&self, name: Ref<str>, labels: HashMap<String, Value>) -> VmResult<Gauge> {
+        let key = vm_try!(metric_key(&self.model, &name, labels));
+        VmResult::Ok(Gauge::new(key, self.custom_metric_recipient.clone()))
+    }
+
+    /// Value histogram of the model with the given name and labels, with buckets from 1 to one
+    /// million in 1-2-5 steps
+    #[rune::function]
+    pub fn histogram(&self, name: Ref<str>, labels: HashMap<String, Value>) -> VmResult<Histogram> {
+        let key = vm_try!(metric_key(&self.model, &name, labels));
+        VmResult::Ok(Histogram::new(
+            key,
+            Arc::from(DEFAULT_VALUE_BOUNDS.as_slice()),
+            self.custom_metric_recipient.clone(),
+        ))
+    }
+
+    /// Value histogram of the model with the given name, labels and buckets upper bounds
+    #[rune::function]
+    pub fn histogram_with_bounds(
+        &self,
+        name: Ref<str>,
+        labels: HashMap<String, Value>,
+        bounds: Vec<Value>,
+    ) -> VmResult<Histogram> {
+        let key = vm_try!(metric_key(&self.model, &name, labels));
+        let bounds = vm_try!(bounds
+            .into_iter()
+            .map(number)
+            .collect::<Result<Vec<_>, _>>());
+        VmResult::Ok(Histogram::new(
+            key,
+            Arc::from(bounds),
+            self.custom_metric_recipient.clone(),
+        ))
+    }
+
+    /// Status of an action result, `-1` for failed actions
+    fn outcome(result: &Result<Value, VmError>) -> ActionOutcome {
+        match result {
+            Ok(val) => OwnedValue::from_value(val.clone())
+                .map(|v| v.extract_status())
+                .unwrap_or(-1),
+            Err(_) => -1,
+        }
+    }
+
+    /// Observe the execution of a synchronous action, timing it and recording metrics.
     ///
-    /// The action is called synchronously (via `Function::call`), and the timing
-    /// is recorded asynchronously via the metrics manager.
+    /// The action is called synchronously (via `Function::call`): the timer is requested before
+    /// the call and stopped once the action returns by a task spawned on the current arbiter, so
+    /// that scripts don't need to await it.
     #[rune::function]
     pub fn observe(&self, name: Ref<str>, action: Function) -> VmResult<Value> {
-        let call_result: Result<Value, _> = action.call(()).into_result();
-        let (outcome, val_or_err) = match call_result {
-            Ok(val) => {
-                let status = OwnedValue::from_value(val.clone())
-                    .map(|v| v.extract_status())
-                    .unwrap_or(-1);
-                (status, Ok(val))
+        let timer_req = self
+            .start_timer_recipient
+            .send(StartActionTimer::new(&self.model, &name));
+        let start = Instant::now();
+        let result: Result<Value, VmError> = action.call(()).into_result();
+        let execution = ExecutionInfo {
+            elapsed: start.elapsed(),
+            outcome: Self::outcome(&result),
+        };
+
+        let stop_timer_recipient = self.stop_timer_recipient.clone();
+        actix::spawn(async move {
+            let out = match timer_req.await {
+                Ok(Ok(timer)) => stop_timer_recipient
+                    .send(StopActionTimer::new(timer, execution))
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|res| res.map_err(|e| e.to_string())),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = out {
+                log::warn!("Error recording action metrics - {e}");
             }
-            Err(e) => (-1, Err(e)),
+        });
+
+        match result {
+            Ok(val) => VmResult::Ok(val),
+            Err(e) => VmResult::Err(e),
+        }
+    }
+
+    /// Observe the execution of an asynchronous action, timing it and recording metrics.
+    ///
+    /// The future returned by the action is awaited (e.g. an `http::get` call), actions returning
+    /// a plain value are timed as `observe` does.
+    #[rune::function(instance, path = Self::observe_async)]
+    pub async fn observe_async(
+        this: Ref<Self>,
+        name: Ref<str>,
+        action: Function,
+    ) -> VmResult<Value> {
+        let timer = vm_try!(this.start_timer(&name).await);
+        let start = Instant::now();
+        let result = match action.call::<Value>(()).into_result() {
+            Ok(val) if val.type_hash() == Future::HASH => match val.into_future() {
+                Ok(future) => future.await.into_result(),
+                Err(e) => Err(VmError::from(e)),
+            },
+            result => result,
         };
+        let elapsed = start.elapsed();
+        vm_try!(
+            this.stop_timer(timer, elapsed, Self::outcome(&result))
+                .await
+        );
 
-        // We can't do async timing here since this is now a sync function.
-        // The timer start/stop is skipped for simplicity during the rune 0.14 migration.
-        // TODO: Restore async metrics timing with a compatible approach.
-        match val_or_err {
+        match result {
             Ok(val) => VmResult::Ok(val),
-            Err(e) => VmResult::err(RuntimeError::from(e)),
+            Err(e) => VmResult::Err(e),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    use actix::{Actor, Addr, Context, Handler, ResponseFuture};
+    use rune::{Diagnostics, Source, Sources, Value, Vm};
+
+    use crate::agent::metrics::custom::{CustomMetricKey, CustomMetricValue, ValueHistogram};
+    use crate::agent::metrics::manager::actor::MetricsManagerActor;
+    use crate::agent::metrics::manager::message::{
+        ActionTimerError, FetchCustomMetrics, RecordCustomMetric, StartActionTimer,
+        StartedActionTimer, StopActionTimer,
+    };
+    use crate::agent::metrics::timer::ActionOutcome;
+    use crate::simulation::rune::extension::metrics;
+
+    /// Forwards the timers to a metrics manager, recording the started actions and the outcomes
+    /// of the stopped ones
+    #[derive(Clone, Default)]
+    struct Recorded {
+        started: Arc<Mutex<Vec<String>>>,
+        stopped: Arc<Mutex<Vec<ActionOutcome>>>,
+    }
+
+    struct RecordingMetrics {
+        manager: Addr<MetricsManagerActor>,
+        recorded: Recorded,
+    }
+
+    impl Actor for RecordingMetrics {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<StartActionTimer> for RecordingMetrics {
+        type Result = ResponseFuture<Result<StartedActionTimer, ActionTimerError>>;
+
+        fn handle(&mut self, msg: StartActionTimer, _ctx: &mut Self::Context) -> Self::Result {
+            self.recorded
+                .started
+                .lock()
+                .unwrap()
+                .push(msg.action().to_string());
+            let req = self.manager.send(msg);
+            Box::pin(async move { req.await.expect("metrics manager not running") })
+        }
+    }
+
+    impl Handler<StopActionTimer> for RecordingMetrics {
+        type Result = ResponseFuture<Result<(), ActionTimerError>>;
+
+        fn handle(&mut self, msg: StopActionTimer, _ctx: &mut Self::Context) -> Self::Result {
+            self.recorded
+                .stopped
+                .lock()
+                .unwrap()
+                .push(msg.execution().outcome);
+            let req = self.manager.send(msg);
+            Box::pin(async move { req.await.expect("metrics manager not running") })
+        }
+    }
+
+    impl Handler<RecordCustomMetric> for RecordingMetrics {
+        type Result = ();
+
+        fn handle(&mut self, msg: RecordCustomMetric, _ctx: &mut Self::Context) -> Self::Result {
+            self.manager.do_send(msg);
+        }
+    }
+
+    async fn run_script<A>(metrics_addr: Addr<A>, script: &str) -> Value
+    where
+        A: Actor<Context = Context<A>>
+            + Handler<StartActionTimer>
+            + Handler<StopActionTimer>
+            + Handler<RecordCustomMetric>,
+    {
+        let mut context = rune::Context::with_default_modules().unwrap();
+        context
+            .install(metrics::module(metrics_addr).unwrap())
+            .unwrap();
+        let runtime = Arc::new(context.runtime().unwrap());
+        let mut sources = Sources::new();
+        sources
+            .insert(Source::new("script", script).unwrap())
+            .unwrap();
+        let mut diagnostics = Diagnostics::new();
+        let unit = rune::prepare(&mut sources)
+            .with_context(&context)
+            .with_diagnostics(&mut diagnostics)
+            .build()
+            .unwrap();
+
+        Vm::new(runtime, Arc::new(unit))
+            .async_call(["main"], ())
+            .await
+            .unwrap()
+    }
+
+    #[actix::test]
+    async fn observed_actions_are_timed() {
+        let recorded = Recorded::default();
+        let metrics_addr = RecordingMetrics {
+            manager: MetricsManagerActor::default().start(),
+            recorded: recorded.clone(),
+        }
+        .start();
+
+        let out = run_script(
+            metrics_addr,
+            r#"
+            pub async fn main() {
+                let registry = hailstorm::metrics::new("Demo");
+                let sync = registry.observe("sync", || 200);
+                let run = registry.observe_async("async", async || 404).await;
+                let plain = registry.observe_async("plain", || 201).await;
+                [sync, run, plain]
+            }
+            "#,
+        )
+        .await;
+        let out: Vec<i64> = rune::from_value(out).unwrap();
+        assert_eq!(out, vec![200, 404, 201]);
+
+        // the sync action timer is stopped by a spawned task
+        actix::clock::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(
+            *recorded.started.lock().unwrap(),
+            vec!["sync", "async", "plain"]
+        );
+        let mut stopped = recorded.stopped.lock().unwrap().clone();
+        stopped.sort();
+        assert_eq!(stopped, vec![200, 201, 404]);
+    }
+
+    #[actix::test]
+    async fn custom_metrics_are_aggregated() {
+        let metrics_addr = MetricsManagerActor::default().start();
+
+        run_script(
+            metrics_addr.clone(),
+            r#"
+            pub async fn main() {
+                let registry = hailstorm::metrics::new("Demo");
+                let orders = registry.counter("orders_placed", #{region: "eu"});
+                orders.inc();
+                orders.add(2);
+                let depth = registry.gauge("queue_depth", #{});
+                depth.set(10);
+                depth.dec();
+                let cart = registry.histogram_with_bounds("cart_size", #{}, [1, 5]);
+                cart.record(3);
+                cart.record(7.5);
+                registry.gauge("orders_placed", #{region: "eu"}).set(1);
+            }
+            "#,
+        )
+        .await;
+
+        let metrics = metrics_addr
+            .send(FetchCustomMetrics)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|snapshot| (snapshot.key, snapshot.value))
+            .collect::<BTreeMap<_, _>>();
+        let key = |name: &str, labels: &[(&str, &str)]| CustomMetricKey {
+            model: String::from("Demo"),
+            name: name.to_string(),
+            labels: labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+        let mut cart = ValueHistogram::new(vec![1.0, 5.0]);
+        cart.record(3.0);
+        cart.record(7.5);
+        assert_eq!(
+            metrics,
+            BTreeMap::from([
+                (
+                    key("orders_placed", &[("region", "eu")]),
+                    CustomMetricValue::Counter(3.0)
+                ),
+                (key("queue_depth", &[]), CustomMetricValue::Gauge(9.0)),
+                (key("cart_size", &[]), CustomMetricValue::Histogram(cart)),
+            ])
+        );
+    }
+}
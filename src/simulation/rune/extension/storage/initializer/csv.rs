@@ -32,7 +32,7 @@ pub struct CsvStorageInitializer {
 ///
 /// - `id`: A `u32` representing the unique identifier of the entry, typically corresponding to a bot ID.
 /// - `values`: A `HashMap<String, String>` storing all other CSV columns as key-value pairs, allowing for
-/// dynamic and flexible data structures.
+///   dynamic and flexible data structures.
 #[derive(Deserialize)]
 struct CsvEntry {
     id: u32,
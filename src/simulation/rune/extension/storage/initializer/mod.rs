@@ -12,16 +12,16 @@ pub mod empty;
 /// # Methods
 ///
 /// - `initial_values_for`: Given a storage name and bot ID, returns a `HashMap` containing the
-/// initial key-value pairs to populate the storage.
+///   initial key-value pairs to populate the storage.
 pub trait StorageInitializerRegistry {
     /// Provides initial values for a specified storage and bot ID.
     ///
     /// # Parameters
     ///
     /// - `name`: A string slice representing the name of the storage. This could correspond to different
-    /// types or categories of data needed by bots.
+    ///   types or categories of data needed by bots.
     /// - `bot_id`: A 32-bit unsigned integer representing the unique identifier of the bot.
-    /// This allows for customization of initial data on a per-bot basis.
+    ///   This allows for customization of initial data on a per-bot basis.
     ///
     /// # Returns
     ///
@@ -12,7 +12,7 @@ use rune::{ContextError, Module};
 /// # Type Parameters
 ///
 /// - `Initializer`: The type of the storage initializer, which determines how storage will be
-/// populated at the start of the simulation or application.
+///   populated at the start of the simulation or application.
 ///
 /// # Default Implementation
 ///
@@ -34,7 +34,7 @@ impl<I> StorageModuleArgs<I> {
     /// # Parameters
     ///
     /// - `initializer`: An instance of the `Initializer` type, responsible for providing initial values
-    /// to the storage based on predefined logic or data sources.
+    ///   to the storage based on predefined logic or data sources.
     ///
     /// # Returns
     ///
@@ -65,8 +65,8 @@ impl<I> StorageModuleArgs<I> {
 /// # Type Parameters
 ///
 /// - `Initializer`: The type of the storage initializer. Must implement `StorageInitializerRegistry`
-/// and be thread-safe (`Send + Sync`). It's also required to have a static lifetime since it will be
-/// used across the context of the Rune VM.
+///   and be thread-safe (`Send + Sync`). It's also required to have a static lifetime since it will be
+///   used across the context of the Rune VM.
 ///
 /// # Parameters
 ///
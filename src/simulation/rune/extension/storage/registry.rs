@@ -37,10 +37,10 @@ impl StorageSlice {
     pub fn write(&mut self, key: String, value: String) {
         self.storage
             .entry(self.bot_id)
-            .or_insert_with(Default::default)
+            .or_default()
             .storages
             .entry(self.name.clone())
-            .or_insert_with(Default::default)
+            .or_default()
             .values
             .insert(key, value);
     }
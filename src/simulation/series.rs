@@ -0,0 +1,298 @@
+use std::fmt;
+use std::io::Read;
+
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::error::TimeSeriesError;
+
+/// How a time series is evaluated between two recorded points.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    /// Straight line between the two points.
+    #[default]
+    Linear,
+    /// Value of the previous point, until the next one.
+    Step,
+}
+
+/// Point of a recorded time series.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SeriesPoint {
+    /// Seconds since the series start.
+    pub t: f64,
+    /// Recorded value (e.g. concurrent users).
+    pub value: f64,
+}
+
+fn unit_scale() -> f64 {
+    1.0
+}
+
+/// Recorded traffic curve replayed as a load shape.
+///
+/// The series is evaluated on the time elapsed since the phase start: before the first point it
+/// has the first value, after the last point it holds the last one. `time_scale` multiplies the
+/// recorded timestamps (e.g. `1.0 / 24.0` replays a day in an hour) and `amplitude_scale` the
+/// recorded values.
+///
+/// # Examples
+///
+/// ```
+/// use hailstorm::simulation::series::{Interpolation, TimeSeries};
+///
+/// let csv = "t,value\n0,100\n60,400\n120,200\n";
+/// let series = TimeSeries::from_csv(csv.as_bytes()).unwrap().amplitude_scale(0.5);
+/// assert_eq!(series.value_at(30.0), 125.0);
+///
+/// let series = series.interpolation(Interpolation::Step).time_scale(2.0);
+/// assert_eq!(series.value_at(150.0), 200.0);
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "TimeSeriesDef")]
+pub struct TimeSeries {
+    points: Vec<SeriesPoint>,
+    #[serde(default)]
+    interpolation: Interpolation,
+    #[serde(default = "unit_scale")]
+    time_scale: f64,
+    #[serde(default = "unit_scale")]
+    amplitude_scale: f64,
+}
+
+/// Unchecked [`TimeSeries`] as deserialized, validated converting it
+#[derive(Deserialize)]
+struct TimeSeriesDef {
+    points: Vec<SeriesPoint>,
+    #[serde(default)]
+    interpolation: Interpolation,
+    #[serde(default = "unit_scale")]
+    time_scale: f64,
+    #[serde(default = "unit_scale")]
+    amplitude_scale: f64,
+}
+
+impl TryFrom<TimeSeriesDef> for TimeSeries {
+    type Error = TimeSeriesError;
+
+    fn try_from(def: TimeSeriesDef) -> Result<Self, Self::Error> {
+        Ok(Self::new(def.points)?
+            .interpolation(def.interpolation)
+            .time_scale(def.time_scale)
+            .amplitude_scale(def.amplitude_scale))
+    }
+}
+
+impl TimeSeries {
+    /// Series of the given points, linearly interpolated and not scaled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are no points or a point is not finite.
+    pub fn new(mut points: Vec<SeriesPoint>) -> Result<Self, TimeSeriesError> {
+        if points.is_empty() {
+            return Err(TimeSeriesError::Empty);
+        }
+        if let Some(point) = points
+            .iter()
+            .find(|point| !point.t.is_finite() || !point.value.is_finite())
+        {
+            return Err(TimeSeriesError::InvalidPoint(point.t, point.value));
+        }
+        points.sort_by(|a, b| a.t.total_cmp(&b.t));
+        Ok(Self {
+            points,
+            interpolation: Interpolation::default(),
+            time_scale: 1.0,
+            amplitude_scale: 1.0,
+        })
+    }
+
+    /// Series of the given points skipping the non finite ones, without points it evaluates to 0
+    pub(crate) fn lenient(points: impl IntoIterator<Item = SeriesPoint>) -> Self {
+        let mut points = points
+            .into_iter()
+            .filter(|point| point.t.is_finite() && point.value.is_finite())
+            .collect::<Vec<_>>();
+        points.sort_by(|a, b| a.t.total_cmp(&b.t));
+        Self {
+            points,
+            interpolation: Interpolation::default(),
+            time_scale: 1.0,
+            amplitude_scale: 1.0,
+        }
+    }
+
+    /// Load a series from a CSV with the `t` and `value` columns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a record cannot be parsed or the series is not valid.
+    pub fn from_csv(reader: impl Read) -> Result<Self, TimeSeriesError> {
+        let points = csv::Reader::from_reader(reader)
+            .deserialize()
+            .collect::<Result<Vec<SeriesPoint>, _>>()?;
+        Self::new(points)
+    }
+
+    /// Load a series from a JSON array of `{"t": <seconds>, "value": <value>}` points.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON cannot be parsed or the series is not valid.
+    pub fn from_json(reader: impl Read) -> Result<Self, TimeSeriesError> {
+        let points: Vec<SeriesPoint> = serde_json::from_reader(reader)?;
+        Self::new(points)
+    }
+
+    /// Set the interpolation between the recorded points
+    pub fn interpolation(self, interpolation: Interpolation) -> Self {
+        Self {
+            interpolation,
+            ..self
+        }
+    }
+
+    /// Multiply the recorded timestamps by the given factor
+    pub fn time_scale(self, time_scale: f64) -> Self {
+        Self { time_scale, ..self }
+    }
+
+    /// Multiply the recorded values by the given factor
+    pub fn amplitude_scale(self, amplitude_scale: f64) -> Self {
+        Self {
+            amplitude_scale,
+            ..self
+        }
+    }
+
+    /// immutable points reference
+    pub fn points_ref(&self) -> &[SeriesPoint] {
+        &self.points
+    }
+
+    /// Series interpolation
+    pub fn interpolation_ref(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    /// Factor applied to the recorded timestamps
+    pub fn time_scale_ref(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Factor applied to the recorded values
+    pub fn amplitude_scale_ref(&self) -> f64 {
+        self.amplitude_scale
+    }
+
+    /// Scaled value of the series at `t` seconds
+    pub fn value_at(&self, t: f64) -> f64 {
+        let recorded_t = if self.time_scale > 0.0 {
+            t / self.time_scale
+        } else {
+            t
+        };
+        let next_idx = self.points.partition_point(|point| point.t <= recorded_t);
+        let value = match (
+            next_idx.checked_sub(1).map(|idx| self.points[idx]),
+            self.points.get(next_idx),
+        ) {
+            (Some(prev), Some(next)) => match self.interpolation {
+                Interpolation::Linear => {
+                    let progress = (recorded_t - prev.t) / (next.t - prev.t);
+                    prev.value + (next.value - prev.value) * progress
+                }
+                Interpolation::Step => prev.value,
+            },
+            (Some(last), None) => last.value,
+            (None, Some(first)) => first.value,
+            (None, None) => 0.0,
+        };
+        value * self.amplitude_scale
+    }
+}
+
+impl fmt::Display for TimeSeries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let interpolation = match self.interpolation {
+            Interpolation::Linear => "linear",
+            Interpolation::Step => "step",
+        };
+        write!(
+            f,
+            "series of {} points ({interpolation}, time x{}, amplitude x{})",
+            self.points.len(),
+            self.time_scale,
+            self.amplitude_scale
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn points(values: &[(f64, f64)]) -> Vec<SeriesPoint> {
+        values
+            .iter()
+            .map(|(t, value)| SeriesPoint {
+                t: *t,
+                value: *value,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn interpolation_and_bounds() {
+        let series = TimeSeries::new(points(&[(20.0, 300.0), (10.0, 100.0)])).unwrap();
+        assert_eq!(series.value_at(0.0), 100.0);
+        assert_eq!(series.value_at(15.0), 200.0);
+        assert_eq!(series.value_at(30.0), 300.0);
+
+        let series = series.interpolation(Interpolation::Step);
+        assert_eq!(series.value_at(15.0), 100.0);
+        assert_eq!(series.value_at(20.0), 300.0);
+    }
+
+    #[test]
+    fn scaling() {
+        let series = TimeSeries::new(points(&[(0.0, 0.0), (3600.0, 1000.0)]))
+            .unwrap()
+            .time_scale(0.1)
+            .amplitude_scale(2.0);
+        assert_eq!(series.value_at(180.0), 1000.0);
+        assert_eq!(series.value_at(360.0), 2000.0);
+    }
+
+    #[test]
+    fn load_from_json() {
+        let series =
+            TimeSeries::from_json(r#"[{"t": 0, "value": 5}, {"t": 10, "value": 15}]"#.as_bytes())
+                .unwrap();
+        assert_eq!(series.value_at(5.0), 10.0);
+
+        let series: TimeSeries = serde_json::from_str(
+            r#"{"points": [{"t": 10, "value": 0}, {"t": 0, "value": 10}], "interpolation": "step"}"#,
+        )
+        .unwrap();
+        assert_eq!(series.value_at(5.0), 10.0);
+        assert!(serde_json::from_str::<TimeSeries>(r#"{"points": []}"#).is_err());
+    }
+
+    #[test]
+    fn invalid_series() {
+        assert!(matches!(
+            TimeSeries::new(vec![]),
+            Err(TimeSeriesError::Empty)
+        ));
+        assert!(matches!(
+            TimeSeries::new(points(&[(0.0, f64::NAN)])),
+            Err(TimeSeriesError::InvalidPoint(..))
+        ));
+        assert!(matches!(
+            TimeSeries::from_csv("t,value\n0,ten\n".as_bytes()),
+            Err(TimeSeriesError::Csv(_))
+        ));
+    }
+}
@@ -1,26 +1,33 @@
+use crate::simulation::series::TimeSeries;
+use serde::{Deserialize, Serialize};
 use std::f64::consts::PI;
+use std::fmt;
 use std::ops::{Mul, Sub};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds in a day, the period of the `tod` shape variable
+pub const DAY_SECS: f64 = 86_400.0;
 
 /// Parses a given string representation of a mathematical function into a callable function.
 ///
 /// This function leverages the `meval` crate to parse and compile a string into a function
 /// that takes a `f64` as input and returns a `f64`. It extends the parsing context with custom
-/// shape functions (`rect`, `tri`, `step`, `trapz`, `costrapz`) before parsing, allowing these
-/// to be used within the input string.
+/// shape functions (`rect`, `tri`, `step`, `trapz`, `costrapz`, `rand`, `noise`, `burst`) before
+/// parsing, allowing these to be used within the input string.
 ///
 /// # Parameters
 ///
 /// * `fun`: A `String` representing the mathematical function to be parsed. This string can
-/// include standard mathematical operations, numbers, and the custom shape functions defined
-/// within this function.
+///   include standard mathematical operations, numbers, and the custom shape functions defined
+///   within this function.
 ///
 /// # Returns
 ///
 /// A `Result` which is:
 /// - `Ok`: Contains the parsed function as `impl Fn(f64) -> f64`. This function can be called
-/// with a `f64` argument to evaluate the expression at that point.
+///   with a `f64` argument to evaluate the expression at that point.
 /// - `Err`: An error of type `meval::Error` if the string cannot be parsed into a valid
-/// mathematical expression or function.
+///   mathematical expression or function.
 ///
 /// # Custom Shape Functions
 ///
@@ -30,6 +37,17 @@ use std::ops::{Mul, Sub};
 /// - `trapz(x, b_low, b_sup)`: Trapezoidal function that depends on x and bounds b_low and b_sup.
 /// - `costrapz(x, b_low, b_sup)`: Cosine-tapered trapezoidal function, also depending on x and bounds.
 ///
+/// # Stochastic Functions
+///
+/// The random functions hash their arguments instead of drawing from a generator, so every agent
+/// gets the same value for the same `x` and `seed`:
+///
+/// - `rand(x, seed)`: Uniform value in `[0, 1)`, constant over each unit interval of x.
+/// - `noise(x, seed)`: Smooth noise in `[-1, 1]`, cosine interpolated between unit intervals of x.
+/// - `burst(x, rate, width, seed)`: Returns 1.0 during bursts and 0.0 otherwise. x is split in
+///   windows of `width` and each window is a burst with probability `1 - exp(-rate * width)`, as
+///   for Poisson arrivals of `rate` bursts per unit of x.
+///
 /// # Examples
 ///
 /// ```
@@ -57,6 +75,83 @@ use std::ops::{Mul, Sub};
 /// of the returned function depend on the `meval` crate's ability to parse and evaluate mathematical
 /// expressions safely.
 pub fn parse_shape_fun(fun: String) -> Result<impl Fn(f64) -> f64, meval::Error> {
+    let expr: meval::Expr = fun.parse()?;
+    expr.bind_with_context(shape_context(), "t")
+}
+
+/// Parses the shape of a simulation phase into a callable function.
+///
+/// Same as [`parse_shape_fun`], but the returned function takes four arguments bound to the
+/// shape variables:
+///
+/// - `t`: seconds elapsed since the phase start.
+/// - `t_total`: seconds elapsed since the simulation start.
+/// - `duration`: phase duration in seconds (infinite for simulations without phases).
+/// - `tod`: wall-clock time of day in seconds since the UTC midnight, see [`time_of_day`].
+///
+/// # Examples
+///
+/// ```
+/// use hailstorm::simulation::shape::parse_phase_shape_fun;
+///
+/// let ramp = parse_phase_shape_fun(String::from("100 * t / duration")).unwrap();
+/// assert_eq!(ramp(30.0, 90.0, 60.0, 0.0), 50.0);
+///
+/// // peak at noon, lowest at midnight
+/// let diurnal = parse_phase_shape_fun(String::from("100 - 50 * cos(2 * pi * tod / 86400)")).unwrap();
+/// assert_eq!(diurnal(0.0, 0.0, 60.0, 43_200.0), 150.0);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the string cannot be parsed into a valid mathematical expression.
+pub fn parse_phase_shape_fun(
+    fun: String,
+) -> Result<impl Fn(f64, f64, f64, f64) -> f64, meval::Error> {
+    let expr: meval::Expr = fun.parse()?;
+    expr.bind4_with_context(shape_context(), "t", "t_total", "duration", "tod")
+}
+
+/// Seconds elapsed since the UTC midnight at the given time, the value of the `tod` shape
+/// variable.
+///
+/// UTC is used so that agents in different time zones agree on the shape value, diurnal patterns
+/// of a given zone shift `tod` by the zone offset (e.g. `(tod + 3600) % 86400` for UTC+1).
+pub fn time_of_day(ts: SystemTime) -> f64 {
+    ts.duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs_f64() % DAY_SECS)
+        .unwrap_or(0.0)
+}
+
+/// Uniform value in `[0, 1)` derived from the given cell and seed (splitmix64 finalizer)
+fn hash_unit(cell: f64, seed: f64) -> f64 {
+    let mut x = (cell as i64 as u64) ^ seed.to_bits().wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn noise(x: f64, seed: f64) -> f64 {
+    let cell = x.floor();
+    let (from, to) = (hash_unit(cell, seed), hash_unit(cell + 1.0, seed));
+    let eased = (1.0 - (PI * (x - cell)).cos()) / 2.0;
+    (from + (to - from) * eased) * 2.0 - 1.0
+}
+
+fn burst(x: f64, rate: f64, width: f64, seed: f64) -> f64 {
+    if !(width > 0.0 && rate > 0.0) {
+        return 0.0;
+    }
+    let probability = 1.0 - (-rate * width).exp();
+    if hash_unit((x / width).floor(), seed) < probability {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn shape_context() -> meval::Context<'static> {
     let mut ctx = meval::Context::new(); // built-ins
     ctx.func("rect", |x| {
         if x.abs() > 0.5 {
@@ -98,10 +193,164 @@ pub fn parse_shape_fun(fun: String) -> Result<impl Fn(f64) -> f64, meval::Error>
                 .cos()
                 .powi(2)
         }
-    });
+    })
+    .func2("rand", |x, seed| hash_unit(x.floor(), seed))
+    .func2("noise", noise)
+    .funcn("burst", |xs| burst(xs[0], xs[1], xs[2], xs[3]), 4);
+    ctx
+}
+
+/// How a load stage moves from the previous target to its own one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    /// Constant rate ramp.
+    #[default]
+    Linear,
+    /// Jump to the target at the stage start.
+    Step,
+    /// Cosine ramp, smooth at both ends.
+    Cosine,
+}
+
+/// Stage of a declarative load profile, reaching `target` bots in `duration`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LoadStage {
+    /// Bots count at the end of the stage.
+    pub target: f64,
+    /// Stage duration.
+    pub duration: Duration,
+    /// Interpolation from the previous target.
+    pub easing: Easing,
+}
+
+impl LoadStage {
+    /// Linear stage reaching the target in the given duration
+    pub fn new(target: f64, duration: Duration) -> Self {
+        Self {
+            target,
+            duration,
+            easing: Easing::Linear,
+        }
+    }
 
-    let expr: meval::Expr = fun.parse()?;
-    expr.bind_with_context(ctx, "t")
+    /// Set the stage easing
+    pub fn easing(self, easing: Easing) -> Self {
+        Self { easing, ..self }
+    }
+}
+
+impl fmt::Display for LoadStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let easing = match self.easing {
+            Easing::Linear => "linear",
+            Easing::Step => "step",
+            Easing::Cosine => "cosine",
+        };
+        write!(
+            f,
+            "{easing} to {} in {}s",
+            self.target,
+            self.duration.as_secs_f64()
+        )
+    }
+}
+
+/// Builds the shape of a stage-based load profile.
+///
+/// The profile starts from zero bots at `t = 0` and goes through the stages in order, each one
+/// moving from the previous target to its own with the stage easing. The last target is held
+/// once all the stages are completed.
+///
+/// # Examples
+///
+/// ```
+/// use hailstorm::simulation::shape::{stages_shape_fun, LoadStage};
+/// use std::time::Duration;
+///
+/// let ramp_hold_down = stages_shape_fun(vec![
+///     LoadStage::new(100.0, Duration::from_secs(60)),
+///     LoadStage::new(100.0, Duration::from_secs(300)),
+///     LoadStage::new(0.0, Duration::from_secs(60)),
+/// ]);
+/// assert_eq!(ramp_hold_down(30.0), 50.0);
+/// assert_eq!(ramp_hold_down(200.0), 100.0);
+/// assert_eq!(ramp_hold_down(390.0), 50.0);
+/// assert_eq!(ramp_hold_down(600.0), 0.0);
+/// ```
+pub fn stages_shape_fun(stages: Vec<LoadStage>) -> impl Fn(f64) -> f64 {
+    move |t| {
+        if t < 0.0 {
+            return 0.0;
+        }
+        let mut stage_start = 0.0;
+        let mut from = 0.0;
+        for stage in stages.iter() {
+            let duration = stage.duration.as_secs_f64();
+            if t < stage_start + duration {
+                let progress = (t - stage_start) / duration;
+                let eased = match stage.easing {
+                    Easing::Linear => progress,
+                    Easing::Step => 1.0,
+                    Easing::Cosine => (1.0 - (PI * progress).cos()) / 2.0,
+                };
+                return from + (stage.target - from) * eased;
+            }
+            stage_start += duration;
+            from = stage.target;
+        }
+        from
+    }
+}
+
+/// Shape function of a model taking `t`, `t_total`, `duration` and `tod`, see
+/// [`ModelShape::parse`]
+pub type ShapeFn = Box<dyn Fn(f64, f64, f64, f64) -> f64>;
+
+/// Load shape of a bot model: an expression (see [`parse_phase_shape_fun`]), a stage-based
+/// profile (see [`stages_shape_fun`]) or a recorded time series, the last two evaluated on the
+/// time elapsed since the phase start.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModelShape {
+    /// Shape expression.
+    Expression(String),
+    /// Stage-based load profile.
+    Stages(Vec<LoadStage>),
+    /// Recorded traffic curve.
+    Series(TimeSeries),
+}
+
+impl ModelShape {
+    /// Parses the shape into a function of `t`, `t_total`, `duration` and `tod`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the shape is an expression that cannot be parsed.
+    pub fn parse(self) -> Result<ShapeFn, meval::Error> {
+        Ok(match self {
+            ModelShape::Expression(expression) => Box::new(parse_phase_shape_fun(expression)?),
+            ModelShape::Stages(stages) => {
+                let profile = stages_shape_fun(stages);
+                Box::new(move |t, _t_total, _duration, _tod| profile(t))
+            }
+            ModelShape::Series(series) => {
+                Box::new(move |t, _t_total, _duration, _tod| series.value_at(t))
+            }
+        })
+    }
+}
+
+impl fmt::Display for ModelShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelShape::Expression(expression) => f.write_str(expression),
+            ModelShape::Stages(stages) => {
+                let stages = stages.iter().map(LoadStage::to_string).collect::<Vec<_>>();
+                write!(f, "[{}]", stages.join(", "))
+            }
+            ModelShape::Series(series) => series.fmt(f),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -120,7 +369,6 @@ mod test {
             let fun = parse_shape_fun(String::from(f_name)).expect("Error parsing fun");
 
             let coord = (0..=512)
-                .into_iter()
                 .map(|x| {
                     let y = 224.0 - fun(x as f64 / 256.0 - 1.0) * 192.0;
                     format!("{},{:.2}", x, y)
@@ -130,4 +378,78 @@ mod test {
             println!("{f_name}: {coord}");
         }
     }
+
+    #[test]
+    fn test_phase_shape_variables() {
+        let fun = parse_phase_shape_fun(String::from(
+            "t + 10 * t_total + 100 * duration + 1000 * tod",
+        ))
+        .expect("Error parsing fun");
+        assert_eq!(fun(1.0, 2.0, 3.0, 4.0), 4321.0);
+        assert!(parse_phase_shape_fun(String::from("t + x")).is_err());
+    }
+
+    #[test]
+    fn test_stochastic_functions_are_seeded() {
+        let parse = |f| parse_shape_fun(String::from(f)).expect("Error parsing fun");
+        let (agent_a, agent_b) = (parse("noise(t / 10, 7)"), parse("noise(t / 10, 7)"));
+        let other_seed = parse("noise(t / 10, 8)");
+        let samples = (0..1000).map(|x| x as f64 * 0.37).collect::<Vec<_>>();
+        assert!(samples.iter().all(|t| agent_a(*t) == agent_b(*t)));
+        assert!(samples.iter().any(|t| agent_a(*t) != other_seed(*t)));
+        assert!(samples.iter().all(|t| (-1.0..=1.0).contains(&agent_a(*t))));
+
+        let rand = parse("rand(t, 1)");
+        assert_eq!(rand(3.1), rand(3.9));
+        assert!(samples.iter().all(|t| (0.0..1.0).contains(&rand(*t))));
+    }
+
+    #[test]
+    fn test_burst_rate() {
+        // one 10s burst every 1000s on average
+        let burst = parse_shape_fun(String::from("burst(t, 0.001, 10, 42)")).unwrap();
+        let windows = 100_000;
+        let bursts = (0..windows)
+            .filter(|window| burst(*window as f64 * 10.0 + 5.0) == 1.0)
+            .count();
+        let expected = windows as f64 * (1.0 - (-0.01f64).exp());
+        assert!((bursts as f64 - expected).abs() < expected * 0.1);
+        assert_eq!(burst(12.0), burst(18.0));
+        assert!(parse_shape_fun(String::from("burst(t, 0.001, 10)")).is_err());
+    }
+
+    #[test]
+    fn test_time_of_day() {
+        let ts = UNIX_EPOCH + Duration::from_secs(3 * 86_400 + 3_600);
+        assert_eq!(time_of_day(ts), 3_600.0);
+    }
+
+    #[test]
+    fn test_stages_easing() {
+        let stage = |easing| {
+            stages_shape_fun(vec![
+                LoadStage::new(100.0, Duration::from_secs(10)).easing(easing)
+            ])
+        };
+        assert_eq!(stage(Easing::Linear)(2.5), 25.0);
+        assert_eq!(stage(Easing::Step)(0.0), 100.0);
+        assert!((stage(Easing::Cosine)(2.5) - 14.64).abs() < 0.01);
+        assert!((stage(Easing::Cosine)(5.0) - 50.0).abs() < 1e-9);
+        for easing in [Easing::Linear, Easing::Step, Easing::Cosine] {
+            assert_eq!(stage(easing)(-1.0), 0.0);
+            assert_eq!(stage(easing)(10.0), 100.0);
+        }
+    }
+
+    #[test]
+    fn test_model_shape_stages_follow_phase_time() {
+        let shape = ModelShape::Stages(vec![LoadStage::new(10.0, Duration::from_secs(10))])
+            .parse()
+            .unwrap();
+        assert_eq!(shape(5.0, 100.0, f64::INFINITY, 0.0), 5.0);
+        assert_eq!(
+            ModelShape::Stages(vec![LoadStage::new(10.0, Duration::from_secs(10))]).to_string(),
+            "[linear to 10 in 10s]"
+        );
+    }
 }
@@ -26,7 +26,7 @@ where
     ///
     /// - `dur`: The `Duration` between invocations of the closure.
     /// - `f`: A closure that is called each interval with an `Addr<A>` if the actor is still alive. The
-    /// closure should return a `Future`, which will be awaited before sleeping until the next interval.
+    ///   closure should return a `Future`, which will be awaited before sleeping until the next interval.
     ///
     /// # Returns
     ///
@@ -77,7 +77,6 @@ impl<A> WeakContext<A> for Context<A> where A: Actor<Context = Self> {}
 #[cfg(test)]
 mod tests {
     use super::*;
-    use actix::prelude::*;
     use std::sync::{Arc, Mutex};
     use tokio::time::sleep;
 
@@ -101,7 +100,7 @@ mod tests {
     #[actix::test]
     async fn test_periodic_task_runs() {
         let counter = Arc::new(Mutex::new(0));
-        let actor = TestActor(counter.clone()).start();
+        let _actor = TestActor(counter.clone()).start();
 
         // Wait to ensure the task has time to run a few times.
         sleep(Duration::from_millis(350)).await;
@@ -21,7 +21,7 @@ pub trait VarintDecode: Sized {
 impl VarintEncode for u32 {
     fn to_varint(&self) -> Vec<u8> {
         let filled_bits = 32 - self.leading_zeros() as usize;
-        let result_len = max((filled_bits + 6) / 7, 1);
+        let result_len = max(filled_bits.div_ceil(7), 1);
         let mut result = Vec::with_capacity(result_len);
         let first_grp = 5 - result_len;
         for g in first_grp..5 {